@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use criterion::{Criterion, criterion_group, criterion_main};
 use repeater::crud::DB;
-use repeater::parser::register_all_cards;
+use repeater::parser::{CollectOptions, register_all_cards};
 use std::hint::black_box;
 use tokio::runtime::Runtime;
 
@@ -17,9 +17,10 @@ fn bench_register_all_cards(c: &mut Criterion) {
             let db = Arc::clone(&db);
             let paths = paths.clone();
             async move {
-                let (cards, stats) = register_all_cards(db.as_ref(), paths)
-                    .await
-                    .expect("failed to register cards");
+                let (cards, stats) =
+                    register_all_cards(db.as_ref(), paths, CollectOptions::default())
+                        .await
+                        .expect("failed to register cards");
                 black_box(cards);
                 black_box(stats);
             }
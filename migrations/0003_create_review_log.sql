@@ -0,0 +1,11 @@
+-- Create the review log table.
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS review_log (
+    id INTEGER PRIMARY KEY,
+    card_hash TEXT NOT NULL,
+    reviewed_at TEXT NOT NULL,
+    review_status TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_review_log_reviewed_at ON review_log(reviewed_at);
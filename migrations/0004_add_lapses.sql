@@ -0,0 +1,2 @@
+-- Track how often a graduated card has been forgotten.
+ALTER TABLE cards ADD COLUMN lapses INTEGER NOT NULL DEFAULT 0;
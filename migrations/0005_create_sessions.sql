@@ -0,0 +1,11 @@
+-- One row per drill session that graded at least one card.
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL,
+    ended_at TEXT NOT NULL,
+    cards_reviewed INTEGER NOT NULL,
+    passed INTEGER NOT NULL,
+    failed INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_sessions_ended_at ON sessions(ended_at);
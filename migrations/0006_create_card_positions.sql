@@ -0,0 +1,7 @@
+-- Where each card was last seen, so `--track-edits` can recognise an edited card.
+CREATE TABLE IF NOT EXISTS card_positions (
+    file_path TEXT NOT NULL,
+    start_line INTEGER NOT NULL,
+    card_hash TEXT NOT NULL,
+    PRIMARY KEY (file_path, start_line)
+) STRICT;
@@ -0,0 +1,10 @@
+-- The card's schedule just before each review, so `undo-last` can restore it.
+-- NULL `prev_review_count` marks reviews logged before this was recorded.
+ALTER TABLE review_log ADD COLUMN prev_last_reviewed_at TEXT;
+ALTER TABLE review_log ADD COLUMN prev_stability REAL;
+ALTER TABLE review_log ADD COLUMN prev_difficulty REAL;
+ALTER TABLE review_log ADD COLUMN prev_interval_raw REAL;
+ALTER TABLE review_log ADD COLUMN prev_interval_days INTEGER;
+ALTER TABLE review_log ADD COLUMN prev_due_date TEXT;
+ALTER TABLE review_log ADD COLUMN prev_review_count INTEGER;
+ALTER TABLE review_log ADD COLUMN prev_lapses INTEGER;
@@ -0,0 +1,370 @@
+use clap::ValueEnum;
+use ignore::WalkBuilder;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use anyhow::{Context, Result, bail};
+
+use crate::crud::DB;
+use crate::fsrs::Performance;
+use crate::palette::Palette;
+use crate::parser::{cards_from_md, extract_media};
+use crate::utils::{is_markdown, pluralize};
+
+/// Which cards `pack --only` keeps, by their scheduling state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PackOnly {
+    /// Reviewed cards whose due date has passed.
+    Due,
+    /// Cards that have never been reviewed.
+    New,
+}
+
+/// Zips every markdown file under `dir`, plus the media they reference, into `out`.
+pub fn pack(dir: &Path, out: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("Deck directory does not exist: {}", dir.display());
+    }
+    let (markdown_files, media_files) = collect_bundle_files(dir)?;
+    if markdown_files.is_empty() {
+        bail!("No markdown files found under {}", dir.display());
+    }
+    let markdown = markdown_files
+        .into_iter()
+        .map(|relative| {
+            let contents = fs::read(dir.join(&relative))?;
+            Ok((relative, contents))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    write_bundle(dir, out, &markdown, &media_files)
+}
+
+/// Like `pack`, but each markdown file keeps only the cards matching `only`
+/// (plus any text before its first card), and files left without cards are
+/// dropped.
+pub async fn pack_only(db: &DB, dir: &Path, out: &Path, only: PackOnly) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("Deck directory does not exist: {}", dir.display());
+    }
+    let now = chrono::Utc::now();
+    let mut markdown = Vec::new();
+    let mut media_files = BTreeSet::new();
+    for relative in markdown_files(dir)? {
+        let path = dir.join(&relative);
+        let cards = cards_from_md(&path)?;
+        // Cards the database has never seen are new.
+        let performances = db.get_performances(&cards).await?;
+        let keep: HashSet<(usize, usize)> = cards
+            .iter()
+            .filter(|card| {
+                let performance = performances
+                    .get(&card.card_hash)
+                    .copied()
+                    .unwrap_or_default();
+                match (only, performance) {
+                    (PackOnly::New, Performance::New) => true,
+                    (PackOnly::Due, Performance::Reviewed(reviewed)) => {
+                        !card.directives.suspended && reviewed.due_date <= now
+                    }
+                    _ => false,
+                }
+            })
+            .map(|card| card.file_card_range)
+            .collect();
+        if keep.is_empty() {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let first_card = cards.iter().map(|card| card.file_card_range.0).min();
+        let kept = keep_card_lines(&contents, first_card.unwrap_or(0), &keep);
+        collect_media(dir, &relative, &kept, &mut media_files);
+        markdown.push((relative, kept.into_bytes()));
+    }
+    if markdown.is_empty() {
+        bail!(
+            "No {} cards found under {}",
+            match only {
+                PackOnly::Due => "due",
+                PackOnly::New => "new",
+            },
+            dir.display()
+        );
+    }
+    write_bundle(dir, out, &markdown, &media_files)
+}
+
+/// The lines before `first_card`, followed by the lines of each card whose
+/// line range is in `keep`.
+fn keep_card_lines(contents: &str, first_card: usize, keep: &HashSet<(usize, usize)>) -> String {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut ranges: Vec<&(usize, usize)> = keep.iter().collect();
+    ranges.sort();
+    let mut out = String::with_capacity(contents.len());
+    for line in &lines[..first_card.min(lines.len())] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    for &(start, end) in ranges {
+        for line in &lines[start.min(lines.len())..end.min(lines.len())] {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Zips `markdown`, given as paths relative to `dir` with their contents, and
+/// the media files under `dir` into `out`.
+fn write_bundle(
+    dir: &Path,
+    out: &Path,
+    markdown: &[(PathBuf, Vec<u8>)],
+    media_files: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    let file = File::create(out).with_context(|| format!("failed to create {}", out.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (relative, contents) in markdown {
+        zip.start_file(archive_name(relative), options)?;
+        zip.write_all(contents)?;
+    }
+    for relative in media_files {
+        zip.start_file(archive_name(relative), options)?;
+        let mut source = File::open(dir.join(relative))?;
+        io::copy(&mut source, &mut zip)?;
+    }
+    zip.finish()?.flush()?;
+
+    println!(
+        "Packed {} and {} into {}",
+        pluralize("markdown file", markdown.len()),
+        pluralize("media file", media_files.len()),
+        Palette::paint(Palette::ACCENT, out.display())
+    );
+    Ok(())
+}
+
+/// Restores a bundle created by `pack` into `dir`. Existing files are never overwritten.
+pub fn unpack(archive: &Path, dir: &Path) -> Result<()> {
+    let file =
+        File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    let mut zip = ZipArchive::new(file).context("failed to read bundle as zip archive")?;
+
+    let mut targets = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let entry = zip.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+            bail!("Refusing to unpack unsafe path: {}", entry.name());
+        };
+        let target = dir.join(relative);
+        if target.exists() {
+            bail!(
+                "{} already exists; unpack into an empty directory",
+                target.display()
+            );
+        }
+        targets.push((i, target));
+    }
+
+    for (i, target) in &targets {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut entry = zip.by_index(*i)?;
+        let mut out = File::create(target)?;
+        io::copy(&mut entry, &mut out)?;
+    }
+
+    println!(
+        "Unpacked {} into {}",
+        pluralize("file", targets.len()),
+        Palette::paint(Palette::ACCENT, dir.display())
+    );
+    Ok(())
+}
+
+/// Returns the markdown files and the referenced media under `dir`, relative to it.
+/// Media that is missing or lives outside `dir` is reported and left out.
+fn collect_bundle_files(dir: &Path) -> Result<(Vec<PathBuf>, BTreeSet<PathBuf>)> {
+    let markdown_files = markdown_files(dir)?;
+    let mut media_files = BTreeSet::new();
+    for relative in &markdown_files {
+        let path = dir.join(relative);
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        collect_media(dir, relative, &contents, &mut media_files);
+    }
+    Ok((markdown_files, media_files))
+}
+
+/// Markdown files under `dir`, relative to it, in file name order.
+fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut builder = WalkBuilder::new(dir);
+    builder
+        .hidden(false)
+        .git_ignore(true)
+        .git_exclude(true)
+        .sort_by_file_name(|a, b| a.cmp(b));
+
+    let mut markdown_files = Vec::new();
+    for entry in builder.build() {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) || !is_markdown(path) {
+            continue;
+        }
+        markdown_files.push(path.strip_prefix(dir)?.to_path_buf());
+    }
+    Ok(markdown_files)
+}
+
+/// Adds the media `contents` of the markdown file `relative` references to
+/// `media_files`, warning about media that is missing or outside `dir`.
+fn collect_media(dir: &Path, relative: &Path, contents: &str, media_files: &mut BTreeSet<PathBuf>) {
+    let base_dir = relative.parent().unwrap_or(Path::new(""));
+    for media in extract_media(contents, Some(base_dir)) {
+        let media_path = media.path();
+        if media_path.to_string_lossy().contains("://") {
+            continue;
+        }
+        let Some(media_relative) = normalize_relative(media_path) else {
+            eprintln!(
+                "{} {} references media outside the deck: {}",
+                Palette::paint(Palette::WARNING, "warning:"),
+                relative.display(),
+                media_path.display()
+            );
+            continue;
+        };
+        if !dir.join(&media_relative).is_file() {
+            eprintln!(
+                "{} {} references missing media: {}",
+                Palette::paint(Palette::WARNING, "warning:"),
+                relative.display(),
+                media_relative.display()
+            );
+            continue;
+        }
+        media_files.insert(media_relative);
+    }
+}
+
+/// Resolves `.` and `..` in a relative path, returning `None` if it escapes its root.
+fn normalize_relative(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+fn archive_name(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_and_unpack_round_trip_markdown_and_media() {
+        let source = tempfile::tempdir().unwrap();
+        let root = source.path();
+        fs::create_dir_all(root.join("bio/media")).unwrap();
+        fs::write(
+            root.join("bio/cells.md"),
+            "Q: What is this?\n![cell](media/cell.png)\nA: A cell\n\nQ: Missing?\n![gone](media/gone.png)\nA: yes\n",
+        )
+        .unwrap();
+        fs::write(root.join("bio/media/cell.png"), [0u8, 1, 2, 3]).unwrap();
+        fs::write(root.join("bio/media/unused.png"), [9u8]).unwrap();
+        fs::write(root.join("intro.md"), "C: [Rust] is a language\n").unwrap();
+
+        let bundle_dir = tempfile::tempdir().unwrap();
+        let bundle = bundle_dir.path().join("deck.zip");
+        pack(root, &bundle).unwrap();
+
+        let restored = tempfile::tempdir().unwrap();
+        unpack(&bundle, restored.path()).unwrap();
+
+        for relative in ["bio/cells.md", "bio/media/cell.png", "intro.md"] {
+            assert_eq!(
+                fs::read(restored.path().join(relative)).unwrap(),
+                fs::read(root.join(relative)).unwrap(),
+                "{relative} should round-trip"
+            );
+        }
+        assert!(!restored.path().join("bio/media/unused.png").exists());
+        assert!(!restored.path().join("bio/media/gone.png").exists());
+
+        assert!(unpack(&bundle, restored.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn pack_only_new_keeps_exactly_the_unreviewed_cards() {
+        use crate::fsrs::ReviewStatus;
+
+        let source = tempfile::tempdir().unwrap();
+        let root = source.path();
+        fs::write(
+            root.join("deck.md"),
+            "# Deck\n\nQ: reviewed?\nA: yes\n\nQ: fresh?\nA: yes\n\nC: also [fresh]\n",
+        )
+        .unwrap();
+        fs::write(root.join("done.md"), "Q: all reviewed?\nA: yes\n").unwrap();
+
+        let db = DB::new_in_memory().await.unwrap();
+        for name in ["deck.md", "done.md"] {
+            let cards = cards_from_md(&root.join(name)).unwrap();
+            db.add_cards_batch(&cards).await.unwrap();
+            for card in cards.iter().filter(|card| card.matches_query("reviewed")) {
+                db.update_card_performance(card, ReviewStatus::Pass, None)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let bundle_dir = tempfile::tempdir().unwrap();
+        let bundle = bundle_dir.path().join("new.zip");
+        pack_only(&db, root, &bundle, PackOnly::New).await.unwrap();
+        let restored = tempfile::tempdir().unwrap();
+        unpack(&bundle, restored.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(restored.path().join("deck.md")).unwrap(),
+            "# Deck\n\nQ: fresh?\nA: yes\n\nC: also [fresh]\n"
+        );
+        assert!(!restored.path().join("done.md").exists());
+    }
+
+    #[test]
+    fn normalize_relative_rejects_paths_escaping_the_deck() {
+        assert_eq!(
+            normalize_relative(Path::new("notes/../media/a.png")),
+            Some(PathBuf::from("media/a.png"))
+        );
+        assert_eq!(normalize_relative(Path::new("../a.png")), None);
+        assert_eq!(normalize_relative(Path::new("/tmp/a.png")), None);
+    }
+}
@@ -1,17 +1,104 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Result, bail};
 
+use crate::cloze_utils::find_cloze_ranges;
 use crate::llm::drill_preprocessor::AIStatus;
 
+/// Mixed into a cloze card's hash so its reverse review is scheduled separately.
+const REVERSE_CLOZE_SALT: &str = "reverse-cloze";
+const CLOZE_DELETION_SALT: &str = "cloze-deletion";
+
 #[derive(Clone, Debug)]
 pub struct Card {
     pub file_path: PathBuf,
-    #[allow(dead_code)]
     pub file_card_range: (usize, usize),
     pub content: CardContent,
     pub card_hash: String,
     pub ai_status: AIStatus,
+    pub directives: CardDirectives,
+    /// Drilled as a reverse cloze: the deleted term is shown and the
+    /// surrounding text is recalled.
+    pub reversed: bool,
+    /// `retention:` from the deck file's frontmatter; `None` schedules for
+    /// the global target recall.
+    pub retention: Option<f64>,
+}
+
+/// `@directive` lines inside a card. They are stripped before hashing, so
+/// adding or removing one keeps the card's review history.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CardDirectives {
+    /// `@important`: drilled ahead of other due cards.
+    pub important: bool,
+    /// `@hard`: starts with a slightly lower difficulty.
+    pub hard: bool,
+    /// `@suspended`: never due, but keeps its history for when the line is removed.
+    pub suspended: bool,
+    /// `@tags a, b`: labels for grouping cards, in the order first seen.
+    pub tags: Vec<String>,
+    /// `@cloze-mode=...`: how a cloze card is masked.
+    pub cloze_mode: ClozeMode,
+    /// `@priority=N`: new cards with a higher number are introduced first;
+    /// unmarked cards are 0.
+    pub priority: i32,
+}
+
+/// Which side of a cloze card hides the deletion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClozeMode {
+    /// The prompt masks the deletion and the answer reveals it.
+    #[default]
+    Hide,
+    /// `recall-term`: the prompt shows the full sentence and the answer masks
+    /// the deletion, so the key term is what gets recalled.
+    RecallTerm,
+    /// `occlude`: the other deletions stay masked on both sides, so each one
+    /// is studied on its own, like image occlusion in text.
+    Occlude,
+}
+
+impl CardDirectives {
+    /// Records `line` if it is a known directive; anything else is card text.
+    pub fn apply(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        match line {
+            "@important" => self.important = true,
+            "@hard" => self.hard = true,
+            "@suspended" => self.suspended = true,
+            "@cloze-mode=hide" => self.cloze_mode = ClozeMode::Hide,
+            "@cloze-mode=recall-term" => self.cloze_mode = ClozeMode::RecallTerm,
+            "@cloze-mode=occlude" => self.cloze_mode = ClozeMode::Occlude,
+            _ => {
+                if let Some(priority) = line.strip_prefix("@priority=") {
+                    let Ok(priority) = priority.trim().parse() else {
+                        return false;
+                    };
+                    self.priority = priority;
+                    return true;
+                }
+                let Some(tags) = line
+                    .strip_prefix("@tags")
+                    .and_then(|rest| rest.strip_prefix(':').or(Some(rest)))
+                    .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+                else {
+                    return false;
+                };
+                self.add_tags(tags);
+            }
+        }
+        true
+    }
+
+    /// Tags are separated by commas or whitespace; a leading `#` is dropped.
+    fn add_tags(&mut self, raw: &str) {
+        for tag in raw.split(|ch: char| ch == ',' || ch.is_whitespace()) {
+            let tag = tag.trim_start_matches('#');
+            if !tag.is_empty() && !self.tags.iter().any(|known| known == tag) {
+                self.tags.push(tag.to_string());
+            }
+        }
+    }
 }
 
 impl Card {
@@ -27,8 +114,108 @@ impl Card {
             content,
             card_hash,
             ai_status: AIStatus::NoNeed,
+            directives: CardDirectives::default(),
+            reversed: false,
+            retention: None,
+        }
+    }
+
+    /// The reverse review of a cloze card, under its own hash. Basic cards
+    /// already have a question to recall and return `None`.
+    pub fn reversed_cloze(&self) -> Option<Card> {
+        if !matches!(self.content, CardContent::Cloze { .. }) {
+            return None;
+        }
+        let mut card = self.clone();
+        card.card_hash = reverse_cloze_hash(&self.card_hash);
+        card.reversed = true;
+        Some(card)
+    }
+
+    /// Where the card lives: its file and the `(start, end)` line range within it.
+    /// Sorting by locator gives document order across a tree of decks.
+    pub fn locator(&self) -> (&Path, usize, usize) {
+        (
+            &self.file_path,
+            self.file_card_range.0,
+            self.file_card_range.1,
+        )
+    }
+
+    /// `file:line  first line of the prompt`, the row that names a card in
+    /// listings.
+    pub fn location_label(&self) -> String {
+        let prompt = match &self.content {
+            CardContent::Basic { question, .. } => question,
+            CardContent::Cloze { text, .. } => text,
+        };
+        format!(
+            "{}:{}  {}",
+            self.file_path.display(),
+            self.file_card_range.0 + 1,
+            prompt.lines().next().unwrap_or_default()
+        )
+    }
+
+    /// Case-insensitive substring match over the question, answer, or cloze text.
+    pub fn matches_query(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        match &self.content {
+            CardContent::Basic { question, answer } => {
+                question.to_lowercase().contains(&query) || answer.to_lowercase().contains(&query)
+            }
+            CardContent::Cloze { text, .. } => text.to_lowercase().contains(&query),
+        }
+    }
+
+    /// Expands a card into the items shown during a drill. A cloze card with
+    /// several bracketed deletions yields one item per deletion, each under its
+    /// own hash so it is scheduled on its own; every other card, including a
+    /// reverse cloze, is a single item.
+    pub fn drill_items(&self) -> Vec<Card> {
+        let CardContent::Cloze { text, .. } = &self.content else {
+            return vec![self.clone()];
+        };
+        if self.reversed {
+            return vec![self.clone()];
+        }
+        let ranges: Vec<ClozeRange> = find_cloze_ranges(text)
+            .into_iter()
+            .filter_map(|(start, end)| ClozeRange::new(start, end).ok())
+            .collect();
+        if ranges.len() <= 1 {
+            return vec![self.clone()];
         }
+
+        ranges
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| {
+                let mut item = self.clone();
+                item.card_hash = cloze_deletion_hash(&self.card_hash, index);
+                item.content = CardContent::Cloze {
+                    text: text.clone(),
+                    cloze_range: Some(range),
+                };
+                item
+            })
+            .collect()
+    }
+}
+
+/// Hash under which the reverse review of the cloze card `card_hash` is stored.
+pub fn reverse_cloze_hash(card_hash: &str) -> String {
+    blake3::hash(format!("{REVERSE_CLOZE_SALT}:{card_hash}").as_bytes()).to_string()
+}
+
+/// Hash under which deletion `index` of the cloze card `card_hash` is
+/// scheduled. The first deletion keeps the card's own hash, so reviews from
+/// before the card gained more deletions stay with it.
+pub fn cloze_deletion_hash(card_hash: &str, index: usize) -> String {
+    if index == 0 {
+        return card_hash.to_string();
     }
+    blake3::hash(format!("{CLOZE_DELETION_SALT}:{card_hash}:{index}").as_bytes()).to_string()
 }
 
 #[derive(Clone, Debug)]
@@ -43,9 +230,13 @@ pub enum CardContent {
     },
 }
 
+/// Byte offsets of a deletion in the cloze text, brackets included.
 #[derive(Clone, Debug)]
 pub struct ClozeRange {
+    /// Offset of the opening `[`.
     pub start: usize,
+    /// One past the closing `]`, so a deletion at the end of the text has
+    /// `end == text.len()`.
     pub end: usize,
 }
 
@@ -1,16 +1,21 @@
 use crate::card::ClozeRange;
 
+/// Byte ranges of the `[...]` deletions in `text`, brackets included.
+/// Markdown links and images (`[text](url)`) are not deletions.
 pub fn find_cloze_ranges(text: &str) -> Vec<(usize, usize)> {
     let mut ranges = Vec::new();
     let mut start = None;
+    let mut chars = text.char_indices().peekable();
 
-    for (i, ch) in text.char_indices() {
+    while let Some((i, ch)) = chars.next() {
         match ch {
             '[' if start.is_none() => start = Some(i),
             ']' => {
                 if let Some(s) = start.take() {
-                    let e = i + ch.len_utf8();
-                    ranges.push((s, e));
+                    let is_link = matches!(chars.peek(), Some((_, '(')));
+                    if !is_link {
+                        ranges.push((s, i + ch.len_utf8()));
+                    }
                 }
             }
             _ => {}
@@ -20,15 +25,63 @@ pub fn find_cloze_ranges(text: &str) -> Vec<(usize, usize)> {
     ranges
 }
 
+/// Replaces the deletion at `range` with underscores. `range` holds byte
+/// offsets into `text` as returned by `find_cloze_ranges`, so it stays aligned
+/// across newlines. A range that doesn't fit `text` hides the whole card rather
+/// than panicking or revealing the answer.
 pub fn mask_cloze_text(text: &str, range: &ClozeRange) -> String {
-    let start = range.start;
-    let end = range.end;
-    let hidden_section = &text[start..end];
+    let (Some(before), Some(hidden_section), Some(after)) = (
+        text.get(..range.start),
+        text.get(range.start..range.end),
+        text.get(range.end..),
+    ) else {
+        return "[___]".to_string();
+    };
+    format!("{before}{}{after}", masked(hidden_section))
+}
+
+/// `[___]` as wide as the deletion `hidden_section`, brackets included.
+fn masked(hidden_section: &str) -> String {
     let core = hidden_section.trim_start_matches('[').trim_end_matches(']');
-    let placeholder = "_".repeat(core.chars().count().max(3));
+    // A deletion spanning lines still masks to a single line.
+    let hidden_chars = core.chars().filter(|ch| !matches!(ch, '\n' | '\r')).count();
+    format!("[{}]", "_".repeat(hidden_chars.max(3)))
+}
+
+/// Masks every deletion in `text`, except the one at `active` when `reveal`
+/// is set, so only the deletion being studied is ever shown.
+pub fn occlude_cloze_text(text: &str, active: &ClozeRange, reveal: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in find_cloze_ranges(text) {
+        out.push_str(&text[last..start]);
+        let section = &text[start..end];
+        if reveal && start == active.start && end == active.end {
+            out.push_str(section);
+        } else {
+            out.push_str(&masked(section));
+        }
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
 
-    let masked = format!("{}[{}]{}", &text[..start], placeholder, &text[end..]);
-    masked
+/// Drops the brackets around the deletion at `range`, showing the sentence as
+/// plain text. A range that doesn't fit `text` leaves it unchanged.
+pub fn unbracket_cloze_text(text: &str, range: &ClozeRange) -> String {
+    let (Some(before), Some(hidden_section), Some(after)) = (
+        text.get(..range.start),
+        text.get(range.start..range.end),
+        text.get(range.end..),
+    ) else {
+        return text.to_string();
+    };
+    let core = hidden_section
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(hidden_section);
+    format!("{before}{core}{after}")
 }
 
 #[cfg(test)]
@@ -66,4 +119,82 @@ mod tests {
             "Capital of 日本 is [______________________________]"
         );
     }
+
+    #[test]
+    fn mask_cloze_text_aligns_with_multi_line_cloze_text() {
+        let card = crate::parser::content_to_card(
+            std::path::Path::new("test.md"),
+            "C: Ohio is a state.\nIts capital is [Columbus].\n",
+            0,
+            2,
+        )
+        .unwrap();
+        let crate::card::CardContent::Cloze {
+            text,
+            cloze_range: Some(range),
+        } = &card.content
+        else {
+            panic!("Expected a cloze with a range");
+        };
+        assert_eq!(
+            mask_cloze_text(text, range),
+            "Ohio is a state.\nIts capital is [________]."
+        );
+
+        let text = "Split [across\nlines] here";
+        let (start, end) = find_cloze_ranges(text)[0];
+        let range = ClozeRange::new(start, end).unwrap();
+        assert_eq!(mask_cloze_text(text, &range), "Split [___________] here");
+    }
+
+    #[test]
+    fn markdown_links_are_not_clozes() {
+        let card = crate::parser::content_to_card(
+            std::path::Path::new("test.md"),
+            "C: See [the docs](http://x) and ![diagram](img.png)",
+            0,
+            1,
+        )
+        .unwrap();
+        assert!(matches!(
+            card.content,
+            crate::card::CardContent::Cloze {
+                cloze_range: None,
+                ..
+            }
+        ));
+
+        let text = "See [the docs](http://x) for [Ohio]";
+        assert_eq!(find_cloze_ranges(text), vec![(29, 35)]);
+        assert_eq!(&text[29..35], "[Ohio]");
+    }
+
+    #[test]
+    fn mask_cloze_text_does_not_panic_on_stale_ranges() {
+        let past_the_end = ClozeRange { start: 4, end: 40 };
+        assert_eq!(mask_cloze_text("short [x]", &past_the_end), "[___]");
+
+        // Offset 1 falls inside the two-byte `é`.
+        let mid_char = ClozeRange { start: 1, end: 6 };
+        assert_eq!(mask_cloze_text("é [abc]", &mid_char), "[___]");
+    }
+
+    #[test]
+    fn cloze_ending_at_the_last_byte_is_masked() {
+        for (text, masked) in [
+            ("[x]", "[___]"),
+            ("ends with [x]", "ends with [___]"),
+            ("ends with [東京都]", "ends with [___]"),
+            ("ends with [longer]", "ends with [______]"),
+        ] {
+            let (start, end) = find_cloze_ranges(text)[0];
+            assert_eq!(end, text.len());
+            let range = ClozeRange::new(start, end).unwrap();
+            assert_eq!(mask_cloze_text(text, &range), masked);
+        }
+
+        // One byte past the end is stale, not a reason to panic.
+        let past_the_end = ClozeRange { start: 0, end: 4 };
+        assert_eq!(mask_cloze_text("[x]", &past_the_end), "[___]");
+    }
 }
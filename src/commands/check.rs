@@ -1,9 +1,20 @@
 use crate::{
+    card::Card,
     check_version::{check_version, prompt_for_new_version},
+    commands::{
+        drill::{self, DrillOptions},
+        edits,
+    },
     crud::DB,
-    parser::{FileSearchStats, register_all_cards},
-    stats::{CardLifeCycle, CardStats, Histogram},
+    fsrs::{Performance, retrievability},
+    palette::Palette,
+    parser::{CollectOptions, FileSearchStats, register_all_cards},
+    stats::{
+        CardLifeCycle, CardStats, DayZone, GhostCard, HEATMAP_WEEKS, Histogram, ReviewHeatmap,
+        display_date_format,
+    },
     tui::Theme,
+    utils::pluralize,
 };
 
 use std::{
@@ -13,8 +24,9 @@ use std::{
     time::Duration,
 };
 
-use anyhow::Result;
-use chrono::NaiveDate;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use clap::ValueEnum;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
@@ -28,20 +40,442 @@ use ratatui::{
     widgets::{Bar, BarChart, BarGroup, Paragraph, Wrap},
 };
 
-pub async fn run(db: &DB, paths: Vec<PathBuf>) -> Result<usize> {
+/// Plain-text renderings of the dashboard stats, for scripts and logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CheckFormat {
+    /// One `Label: value` line per stat.
+    Plain,
+    /// An aligned ASCII table.
+    Table,
+    /// A JSON object.
+    Json,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    pub strict: bool,
+    /// Read deck files with invalid UTF-8 by replacing the bad bytes.
+    pub lossy: bool,
+    /// Print the stats in this format instead of opening the dashboard.
+    pub format: Option<CheckFormat>,
+    /// Also list the DB rows that no longer match any card file.
+    pub list_ghosts: bool,
+    /// Print the cards a drill with these options would show instead of the stats.
+    pub plan: Option<DrillOptions>,
+    /// Print a heatmap of reviews per day instead of the stats.
+    pub heatmap: bool,
+    /// Print the schedule of the cards matching this hash or text instead of the stats.
+    pub explain: Option<String>,
+    /// Print the slowest deck files to parse.
+    pub verbose: bool,
+    /// Offer to keep the history of cards edited since the last tracked run.
+    pub track_edits: bool,
+}
+
+pub async fn run(db: &DB, paths: Vec<PathBuf>, options: CheckOptions) -> Result<usize> {
     let version_check = tokio::spawn(check_version(db.clone()));
 
-    let (card_hashes, file_traversal_stats) = register_all_cards(db, paths).await?;
+    let (card_hashes, file_traversal_stats) = register_all_cards(
+        db,
+        paths,
+        CollectOptions {
+            strict: options.strict,
+            lossy: options.lossy,
+            verbose: options.verbose,
+            track_edits: options.track_edits,
+            ..Default::default()
+        },
+    )
+    .await?;
+    if options.track_edits {
+        edits::carry_over(db, &card_hashes, &file_traversal_stats.edited_cards).await?;
+    }
+    for warning in no_card_warnings(&file_traversal_stats.files_without_cards)
+        .iter()
+        .chain(&file_traversal_stats.duplicate_cards)
+    {
+        eprintln!("warning: {warning}");
+    }
     let count = card_hashes.len();
-    let crud_stats = db.collection_stats(&card_hashes).await?;
+    if let Some(drill_options) = &options.plan {
+        let cards = drill::plan(db, &card_hashes, drill_options).await?;
+        print!("{}", format_plan(&cards));
+        return Ok(count);
+    }
+    if let Some(target) = &options.explain {
+        let mut cards: Vec<&Card> = card_hashes
+            .values()
+            .filter(|card| card.card_hash == *target || card.matches_query(target))
+            .collect();
+        if cards.is_empty() {
+            bail!("No card matches `{target}`");
+        }
+        cards.sort_by(|a, b| a.locator().cmp(&b.locator()));
+        let now = chrono::Utc::now();
+        for card in cards {
+            let performance = db.get_card_performance(card).await?;
+            print!("{}", format_explanation(card, &performance, now));
+        }
+        return Ok(count);
+    }
+    let day_zone = DayZone::from_env();
+    if options.heatmap {
+        let now = chrono::Utc::now();
+        let since = now - chrono::Duration::weeks(HEATMAP_WEEKS as i64 + 1);
+        let reviews = db.review_times_since(since).await?;
+        let heatmap =
+            ReviewHeatmap::from_reviews(&reviews, day_zone.date_of(now), HEATMAP_WEEKS, day_zone);
+        print!("{}", format_heatmap(&heatmap));
+        return Ok(count);
+    }
+    let mut crud_stats = db.collection_stats(&card_hashes, day_zone).await?;
+    crud_stats.added_today = db.added_today(day_zone).await?;
+    crud_stats.reviewed_today = db.reviewed_today(day_zone).await?;
+    crud_stats.last_session = db.last_session().await?;
     if let Some(notification) = version_check.await.ok().flatten() {
         prompt_for_new_version(db, &notification).await;
     }
 
-    render_dashboard(&crud_stats, &file_traversal_stats)?;
+    match options.format {
+        Some(CheckFormat::Plain) => print!("{}", format_plain(&crud_stats)),
+        Some(CheckFormat::Table) => print!("{}", format_table(&crud_stats)),
+        Some(CheckFormat::Json) => {
+            println!("{}", format_json(&crud_stats, options.list_ghosts)?)
+        }
+        None => render_dashboard(&crud_stats, &file_traversal_stats)?,
+    }
+    if options.list_ghosts && options.format != Some(CheckFormat::Json) {
+        print!("{}", format_ghosts(&crud_stats));
+    }
     Ok(count)
 }
 
+/// Everything stored about a card's schedule, plus how overdue it is and its
+/// predicted recall right now.
+fn format_explanation(card: &Card, performance: &Performance, now: DateTime<Utc>) -> String {
+    let mut out = format!("{}\n", card.location_label());
+    let mut field = |label: &str, value: String| out.push_str(&format!("  {label:<16}{value}\n"));
+    field("hash", card.card_hash.clone());
+    if card.directives.suspended {
+        field(
+            "suspended",
+            "yes; never due until the @suspended line is removed".to_string(),
+        );
+    }
+    let Performance::Reviewed(reviewed) = performance else {
+        field(
+            "status",
+            "new; due as soon as the new-card limit allows".to_string(),
+        );
+        return out;
+    };
+    let timestamp = |at: DateTime<Utc>| at.format("%Y-%m-%d %H:%M UTC").to_string();
+    field("reviews", reviewed.review_count.to_string());
+    field("last reviewed", timestamp(reviewed.last_reviewed_at));
+    field("stability", format!("{:.2} days", reviewed.stability));
+    field("difficulty", format!("{:.2}", reviewed.difficulty));
+    // Learning steps are minutes long, which would round to 0.00 days.
+    let raw_interval = if reviewed.interval_raw < 1.0 {
+        format!("{:.0} minutes", reviewed.interval_raw * 24.0 * 60.0)
+    } else {
+        format!("{:.2} days", reviewed.interval_raw)
+    };
+    field(
+        "interval",
+        format!(
+            "{} (raw {raw_interval})",
+            pluralize("day", reviewed.interval_days)
+        ),
+    );
+    let overdue_days = (now - reviewed.due_date).num_days();
+    let due_note = match overdue_days {
+        0 if reviewed.due_date <= now => "due now".to_string(),
+        0 => "due later today".to_string(),
+        days if days > 0 => format!("{} overdue", pluralize("day", days as usize)),
+        days => format!("due in {}", pluralize("day", days.unsigned_abs() as usize)),
+    };
+    field(
+        "due",
+        format!("{} ({due_note})", timestamp(reviewed.due_date)),
+    );
+    if let Some(recall) = retrievability(performance, now) {
+        field("retrievability", format!("{:.0}%", recall * 100.0));
+    }
+    out
+}
+
+/// One `file:line  first line of the prompt` row per card, in drill order.
+fn format_plan(cards: &[Card]) -> String {
+    if cards.is_empty() {
+        return "No cards due.\n".to_string();
+    }
+    let mut out = format!("{} in the next drill:\n", pluralize("card", cards.len()));
+    for card in cards {
+        out.push_str(&format!("{}\n", card.location_label()));
+    }
+    out
+}
+
+const HEATMAP_SHADES: [&str; 4] = ["░", "▒", "▓", "█"];
+
+/// Shade for a day with `count` reviews, scaled to the busiest day: `None`
+/// for a day without reviews.
+fn heatmap_shade(count: usize, busiest: usize) -> Option<&'static str> {
+    if count == 0 || busiest == 0 {
+        return None;
+    }
+    let level = (count * HEATMAP_SHADES.len()).div_ceil(busiest);
+    Some(HEATMAP_SHADES[level.clamp(1, HEATMAP_SHADES.len()) - 1])
+}
+
+/// A contribution-style grid: one column per week, one row per weekday, with
+/// month names over the weeks they start in.
+fn format_heatmap(heatmap: &ReviewHeatmap) -> String {
+    const WEEKDAYS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+    let busiest = heatmap.busiest_day();
+    let mut out = format!(
+        "{} in the last {} weeks (busiest day: {})\n",
+        pluralize("review", heatmap.total()),
+        heatmap.weeks.len(),
+        busiest
+    );
+
+    // A month name sits over the first week starting in it; one that would
+    // run into the next name is dropped.
+    let mut labels: Vec<(usize, String)> = Vec::new();
+    let mut previous_month = None;
+    for week in 0..heatmap.weeks.len() {
+        let monday = heatmap.start + chrono::Duration::weeks(week as i64);
+        if previous_month != Some(monday.month()) {
+            labels.push((week * 2, monday.format("%b").to_string()));
+        }
+        previous_month = Some(monday.month());
+    }
+    let mut months = String::new();
+    for (index, (column, name)) in labels.iter().enumerate() {
+        let fits = labels
+            .get(index + 1)
+            .is_none_or(|(next, _)| column + name.len() < *next);
+        if fits && months.len() <= *column {
+            months.push_str(&" ".repeat(column - months.len()));
+            months.push_str(name);
+        }
+    }
+    out.push_str(&format!("    {}\n", months.trim_end()));
+
+    for (weekday, label) in WEEKDAYS.iter().enumerate() {
+        let mut row = format!("{label:<3} ");
+        for (week, days) in heatmap.weeks.iter().enumerate() {
+            let day = heatmap.start + chrono::Duration::days((week * 7 + weekday) as i64);
+            let cell = if day > heatmap.today {
+                " ".to_string()
+            } else {
+                match heatmap_shade(days[weekday], busiest) {
+                    Some(shade) => Palette::paint(Palette::SUCCESS, shade),
+                    None => Palette::dim("·"),
+                }
+            };
+            row.push_str(&cell);
+            row.push(' ');
+        }
+        out.push_str(row.trim_end());
+        out.push('\n');
+    }
+    let legend: String = HEATMAP_SHADES
+        .iter()
+        .map(|shade| Palette::paint(Palette::SUCCESS, shade))
+        .collect();
+    out.push_str(&format!("    less {legend} more\n"));
+    out
+}
+
+/// Files listed by name before the rest are summarised as a count.
+const MAX_LISTED_FILES_WITHOUT_CARDS: usize = 5;
+
+fn no_card_warnings(files: &[PathBuf]) -> Vec<String> {
+    let mut warnings: Vec<String> = files
+        .iter()
+        .take(MAX_LISTED_FILES_WITHOUT_CARDS)
+        .map(|path| {
+            format!(
+                "{} has text but no cards; card markers (`Q:`, `A:`, `C:`) must start at column 0",
+                path.display()
+            )
+        })
+        .collect();
+    if files.len() > MAX_LISTED_FILES_WITHOUT_CARDS {
+        warnings.push(format!(
+            "{} more with text but no cards",
+            pluralize("file", files.len() - MAX_LISTED_FILES_WITHOUT_CARDS)
+        ));
+    }
+    warnings
+}
+
+fn lifecycle_count(stats: &CardStats, lifecycle: CardLifeCycle) -> i64 {
+    *stats.card_lifecycles.get(&lifecycle).unwrap_or(&0)
+}
+
+fn percent(value: Option<f64>) -> String {
+    value.map_or_else(|| "NA".to_string(), |v| format!("{}%", (v * 100.0).round()))
+}
+
+/// Label/value pairs shared by the plain and table formats.
+fn stat_rows(stats: &CardStats) -> Vec<(&'static str, String)> {
+    vec![
+        ("Cards found", stats.num_cards.to_string()),
+        (
+            "New",
+            lifecycle_count(stats, CardLifeCycle::New).to_string(),
+        ),
+        (
+            "Learning",
+            lifecycle_count(stats, CardLifeCycle::Learning).to_string(),
+        ),
+        (
+            "Young",
+            lifecycle_count(stats, CardLifeCycle::Young).to_string(),
+        ),
+        (
+            "Mature",
+            lifecycle_count(stats, CardLifeCycle::Mature).to_string(),
+        ),
+        ("Due now", stats.due_cards.to_string()),
+        (
+            "Next 7 days",
+            stats.upcoming_week.values().sum::<usize>().to_string(),
+        ),
+        ("Next 30 days", stats.upcoming_month.to_string()),
+        ("Added today", stats.added_today.to_string()),
+        ("Reviewed today", stats.reviewed_today.to_string()),
+        ("Files containing cards", stats.file_paths.len().to_string()),
+        (
+            "Total cards indexed in DB",
+            stats.total_cards_in_db.to_string(),
+        ),
+        ("Ghost cards in DB", stats.ghosts.len().to_string()),
+        (
+            "Average retrievability",
+            percent(stats.retrievability_histogram.mean()),
+        ),
+        (
+            "Average difficulty",
+            percent(stats.difficulty_histogram.mean()),
+        ),
+        ("Last session", last_session_summary(stats)),
+    ]
+}
+
+fn format_plain(stats: &CardStats) -> String {
+    stat_rows(stats)
+        .into_iter()
+        .map(|(label, value)| format!("{label}: {value}\n"))
+        .collect()
+}
+
+fn format_table(stats: &CardStats) -> String {
+    let rows = stat_rows(stats);
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let value_width = rows
+        .iter()
+        .map(|(_, value)| value.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Value".len());
+    let border = format!(
+        "+{}+{}+\n",
+        "-".repeat(label_width + 2),
+        "-".repeat(value_width + 2)
+    );
+    let mut out = border.clone();
+    out.push_str(&format!(
+        "| {:<label_width$} | {:<value_width$} |\n",
+        "Stat", "Value"
+    ));
+    out.push_str(&border);
+    for (label, value) in rows {
+        out.push_str(&format!(
+            "| {label:<label_width$} | {value:>value_width$} |\n"
+        ));
+    }
+    out.push_str(&border);
+    out
+}
+
+pub(crate) fn last_reviewed_label(ghost: &GhostCard) -> String {
+    ghost.last_reviewed_at.map_or_else(
+        || "never reviewed".to_string(),
+        |at| format!("last reviewed {}", at.format("%Y-%m-%d")),
+    )
+}
+
+/// One `hash  last reviewed` line per ghost, for `--list-ghosts`.
+fn format_ghosts(stats: &CardStats) -> String {
+    if stats.ghosts.is_empty() {
+        return "No ghost cards: every DB row matches a card file.\n".to_string();
+    }
+    let mut out = format!(
+        "{} in the DB match no card file:\n",
+        pluralize("ghost card", stats.ghosts.len())
+    );
+    for ghost in &stats.ghosts {
+        out.push_str(&format!(
+            "{}  {}\n",
+            ghost.card_hash,
+            last_reviewed_label(ghost)
+        ));
+    }
+    out
+}
+
+fn format_json(stats: &CardStats, list_ghosts: bool) -> Result<String> {
+    let upcoming_week: serde_json::Map<String, serde_json::Value> = stats
+        .upcoming_week
+        .iter()
+        .map(|(day, count)| (day.to_string(), (*count).into()))
+        .collect();
+    let last_session = stats.last_session.as_ref().map(|session| {
+        serde_json::json!({
+            "started_at": session.started_at.to_rfc3339(),
+            "ended_at": session.ended_at.to_rfc3339(),
+            "cards_reviewed": session.cards_reviewed,
+            "passed": session.passed,
+            "failed": session.failed,
+        })
+    });
+    let mut value = serde_json::json!({
+        "cards_found": stats.num_cards,
+        "new": lifecycle_count(stats, CardLifeCycle::New),
+        "learning": lifecycle_count(stats, CardLifeCycle::Learning),
+        "young": lifecycle_count(stats, CardLifeCycle::Young),
+        "mature": lifecycle_count(stats, CardLifeCycle::Mature),
+        "due_now": stats.due_cards,
+        "upcoming_week": upcoming_week,
+        "upcoming_month": stats.upcoming_month,
+        "added_today": stats.added_today,
+        "reviewed_today": stats.reviewed_today,
+        "files_with_cards": stats.file_paths.len(),
+        "total_cards_in_db": stats.total_cards_in_db,
+        "average_retrievability": stats.retrievability_histogram.mean(),
+        "average_difficulty": stats.difficulty_histogram.mean(),
+        "last_session": last_session,
+        "ghost_cards": stats.ghosts.len(),
+    });
+    if list_ghosts {
+        value["ghosts"] = stats
+            .ghosts
+            .iter()
+            .map(|ghost| {
+                serde_json::json!({
+                    "card_hash": ghost.card_hash,
+                    "last_reviewed_at": ghost.last_reviewed_at.map(|at| at.to_rfc3339()),
+                })
+            })
+            .collect();
+    }
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
 fn render_dashboard(crud_stats: &CardStats, file_traversal_stats: &FileSearchStats) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -144,6 +578,16 @@ fn collection_panel(
                     .unwrap_or(&0)
             )),
             Theme::bullet(),
+            Theme::span("Learning"),
+            Theme::bullet(),
+            Theme::label_span(format!(
+                "{}",
+                *crud_stats
+                    .card_lifecycles
+                    .get(&CardLifeCycle::Learning)
+                    .unwrap_or(&0)
+            )),
+            Theme::bullet(),
             Theme::span("Young"),
             Theme::bullet(),
             Theme::label_span(format!(
@@ -164,28 +608,56 @@ fn collection_panel(
                     .unwrap_or(&0)
             )),
         ]),
-        Line::from(vec![
-            Theme::span("Files Containing Cards"),
-            Theme::bullet(),
-            Theme::label_span(format!("{}", crud_stats.file_paths.len())),
-            Theme::bullet(),
-            Theme::span("Markdowns Parsed"),
-            Theme::bullet(),
-            Theme::label_span(format!("{}", file_traversal_stats.markdown_files)),
-            Theme::bullet(),
-            Theme::span("Files Searched"),
-            Theme::bullet(),
-            Theme::label_span(format!("{}", file_traversal_stats.files_searched)),
-        ]),
+        files_line(crud_stats, file_traversal_stats),
         Line::from(vec![
             Theme::span("Total Cards Indexed in DB"),
             Theme::bullet(),
             Theme::label_span(format!("{}", crud_stats.total_cards_in_db)),
+            Theme::bullet(),
+            Theme::span("Ghosts"),
+            Theme::bullet(),
+            Theme::label_span(format!("{}", crud_stats.ghosts.len())),
         ]),
     ];
     Paragraph::new(lines).block(Theme::panel("Collection"))
 }
 
+fn files_line(crud_stats: &CardStats, file_traversal_stats: &FileSearchStats) -> Line<'static> {
+    let mut spans = vec![
+        Theme::span("Files Containing Cards"),
+        Theme::bullet(),
+        Theme::label_span(format!("{}", crud_stats.file_paths.len())),
+        Theme::bullet(),
+        Theme::span("Markdowns Parsed"),
+        Theme::bullet(),
+        Theme::label_span(format!("{}", file_traversal_stats.markdown_files)),
+        Theme::bullet(),
+        Theme::span("Files Searched"),
+        Theme::bullet(),
+        Theme::label_span(format!("{}", file_traversal_stats.files_searched)),
+    ];
+    if !file_traversal_stats.files_without_cards.is_empty() {
+        spans.extend([
+            Theme::bullet(),
+            Span::styled("Text but no cards", Theme::danger()),
+            Theme::bullet(),
+            Theme::label_span(format!(
+                "{}",
+                file_traversal_stats.files_without_cards.len()
+            )),
+        ]);
+    }
+    if file_traversal_stats.skipped_files > 0 {
+        spans.extend([
+            Theme::bullet(),
+            Span::styled("Unreadable (skipped)", Theme::danger()),
+            Theme::bullet(),
+            Theme::label_span(format!("{}", file_traversal_stats.skipped_files)),
+        ]);
+    }
+    Line::from(spans)
+}
+
 fn due_panel(stats: &CardStats) -> Paragraph<'static> {
     let load_factor = if stats.num_cards == 0 {
         0.0
@@ -221,10 +693,39 @@ fn due_panel(stats: &CardStats) -> Paragraph<'static> {
             Theme::bullet(),
             Theme::label_span(format!("{}", stats.upcoming_month)),
         ]),
+        Line::from(vec![
+            Theme::span("Added today"),
+            Theme::bullet(),
+            Theme::label_span(format!("{}", stats.added_today)),
+            Theme::bullet(),
+            Theme::span("Reviewed today"),
+            Theme::bullet(),
+            Theme::label_span(format!("{}", stats.reviewed_today)),
+        ]),
+        last_session_line(stats),
     ];
     Paragraph::new(lines).block(Theme::panel("Due Status"))
 }
 
+fn last_session_summary(stats: &CardStats) -> String {
+    match &stats.last_session {
+        Some(session) => format!(
+            "{}, {:.0}% pass",
+            pluralize("card", session.cards_reviewed as usize),
+            session.pass_rate().unwrap_or_default() * 100.0
+        ),
+        None => "none yet".to_string(),
+    }
+}
+
+fn last_session_line(stats: &CardStats) -> Line<'static> {
+    Line::from(vec![
+        Theme::span("Last session"),
+        Theme::bullet(),
+        Theme::label_span(last_session_summary(stats)),
+    ])
+}
+
 fn render_upcoming_histogram(frame: &mut Frame<'_>, area: Rect, stats: &CardStats) {
     let block = Theme::panel_with_line(Theme::title_line("Next 7 days histogram"));
     if stats.upcoming_week.is_empty() {
@@ -253,11 +754,12 @@ fn render_upcoming_histogram(frame: &mut Frame<'_>, area: Rect, stats: &CardStat
         chart_area = inner;
     }
 
+    let date_format = display_date_format();
     let bars: Vec<Bar<'static>> = stats
         .upcoming_week
         .iter()
         .map(|(day, count)| {
-            let label = format_upcoming_label(day);
+            let label = format_upcoming_label(*day, &date_format);
             Bar::default()
                 .value(*count as u64)
                 .text_value(count.to_string())
@@ -287,10 +789,8 @@ fn render_upcoming_histogram(frame: &mut Frame<'_>, area: Rect, stats: &CardStat
     frame.render_widget(chart, chart_area);
 }
 
-fn format_upcoming_label(day: &str) -> String {
-    NaiveDate::parse_from_str(day, "%Y-%m-%d")
-        .map(|date| date.format("%a %d").to_string())
-        .unwrap_or_else(|_| day.to_string())
+fn format_upcoming_label(day: NaiveDate, date_format: &str) -> String {
+    day.format(date_format).to_string()
 }
 
 fn render_fsrs_histogram(
@@ -416,18 +916,202 @@ fn help_panel(stats: &CardStats) -> Paragraph<'static> {
 
 #[cfg(test)]
 mod tests {
-    use super::format_upcoming_label;
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::crud::sessions::SessionRecord;
+
+    fn fixed_stats() -> CardStats {
+        let day = NaiveDate::from_ymd_opt(2024, 12, 25).unwrap();
+        let started_at = chrono::DateTime::parse_from_rfc3339("2024-12-24T10:00:00Z")
+            .unwrap()
+            .to_utc();
+        CardStats {
+            total_cards_in_db: 40,
+            added_today: 2,
+            reviewed_today: 34,
+            num_cards: 30,
+            card_lifecycles: [
+                (CardLifeCycle::New, 10),
+                (CardLifeCycle::Learning, 3),
+                (CardLifeCycle::Young, 12),
+                (CardLifeCycle::Mature, 5),
+            ]
+            .into_iter()
+            .collect(),
+            due_cards: 12,
+            upcoming_week: [(day, 12), (day.succ_opt().unwrap(), 3)]
+                .into_iter()
+                .collect(),
+            upcoming_month: 20,
+            file_paths: [(PathBuf::from("deck.md"), 30)].into_iter().collect(),
+            last_session: Some(SessionRecord {
+                started_at,
+                ended_at: started_at + chrono::Duration::minutes(9),
+                cards_reviewed: 34,
+                passed: 30,
+                failed: 4,
+            }),
+            ghosts: vec![
+                GhostCard {
+                    card_hash: "abc123".to_string(),
+                    last_reviewed_at: Some(started_at),
+                },
+                GhostCard {
+                    card_hash: "def456".to_string(),
+                    last_reviewed_at: None,
+                },
+            ],
+            ..Default::default()
+        }
+    }
 
     #[test]
-    fn format_upcoming_label_pretty_prints_dates() {
-        assert_eq!(format_upcoming_label("2024-12-25"), "Wed 25");
+    fn plain_format_lists_one_stat_per_line() {
+        let plain = format_plain(&fixed_stats());
+        assert!(plain.starts_with(
+            "Cards found: 30\nNew: 10\nLearning: 3\nYoung: 12\nMature: 5\nDue now: 12\n"
+        ));
+        assert!(plain.contains("Next 7 days: 15\n"));
+        assert!(plain.contains("Average retrievability: NA\n"));
+        assert!(plain.ends_with("Last session: 34 cards, 88% pass\n"));
     }
 
     #[test]
-    fn format_upcoming_label_falls_back_to_original_input() {
+    fn table_format_aligns_columns() {
+        let table = format_table(&fixed_stats());
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], lines[2]);
+        assert_eq!(lines[0], *lines.last().unwrap());
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert!(lines.contains(&"| Cards found               |                 30 |"));
+    }
+
+    #[test]
+    fn json_format_is_machine_readable() {
+        let json: serde_json::Value =
+            serde_json::from_str(&format_json(&fixed_stats(), false).unwrap()).unwrap();
+        assert_eq!(json["cards_found"], 30);
+        assert_eq!(json["upcoming_week"]["2024-12-26"], 3);
+        assert_eq!(json["average_difficulty"], serde_json::Value::Null);
+        assert_eq!(json["last_session"]["passed"], 30);
+        assert_eq!(json["ghost_cards"], 2);
+        assert!(json.get("ghosts").is_none());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format_json(&fixed_stats(), true).unwrap()).unwrap();
+        assert_eq!(json["ghosts"][0]["card_hash"], "abc123");
+        assert_eq!(
+            json["ghosts"][1]["last_reviewed_at"],
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn lists_ghosts_with_their_last_review() {
         assert_eq!(
-            format_upcoming_label("not-a-date"),
-            "not-a-date".to_string()
+            format_ghosts(&fixed_stats()),
+            "2 ghost cards in the DB match no card file:\n\
+             abc123  last reviewed 2024-12-24\n\
+             def456  never reviewed\n"
         );
+        assert_eq!(
+            format_ghosts(&CardStats::default()),
+            "No ghost cards: every DB row matches a card file.\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn explain_shows_the_stored_schedule() {
+        use crate::fsrs::ReviewStatus;
+        use crate::parser::content_to_card;
+
+        let db = DB::new_in_memory().await.unwrap();
+        let card = content_to_card(&PathBuf::from("deck.md"), "Q: why?\nA: because", 2, 4).unwrap();
+        db.add_card(&card).await.unwrap();
+
+        let now = chrono::Utc::now();
+        let new = format_explanation(&card, &Performance::New, now);
+        assert!(new.starts_with("deck.md:3  why?\n"));
+        assert!(new.contains("status          new;"));
+
+        let reviewed_at = now - chrono::Duration::days(10);
+        db.update_card_performance(&card, ReviewStatus::Pass, Some(reviewed_at))
+            .await
+            .unwrap();
+        let performance = db.get_card_performance(&card).await.unwrap();
+        let Performance::Reviewed(reviewed) = performance else {
+            panic!("card was reviewed");
+        };
+        let explained = format_explanation(&card, &performance, now);
+        assert!(explained.contains(&format!(
+            "  stability       {:.2} days\n",
+            reviewed.stability
+        )));
+        assert!(explained.contains(&format!("  difficulty      {:.2}\n", reviewed.difficulty)));
+        assert!(explained.contains("days overdue)\n"));
+        let recall = retrievability(&performance, now).unwrap();
+        assert!(explained.contains(&format!("  retrievability  {:.0}%\n", recall * 100.0)));
+    }
+
+    #[tokio::test]
+    async fn plan_lists_the_cards_due_today_returns() {
+        use crate::fsrs::ReviewStatus;
+        use crate::parser::content_to_card;
+        use std::collections::HashMap;
+
+        let db = DB::new_in_memory().await.unwrap();
+        let path = PathBuf::from("deck.md");
+        let mut card_hashes = HashMap::new();
+        for (idx, name) in ["a", "b", "c", "d"].iter().enumerate() {
+            let card = content_to_card(
+                &path,
+                &format!("Q: {name}?\nmore\nA: {name}"),
+                idx * 4,
+                idx * 4 + 3,
+            )
+            .unwrap();
+            db.add_card(&card).await.unwrap();
+            card_hashes.insert(card.card_hash.clone(), card);
+        }
+        // Reviewed on an earlier day, so it doesn't use up today's new cards.
+        let reviewed = card_hashes.values().last().unwrap();
+        let yesterday = chrono::Utc::now() - chrono::Duration::days(1);
+        db.update_card_performance(reviewed, ReviewStatus::Fail, Some(yesterday))
+            .await
+            .unwrap();
+
+        let options = DrillOptions {
+            card_limit: Some(3),
+            new_card_limit: Some(2),
+            ..Default::default()
+        };
+        let planned = drill::plan(&db, &card_hashes, &options).await.unwrap();
+        let due = db.due_today(&card_hashes, Some(3), Some(2)).await.unwrap();
+        let hashes = |cards: &[Card]| {
+            cards
+                .iter()
+                .map(|card| card.card_hash.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hashes(&planned), hashes(&due));
+        assert_eq!(planned.len(), 3);
+
+        let listing = format_plan(&planned);
+        assert!(listing.starts_with("3 cards in the next drill:\n"));
+        assert!(listing.contains("deck.md:1  a?\n"));
+        assert_eq!(format_plan(&[]), "No cards due.\n");
+    }
+
+    #[test]
+    fn format_upcoming_label_pretty_prints_dates() {
+        let day = NaiveDate::from_ymd_opt(2024, 12, 25).unwrap();
+        assert_eq!(format_upcoming_label(day, "%a %d"), "Wed 25");
+    }
+
+    #[test]
+    fn format_upcoming_label_uses_the_configured_format() {
+        let day = NaiveDate::from_ymd_opt(2024, 12, 25).unwrap();
+        assert_eq!(format_upcoming_label(day, "%d/%m"), "25/12");
     }
 }
@@ -4,9 +4,11 @@ use crate::{
     palette::Palette,
     parser::{cards_from_md, content_to_card},
     tui::Editor,
+    tui::TerminalGuard,
     tui::Theme,
-    utils::ask_yn,
-    utils::is_markdown,
+    tui::centered_column,
+    tui::read_key,
+    utils::{ask_yn, can_prompt, is_markdown},
 };
 
 use std::{
@@ -14,13 +16,13 @@ use std::{
     fs::{self, File, OpenOptions},
     io::{self, Write},
     path::{Path, PathBuf},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, bail};
 use crossterm::{
     event::{
-        self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        self, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
         PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
     },
     execute,
@@ -35,11 +37,49 @@ use ratatui::{
 };
 
 const FLASH_SECS: f64 = 1.5;
+/// Appended to the deck's file name for the file holding an unsaved card.
+const AUTOSAVE_SUFFIX: &str = ".repeat-autosave";
 
-pub async fn run(db: &DB, card_path: PathBuf) -> Result<()> {
+/// Card fields given on the command line to start the editor with.
+#[derive(Clone, Debug, Default)]
+pub struct Prefill {
+    pub front: Option<String>,
+    pub back: Option<String>,
+    pub cloze: Option<String>,
+}
+
+impl Prefill {
+    /// The card type and editor text for these fields, or `None` to start
+    /// from the empty template. A cloze wins over front and back.
+    fn buffer(&self) -> Option<(CardType, String)> {
+        if let Some(cloze) = &self.cloze {
+            return Some((CardType::Cloze, format!("C: {}", cloze.trim())));
+        }
+        if self.front.is_none() && self.back.is_none() {
+            return None;
+        }
+        let field = |value: &Option<String>| value.as_deref().unwrap_or("").trim().to_string();
+        Some((
+            CardType::Basic,
+            format!("Q: {}\nA: {}", field(&self.front), field(&self.back)),
+        ))
+    }
+}
+
+pub async fn run(
+    db: &DB,
+    card_path: PathBuf,
+    max_width: Option<u16>,
+    prefill: Prefill,
+    autosave: Option<Duration>,
+) -> Result<()> {
     if !is_markdown(&card_path) {
         bail!("Card path must be a markdown file: {}", card_path.display());
     }
+    // The editor and its prompts both need a terminal.
+    if !can_prompt() {
+        bail!("create needs an interactive terminal");
+    }
 
     let file_exists = card_path.is_file();
     if !file_exists {
@@ -54,10 +94,67 @@ pub async fn run(db: &DB, card_path: PathBuf) -> Result<()> {
         create_file(&card_path)?;
     }
 
-    capture_cards(db, &card_path).await?;
+    let mut initial = prefill.buffer();
+    if initial.is_none()
+        && let Some(unsaved) = read_autosave(&card_path)
+    {
+        if ask_yn(format!(
+            "Found a card you hadn't saved in {}. Restore it?",
+            Palette::paint(Palette::ACCENT, card_path.display())
+        )) {
+            initial = Some((buffer_card_type(&unsaved), unsaved));
+        } else {
+            clear_autosave(&card_path);
+        }
+    }
+
+    capture_cards(db, &card_path, max_width, initial, autosave).await?;
     Ok(())
 }
 
+fn autosave_path(card_path: &Path) -> PathBuf {
+    let mut name = card_path.file_name().unwrap_or_default().to_os_string();
+    name.push(AUTOSAVE_SUFFIX);
+    card_path.with_file_name(name)
+}
+
+/// Whether `contents` holds anything beyond the empty `Q:`/`A:`/`C:` labels.
+fn has_card_text(contents: &str) -> bool {
+    contents
+        .lines()
+        .any(|line| !matches!(line.trim(), "" | "Q:" | "A:" | "C:"))
+}
+
+/// Keeps the editor's unsaved `contents` next to the deck, or removes the
+/// autosave file when there is nothing worth keeping.
+fn write_autosave(card_path: &Path, contents: &str) -> io::Result<()> {
+    if has_card_text(contents) {
+        fs::write(autosave_path(card_path), contents)
+    } else {
+        clear_autosave(card_path);
+        Ok(())
+    }
+}
+
+/// The unsaved card left by an earlier `create` session on this deck.
+fn read_autosave(card_path: &Path) -> Option<String> {
+    fs::read_to_string(autosave_path(card_path))
+        .ok()
+        .filter(|contents| has_card_text(contents))
+}
+
+fn clear_autosave(card_path: &Path) {
+    let _ = fs::remove_file(autosave_path(card_path));
+}
+
+fn buffer_card_type(contents: &str) -> CardType {
+    if contents.trim_start().starts_with("C:") {
+        CardType::Cloze
+    } else {
+        CardType::Basic
+    }
+}
+
 fn create_file(path: &Path) -> Result<File> {
     if let Some(parent) = path.parent()
         && !parent.as_os_str().is_empty()
@@ -69,29 +166,54 @@ fn create_file(path: &Path) -> Result<File> {
 }
 
 async fn create_card_append_file(db: &DB, path: &Path, contents: &str) -> Result<()> {
-    let existing_len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-    let start_idx = existing_len as usize;
-    let end_idx = start_idx + contents.len();
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let (block, start_idx) = separated_block(&existing, contents);
+    let end_idx = start_idx + block.len();
 
-    let card = content_to_card(path, contents, start_idx, end_idx).context("Invalid card")?;
+    let card = content_to_card(path, &block, start_idx, end_idx).context("Invalid card")?;
     let card_exists = db.card_exists(&card).await?;
     if card_exists {
         bail!("This card already exists in the database.");
     }
 
     let mut file = create_file(path)?;
-    if start_idx > 0 {
-        writeln!(file)?;
-    }
-    writeln!(file, "{}", contents)?;
+    write!(file, "{}{}", separator(&existing), block)?;
 
     db.add_card(&card).await?;
 
     Ok(())
 }
 
-async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
+/// Cards in a file are separated by exactly one blank line. This is what has
+/// to be written before a new card so that `existing` ends with one, without
+/// adding blank lines to the end of the card above it.
+fn separator(existing: &str) -> &'static str {
+    if existing.is_empty() || existing.ends_with("\n\n") {
+        ""
+    } else if existing.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    }
+}
+
+/// The card as it will be appended, stripped of surrounding blank lines and
+/// ending in a single newline, and the byte offset it will start at.
+fn separated_block(existing: &str, contents: &str) -> (String, usize) {
+    let block = format!("{}\n", contents.trim_start_matches(['\r', '\n']).trim_end());
+    let start_idx = existing.len() + separator(existing).len();
+    (block, start_idx)
+}
+
+async fn capture_cards(
+    db: &DB,
+    card_path: &Path,
+    max_width: Option<u16>,
+    initial: Option<(CardType, String)>,
+    autosave: Option<Duration>,
+) -> Result<()> {
     enable_raw_mode()?;
+    let guard = TerminalGuard::for_terminal();
     let mut stdout = io::stdout();
     execute!(
         stdout,
@@ -105,7 +227,13 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
     terminal.show_cursor()?;
     let editor_result: Result<()> = async {
-        let mut editor = Editor::new();
+        let mut editor = match initial {
+            Some((card_type, content)) => Editor::with_content(card_type, &content),
+            None => Editor::new(),
+        };
+        let mut last_autosave = Instant::now();
+        // Set when the autosave on exit failed, so a second exit key discards.
+        let mut exit_without_autosave = false;
         let mut status: Option<String> = None;
         let existing_cards = cards_from_md(card_path)?;
         let unique_hashes: HashSet<_> = existing_cards.into_iter().map(|c| c.card_hash).collect();
@@ -115,9 +243,20 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
         let mut card_last_save_attempt: Option<std::time::Instant> = None;
         let mut view_height = 0usize;
         loop {
+            if let Some(every) = autosave
+                && last_autosave.elapsed() >= every
+            {
+                // A failed autosave must not end the session and lose the buffer.
+                if let Err(err) = write_autosave(card_path, &editor.content()) {
+                    card_last_save_attempt = Some(Instant::now());
+                    status = Some(format!("Unable to autosave: {err}"));
+                }
+                last_autosave = Instant::now();
+            }
             terminal.draw(|frame| {
                 let area = frame.area();
                 frame.render_widget(Theme::backdrop(), area);
+                let area = centered_column(area, max_width);
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
                     .constraints([Constraint::Min(5), Constraint::Length(5)])
@@ -183,7 +322,7 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
             })?;
 
             if event::poll(Duration::from_millis(16))?
-                && let Event::Key(key) = event::read()?
+                && let Some(key) = read_key(&mut terminal)?
             {
                 if key.kind != KeyEventKind::Press {
                     continue;
@@ -192,8 +331,20 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
                     || (key.code == KeyCode::Char('c')
                         && key.modifiers.contains(KeyModifiers::CONTROL))
                 {
+                    if autosave.is_some()
+                        && !exit_without_autosave
+                        && let Err(err) = write_autosave(card_path, &editor.content())
+                    {
+                        exit_without_autosave = true;
+                        card_last_save_attempt = Some(Instant::now());
+                        status = Some(format!(
+                            "Unable to autosave: {err}. Press Esc again to exit without it."
+                        ));
+                        continue;
+                    }
                     break;
                 }
+                exit_without_autosave = false;
                 if key.code == KeyCode::Char('b') && key.modifiers.contains(KeyModifiers::CONTROL) {
                     editor.card_type = CardType::Basic;
                     editor.clear();
@@ -211,6 +362,7 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
                     match save_status {
                         Ok(_) => {
                             editor.clear();
+                            clear_autosave(card_path);
                             card_created_count += 1;
                             num_cards_in_collection += 1;
                             card_last_save_attempt = Some(std::time::Instant::now());
@@ -268,6 +420,7 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
         LeaveAlternateScreen
     )?;
     terminal.show_cursor()?;
+    guard.disarm();
 
     editor_result
 }
@@ -287,4 +440,92 @@ mod tests {
         let result = create_card_append_file(&db, &card_path, content).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn appended_cards_are_separated_by_one_blank_line() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let card_path = dir.path().join("deck.md");
+        fs::write(&card_path, "Q: first?\nA: one").unwrap();
+
+        let contents = [
+            "Q: second?\nA: two\n\n\n",
+            "\nC: The [third] card",
+            "Q: fourth?\nA: four",
+        ];
+        for content in contents {
+            create_card_append_file(&db, &card_path, content)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(&card_path).unwrap(),
+            "Q: first?\nA: one\n\nQ: second?\nA: two\n\nC: The [third] card\n\nQ: fourth?\nA: four\n"
+        );
+
+        let parsed = cards_from_md(&card_path).unwrap();
+        assert_eq!(parsed.len(), 4);
+        for card in &parsed[1..] {
+            assert!(db.card_exists(card).await.unwrap());
+        }
+        let ranges: Vec<_> = parsed.iter().map(|card| card.file_card_range).collect();
+        assert_eq!(ranges, vec![(0, 3), (3, 6), (6, 8), (8, 10)]);
+    }
+
+    #[test]
+    fn autosave_is_written_detected_and_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let card_path = dir.path().join("deck.md");
+        assert_eq!(read_autosave(&card_path), None);
+
+        // The bare template is not worth restoring.
+        write_autosave(&card_path, "Q: \nA: ").unwrap();
+        assert!(!autosave_path(&card_path).exists());
+
+        write_autosave(&card_path, "C: Half a [thought").unwrap();
+        assert_eq!(
+            autosave_path(&card_path),
+            dir.path().join("deck.md.repeat-autosave")
+        );
+        let unsaved = read_autosave(&card_path).unwrap();
+        assert_eq!(unsaved, "C: Half a [thought");
+        assert_eq!(buffer_card_type(&unsaved), CardType::Cloze);
+
+        clear_autosave(&card_path);
+        assert_eq!(read_autosave(&card_path), None);
+    }
+
+    #[test]
+    fn prefill_builds_the_starting_buffer() {
+        assert!(Prefill::default().buffer().is_none());
+
+        let basic = Prefill {
+            front: Some("What is 2 + 2?".into()),
+            back: Some(" 4\n".into()),
+            cloze: None,
+        };
+        let (card_type, content) = basic.buffer().unwrap();
+        assert_eq!(card_type, CardType::Basic);
+        assert_eq!(content, "Q: What is 2 + 2?\nA: 4");
+        let editor = Editor::with_content(card_type, &content);
+        assert_eq!(editor.content(), content);
+        assert_eq!(editor.cursor(), (1, 4));
+
+        let front_only = Prefill {
+            front: Some("Capital of Peru?".into()),
+            ..Default::default()
+        };
+        assert_eq!(front_only.buffer().unwrap().1, "Q: Capital of Peru?\nA: ");
+
+        let cloze = Prefill {
+            cloze: Some("The capital of Peru is [Lima].".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cloze.buffer(),
+            Some((CardType::Cloze, "C: The capital of Peru is [Lima].".into()))
+        );
+        let (_, content) = cloze.buffer().unwrap();
+        assert!(content_to_card(Path::new("test.md"), &content, 0, 1).is_ok());
+    }
 }
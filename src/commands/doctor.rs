@@ -0,0 +1,327 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::crud::DB;
+use crate::crud::db::DB_FILE_NAME;
+use crate::llm::secrets::{ApiKeyLookup, get_api_key_from_sources};
+use crate::palette::Palette;
+use crate::parser::{CollectOptions, scan_cards};
+use crate::utils::pluralize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    fn print(&self) {
+        let mark = match self.status {
+            CheckStatus::Pass => Palette::paint(Palette::SUCCESS, "✔"),
+            CheckStatus::Warn => Palette::paint(Palette::WARNING, "!"),
+            CheckStatus::Fail => Palette::paint(Palette::DANGER, "✘"),
+        };
+        println!("{mark} {:<12} {}", self.name, Palette::dim(&self.detail));
+    }
+}
+
+/// Runs every diagnostic and prints a checklist. Nothing on disk is created or changed.
+pub async fn run(paths: Vec<PathBuf>) -> Result<()> {
+    let mut results = Vec::new();
+    match DB::data_dir() {
+        Ok(data_dir) => {
+            results.push(check_data_dir(&data_dir));
+            results.push(check_database(&data_dir.join(DB_FILE_NAME)).await);
+        }
+        Err(err) => results.push(CheckResult::new(
+            "Data dir",
+            CheckStatus::Fail,
+            err.to_string(),
+        )),
+    }
+    results.push(check_cards(paths));
+    results.push(check_api_key(get_api_key_from_sources()));
+
+    for result in &results {
+        result.print();
+    }
+
+    let failures = results
+        .iter()
+        .filter(|result| result.status == CheckStatus::Fail)
+        .count();
+    if failures > 0 {
+        bail!("doctor found {}", pluralize("problem", failures));
+    }
+    Ok(())
+}
+
+fn check_data_dir(data_dir: &Path) -> CheckResult {
+    const NAME: &str = "Data dir";
+    if !data_dir.exists() {
+        return CheckResult::new(
+            NAME,
+            CheckStatus::Warn,
+            format!(
+                "{} does not exist yet; it is created on first use",
+                data_dir.display()
+            ),
+        );
+    }
+    if !data_dir.is_dir() {
+        return CheckResult::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not a directory", data_dir.display()),
+        );
+    }
+    // An anonymous temp file is removed as soon as it is dropped.
+    match tempfile::tempfile_in(data_dir) {
+        Ok(_) => CheckResult::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("{} is writable", data_dir.display()),
+        ),
+        Err(err) => CheckResult::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not writable: {err}", data_dir.display()),
+        ),
+    }
+}
+
+async fn check_database(db_path: &Path) -> CheckResult {
+    const NAME: &str = "Database";
+    if !db_path.exists() {
+        return CheckResult::new(
+            NAME,
+            CheckStatus::Warn,
+            format!(
+                "{} does not exist yet; it is created on first use",
+                db_path.display()
+            ),
+        );
+    }
+    let db = match DB::open_read_only(db_path).await {
+        Ok(db) => db,
+        Err(err) => {
+            return CheckResult::new(
+                NAME,
+                CheckStatus::Fail,
+                format!("cannot open {}: {err}", db_path.display()),
+            );
+        }
+    };
+    let version = match db.schema_version().await {
+        Ok(version) => version,
+        Err(err) => {
+            return CheckResult::new(
+                NAME,
+                CheckStatus::Fail,
+                format!("{}: {err:#}", db_path.display()),
+            );
+        }
+    };
+    match db.pending_migrations().await {
+        Ok(0) => CheckResult::new(
+            NAME,
+            CheckStatus::Pass,
+            format!(
+                "{} opens and the schema is current (version {version})",
+                db_path.display()
+            ),
+        ),
+        Ok(pending) => CheckResult::new(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "{} is missing {}; run any other command to apply them",
+                db_path.display(),
+                pluralize("migration", pending)
+            ),
+        ),
+        Err(err) => CheckResult::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{}: {err:#}", db_path.display()),
+        ),
+    }
+}
+
+fn check_cards(paths: Vec<PathBuf>) -> CheckResult {
+    const NAME: &str = "Cards";
+    let searched = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match scan_cards(paths, CollectOptions::default()) {
+        Ok((0, stats)) => CheckResult::new(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "no cards found in {searched} ({} searched)",
+                pluralize("markdown file", stats.markdown_files)
+            ),
+        ),
+        Ok((count, stats)) => CheckResult::new(
+            NAME,
+            if stats.skipped_files > 0 {
+                CheckStatus::Warn
+            } else {
+                CheckStatus::Pass
+            },
+            format!(
+                "{} parsed from {}{}",
+                pluralize("card", count),
+                pluralize("markdown file", stats.markdown_files),
+                if stats.skipped_files > 0 {
+                    format!(", {} unreadable", pluralize("file", stats.skipped_files))
+                } else {
+                    String::new()
+                }
+            ),
+        ),
+        Err(err) => CheckResult::new(NAME, CheckStatus::Fail, format!("{err:#}")),
+    }
+}
+
+fn check_api_key(lookup: Result<ApiKeyLookup>) -> CheckResult {
+    const NAME: &str = "LLM key";
+    match lookup {
+        Ok(ApiKeyLookup {
+            api_key: Some(_),
+            source,
+            ..
+        }) => CheckResult::new(
+            NAME,
+            CheckStatus::Pass,
+            format!(
+                "found in the {}",
+                source.map(|s| s.description()).unwrap_or("configuration")
+            ),
+        ),
+        Ok(_) => CheckResult::new(
+            NAME,
+            CheckStatus::Warn,
+            "not configured; the optional LLM helper is disabled",
+        ),
+        Err(err) => CheckResult::new(
+            NAME,
+            CheckStatus::Warn,
+            format!("keyring unavailable: {err}"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use anyhow::anyhow;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    use super::*;
+    use crate::llm::secrets::ApiKeySource;
+
+    #[test]
+    fn data_dir_check_reports_missing_writable_and_non_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(check_data_dir(dir.path()).status, CheckStatus::Pass);
+        assert_eq!(
+            check_data_dir(&dir.path().join("missing")).status,
+            CheckStatus::Warn
+        );
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(check_data_dir(&file).status, CheckStatus::Fail);
+        assert!(!dir.path().join("missing").exists());
+    }
+
+    #[tokio::test]
+    async fn database_check_requires_a_migrated_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.db");
+        assert_eq!(check_database(&missing).await.status, CheckStatus::Warn);
+        assert!(!missing.exists());
+
+        let empty = dir.path().join("empty.db");
+        std::fs::write(&empty, "").unwrap();
+        assert_eq!(check_database(&empty).await.status, CheckStatus::Fail);
+
+        let migrated = dir.path().join("cards.db");
+        let options = SqliteConnectOptions::from_str(&migrated.to_string_lossy())
+            .unwrap()
+            .create_if_missing(true);
+        DB::connect(options).await.unwrap();
+        assert_eq!(check_database(&migrated).await.status, CheckStatus::Pass);
+
+        let broken = dir.path().join("broken.db");
+        let options = SqliteConnectOptions::from_str(&broken.to_string_lossy())
+            .unwrap()
+            .create_if_missing(true);
+        let pool = sqlx::SqlitePool::connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE _sqlx_migrations (junk TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        let check = check_database(&broken).await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(
+            check.detail.contains("applied migrations"),
+            "{}",
+            check.detail
+        );
+    }
+
+    #[test]
+    fn cards_check_fails_when_no_cards_are_found() {
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(
+            check_cards(vec![empty.path().to_path_buf()]).status,
+            CheckStatus::Fail
+        );
+        assert_eq!(
+            check_cards(vec![PathBuf::from("test_data")]).status,
+            CheckStatus::Pass
+        );
+    }
+
+    #[test]
+    fn api_key_check_passes_only_with_a_key() {
+        let found = ApiKeyLookup {
+            api_key: Some("key".into()),
+            source: Some(ApiKeySource::Environment),
+            keyring_entry: None,
+        };
+        let missing = ApiKeyLookup {
+            api_key: None,
+            source: None,
+            keyring_entry: None,
+        };
+        assert_eq!(check_api_key(Ok(found)).status, CheckStatus::Pass);
+        assert_eq!(check_api_key(Ok(missing)).status, CheckStatus::Warn);
+        assert_eq!(
+            check_api_key(Err(anyhow!("no backend"))).status,
+            CheckStatus::Warn
+        );
+    }
+}
@@ -1,64 +1,587 @@
-use std::io;
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use crate::card::{Card, CardContent};
-use crate::cloze_utils::mask_cloze_text;
+use crate::card::{Card, CardContent, ClozeMode};
+use crate::cloze_utils::{
+    find_cloze_ranges, mask_cloze_text, occlude_cloze_text, unbracket_cloze_text,
+};
+use crate::commands::edits;
 use crate::crud::DB;
-use crate::fsrs::{LEARN_AHEAD_THRESHOLD_MINS, ReviewStatus};
+use crate::crud::sessions::SessionRecord;
+use crate::fsrs::{
+    FailKind, LEARN_AHEAD_THRESHOLD_MINS, Performance, ReviewStatus, TARGET_RECALL, retrievability,
+};
 use crate::llm::drill_preprocessor::{AIStatus, DrillPreprocessor};
-use crate::parser::register_all_cards;
+use crate::palette::{Palette, PaletteColor};
 use crate::parser::render_markdown;
+use crate::parser::{CollectOptions, register_all_cards};
 use crate::parser::{Media, extract_media};
-use crate::tui::Theme;
-use crate::utils::pluralize;
+use crate::stats::DayZone;
+use crate::tui::image::{
+    GraphicsProtocol, IMAGE_PROTOCOL_ENV, clear_sequence, encode_image, inline_image,
+};
+use crate::tui::{Spinner, TerminalGuard, Theme, centered_column, read_key};
+use crate::utils::{ask_yn, pluralize};
 
 use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
 use crossterm::event::KeyModifiers;
 use crossterm::{
+    cursor::MoveTo,
     event::{
-        self, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        self, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
         PushKeyboardEnhancementFlags,
     },
-    execute,
+    execute, queue,
+    style::Print,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use once_cell::sync::Lazy;
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Margin},
+    style::Style,
     text::{Line, Span},
-    widgets::{Paragraph, Wrap},
+    widgets::{Clear, Paragraph, Wrap},
 };
 use tokio::sync::mpsc;
 
 const MINUTES_PER_DAY: f64 = 24.0 * 60.0;
 const FLASH_SECS: f64 = 2.0;
+/// Tallest an inline image panel gets, in rows.
+const IMAGE_MAX_ROWS: u16 = 12;
+/// Text shown under `A:` while a basic card's answer is hidden; empty disables it.
+const ANSWER_PLACEHOLDER_ENV: &str = "REPEATER_ANSWER_PLACEHOLDER";
+const DEFAULT_ANSWER_PLACEHOLDER: &str = "(press space to reveal)";
+/// Set to `1`, `true` or `yes` to show a basic card's answer length before reveal.
+const ANSWER_LENGTH_HINT_ENV: &str = "REPEATER_ANSWER_LENGTH_HINT";
+/// Stands in for the hidden context of a reverse cloze.
+const REVERSE_CLOZE_PROMPT: &str = "Recall the text around this, then press space";
 
-pub async fn run(
-    db: &DB,
-    paths: Vec<PathBuf>,
-    card_limit: Option<usize>,
-    new_card_limit: Option<usize>,
-    rephrase_questions: bool,
-) -> Result<()> {
-    let (hash_cards, _) = register_all_cards(db, paths).await?;
-    let mut cards_due_today = db
-        .due_today(&hash_cards, card_limit, new_card_limit)
-        .await?;
+static ANSWER_PLACEHOLDER: Lazy<String> = Lazy::new(|| {
+    std::env::var(ANSWER_PLACEHOLDER_ENV)
+        .unwrap_or_else(|_| DEFAULT_ANSWER_PLACEHOLDER.to_string())
+        .trim()
+        .to_string()
+});
+
+static ANSWER_LENGTH_HINT: Lazy<bool> = Lazy::new(|| {
+    std::env::var(ANSWER_LENGTH_HINT_ENV).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        )
+    })
+});
+
+/// Order in which due cards are drilled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DrillOrder {
+    /// Most overdue first, then cards due today, then new cards.
+    #[default]
+    Due,
+    /// Lowest current retrievability first.
+    Weakest,
+    /// Most days past the due date first, whichever due mode picked the cards.
+    Overdue,
+}
+
+/// What `--card-limit` counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LimitCounts {
+    /// Distinct cards: failed and still-learning cards shown again don't count.
+    #[default]
+    Cards,
+    /// Every card shown and graded, so the session ends after that many grades
+    /// even if some cards were waiting to come back.
+    Presentations,
+}
+
+/// What makes a reviewed card due.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DueMode {
+    /// Its scheduled due date has passed.
+    #[default]
+    Date,
+    /// Its predicted recall has dropped below the target retention.
+    Retrievability,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DrillOptions {
+    pub card_limit: Option<usize>,
+    pub limit_counts: LimitCounts,
+    pub new_card_limit: Option<usize>,
+    pub rephrase_questions: bool,
+    pub grade_before_reveal: bool,
+    /// Don't show failed or still-learning cards again in the same session.
+    pub no_redo: bool,
+    /// Drill in plain text on stdout and stdin instead of the full-screen UI.
+    pub no_tui: bool,
+    pub strict: bool,
+    /// Read deck files with invalid UTF-8 by replacing the bad bytes.
+    pub lossy: bool,
+    pub order: DrillOrder,
+    /// With `DrillOrder::Weakest`, put new cards before reviewed ones.
+    pub new_first: bool,
+    /// Take due cards from each deck directory in turn.
+    pub round_robin: bool,
+    /// Draw inline images through a terminal graphics protocol.
+    pub images: bool,
+    pub due_mode: DueMode,
+    /// With `DueMode::Date`, also drill reviewed cards due within this long.
+    pub ahead: Option<chrono::Duration>,
+    /// Recall below which a card is due with `DueMode::Retrievability`;
+    /// `None` uses FSRS's target of 0.9.
+    pub retention: Option<f64>,
+    /// Drill each cloze card's reverse review instead of the cloze itself.
+    pub reverse_clozes: bool,
+    /// Print the slowest deck files to parse before the session starts.
+    pub verbose: bool,
+    /// Offer to keep the history of cards edited since the last tracked run.
+    pub track_edits: bool,
+    /// Widest the card column may get, in terminal columns; `None` fills the screen.
+    pub max_width: Option<u16>,
+    /// Hash or text of the card the first batch should start with.
+    pub start_at: Option<String>,
+    /// Show the card's full file path in the header rather than a short label.
+    pub full_path: bool,
+    /// Stop grading new cards once the drill has run this long.
+    pub time_budget: Option<Duration>,
+}
+
+impl DrillOptions {
+    /// Grades after which a session ends, when the card limit counts
+    /// presentations rather than distinct cards.
+    fn max_presentations(&self) -> Option<usize> {
+        match self.limit_counts {
+            LimitCounts::Cards => None,
+            LimitCounts::Presentations => self.card_limit,
+        }
+    }
+}
+
+pub async fn run(db: &DB, paths: Vec<PathBuf>, options: DrillOptions) -> Result<()> {
+    let collect_options = CollectOptions {
+        strict: options.strict,
+        lossy: options.lossy,
+        verbose: options.verbose,
+        track_edits: options.track_edits,
+        ..Default::default()
+    };
+    // The timing report and edit prompts would be drawn over by the spinner.
+    let spinner =
+        (!options.verbose && !options.track_edits).then(|| Spinner::start("Scanning decks..."));
+    let label_roots = (!options.full_path).then(|| paths.clone());
+    let (mut hash_cards, stats) = register_all_cards(db, paths, collect_options).await?;
+    if options.track_edits {
+        edits::carry_over(db, &hash_cards, &stats.edited_cards).await?;
+    }
+    if options.reverse_clozes {
+        hash_cards = reverse_clozes(db, hash_cards).await?;
+    }
+    let hash_cards = expand_cloze_deletions(db, hash_cards).await?;
+    drop(spinner);
+    for warning in &stats.duplicate_cards {
+        eprintln!("warning: {warning}");
+    }
+    let graphics = if options.images {
+        let detected = GraphicsProtocol::detect();
+        if detected.is_none() {
+            eprintln!(
+                "warning: --images: no supported graphics protocol detected (set {IMAGE_PROTOCOL_ENV} to override); showing image paths instead"
+            );
+        }
+        detected
+    } else {
+        None
+    };
+    let mut reviewed: HashSet<String> = HashSet::new();
+    let time_budget = options.time_budget.map(|budget| TimeBudget {
+        started: Instant::now(),
+        budget,
+    });
 
+    let mut cards_due_today = next_batch(db, &hash_cards, &reviewed, &options).await?;
     if cards_due_today.is_empty() {
         println!("All caught up—no cards due today.");
+        print_next_due(db, &hash_cards).await?;
         return Ok(());
     }
+    println!(
+        "{}",
+        launch_summary(hash_cards.len(), cards_due_today.len())
+    );
 
-    let drill_preprocessor = DrillPreprocessor::new(&cards_due_today, rephrase_questions)?;
-    drill_preprocessor.initialize_card_status(&mut cards_due_today);
-    start_drill_session(db, cards_due_today, drill_preprocessor).await?;
+    loop {
+        let performances = db.get_performances(&cards_due_today).await?;
+        reviewed.extend(cards_due_today.iter().map(|card| card.card_hash.clone()));
+
+        let drill_preprocessor =
+            DrillPreprocessor::new(&cards_due_today, options.rephrase_questions)?;
+        drill_preprocessor.initialize_card_status(&mut cards_due_today);
+        let batch = Batch {
+            cards: cards_due_today,
+            preprocessor: drill_preprocessor,
+            performances,
+        };
+        let session_end = if options.no_tui {
+            run_plain_session(
+                db,
+                batch,
+                time_budget,
+                &options,
+                io::stdin().lock(),
+                &mut io::stdout(),
+            )
+            .await?
+        } else {
+            start_drill_session(
+                db,
+                batch,
+                graphics,
+                label_roots.as_deref(),
+                time_budget,
+                &options,
+            )
+            .await?
+        };
+
+        // Without a cap every due card was already in the session.
+        let Some(limit) = options.card_limit else {
+            break;
+        };
+        if session_end == SessionEnd::Quit || time_budget.is_some_and(|budget| !budget.remains()) {
+            break;
+        }
+        cards_due_today = next_batch(db, &hash_cards, &reviewed, &options).await?;
+        if cards_due_today.is_empty() {
+            println!("All caught up—no more cards due today.");
+            print_next_due(db, &hash_cards).await?;
+            break;
+        }
+        if !ask_yn(format!(
+            "Session complete. Drill {} more?",
+            pluralize("due card", cards_due_today.len().min(limit))
+        )) {
+            break;
+        }
+    }
 
     Ok(())
 }
 
+/// The cards `next_batch` picked for one session, with what drilling them needs.
+struct Batch {
+    cards: Vec<Card>,
+    preprocessor: DrillPreprocessor,
+    performances: HashMap<String, Performance>,
+}
+
+/// `--time`: when the drill started and how long it may run.
+#[derive(Clone, Copy, Debug)]
+struct TimeBudget {
+    started: Instant,
+    budget: Duration,
+}
+
+impl TimeBudget {
+    fn remains(&self) -> bool {
+        within_time_budget(self.started.elapsed(), self.budget)
+    }
+}
+
+/// Whether a drill that has run for `elapsed` may show another card. The
+/// card on screen when the budget runs out is still finished and graded.
+fn within_time_budget(elapsed: Duration, budget: Duration) -> bool {
+    elapsed < budget
+}
+
+/// Short name for a card's file in the drill header: its path under whichever
+/// drilled root contains it, or its folder and file name when none does.
+fn deck_label(file: &Path, roots: &[PathBuf]) -> String {
+    for root in roots {
+        if let Ok(relative) = file.strip_prefix(root) {
+            // A root that is the file itself leaves nothing to show.
+            if !relative.as_os_str().is_empty() {
+                return relative.display().to_string();
+            }
+        }
+    }
+    let name = file.file_name().map(Path::new).unwrap_or(file);
+    match file.parent().and_then(Path::file_name) {
+        Some(folder) => Path::new(folder).join(name).display().to_string(),
+        None => name.display().to_string(),
+    }
+}
+
+/// Printed while the first batch loads, e.g. `Loading 240 cards, 18 due...`.
+fn launch_summary(card_count: usize, due_count: usize) -> String {
+    format!(
+        "Loading {}, {due_count} due...",
+        pluralize("card", card_count)
+    )
+}
+
+async fn print_next_due(db: &DB, hash_cards: &HashMap<String, Card>) -> Result<()> {
+    if let Some(due) = db.next_due_date(hash_cards).await? {
+        println!(
+            "Next card due {}.",
+            format_next_due(due, chrono::Utc::now(), DayZone::from_env())
+        );
+    }
+    Ok(())
+}
+
+/// Describes how far away `due` is, e.g. `in 3 days (Fri)` or `in 4 hours`.
+fn format_next_due(
+    due: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+    day_zone: DayZone,
+) -> String {
+    let due_day = day_zone.date_of(due);
+    let days = (due_day - day_zone.date_of(now)).num_days();
+    let weekday = due_day.format("%a");
+    match days {
+        ..=0 => {
+            let minutes = (due - now).num_minutes().max(1);
+            if minutes < 60 {
+                format!("in {}", pluralize("minute", minutes as usize))
+            } else {
+                format!("in {}", pluralize("hour", (minutes / 60) as usize))
+            }
+        }
+        1 => format!("tomorrow ({weekday})"),
+        _ => format!("in {} ({weekday})", pluralize("day", days as usize)),
+    }
+}
+
+/// Swaps every cloze card for its reverse review and registers the reverse
+/// hashes, so recalling the context is scheduled apart from the cloze.
+async fn reverse_clozes(
+    db: &DB,
+    hash_cards: HashMap<String, Card>,
+) -> Result<HashMap<String, Card>> {
+    let cards: HashMap<String, Card> = hash_cards
+        .into_values()
+        .map(|card| card.reversed_cloze().unwrap_or(card))
+        .map(|card| (card.card_hash.clone(), card))
+        .collect();
+    let reversed: Vec<Card> = cards
+        .values()
+        .filter(|card| card.reversed)
+        .cloned()
+        .collect();
+    db.add_cards_batch(&reversed).await?;
+    Ok(cards)
+}
+
+/// Replaces each multi-deletion cloze card with one item per deletion, keyed
+/// by the item's own hash, and registers the items so each is scheduled
+/// separately. Multi-cloze cards are drilled once per deletion, so the queue
+/// (and the `Card X/Y` header) counts these items rather than file-level cards.
+async fn expand_cloze_deletions(
+    db: &DB,
+    hash_cards: HashMap<String, Card>,
+) -> Result<HashMap<String, Card>> {
+    let mut expanded = HashMap::with_capacity(hash_cards.len());
+    let mut added = Vec::new();
+    for card in hash_cards.into_values() {
+        for item in card.drill_items() {
+            if item.card_hash != card.card_hash {
+                added.push(item.clone());
+            }
+            expanded.insert(item.card_hash.clone(), item);
+        }
+    }
+    db.add_cards_batch(&added).await?;
+    Ok(expanded)
+}
+
+/// The cards a fresh `drill` with `options` would start with, in order.
+pub async fn plan(
+    db: &DB,
+    hash_cards: &HashMap<String, Card>,
+    options: &DrillOptions,
+) -> Result<Vec<Card>> {
+    let hash_cards = expand_cloze_deletions(db, hash_cards.clone()).await?;
+    next_batch(db, &hash_cards, &HashSet::new(), options).await
+}
+
+/// Fetches the next `card_limit`-sized batch of due cards, skipping any card
+/// already drilled this session. New cards introduced earlier today, in this
+/// session or an earlier one, count against `new_card_limit`.
+async fn next_batch(
+    db: &DB,
+    hash_cards: &HashMap<String, Card>,
+    reviewed: &HashSet<String>,
+    options: &DrillOptions,
+) -> Result<Vec<Card>> {
+    // Counted over every drilled card, so new cards graded in an earlier
+    // batch of this session use up the allowance too.
+    let new_card_limit = db
+        .new_cards_left_today(hash_cards, options.new_card_limit)
+        .await?;
+    let candidates = exclude_reviewed(hash_cards, reviewed);
+    // Reordering, and looking for the `--start-at` card, have to see every
+    // due card before the session limit is applied.
+    let starts_at = reviewed.is_empty() && options.start_at.is_some();
+    let due_limit = match options.order {
+        DrillOrder::Due if !options.round_robin && !starts_at => options.card_limit,
+        DrillOrder::Due | DrillOrder::Weakest | DrillOrder::Overdue => None,
+    };
+    let mut cards = match options.due_mode {
+        DueMode::Date => {
+            let ahead = options.ahead.unwrap_or_else(chrono::Duration::zero);
+            db.due_within(&candidates, ahead, due_limit, new_card_limit)
+                .await?
+        }
+        DueMode::Retrievability => {
+            let retention = options.retention.unwrap_or(TARGET_RECALL);
+            db.due_below_retention(&candidates, retention, due_limit, new_card_limit)
+                .await?
+        }
+    };
+    match options.order {
+        DrillOrder::Due => {}
+        DrillOrder::Weakest => {
+            let performances = db.get_performances(&cards).await?;
+            order_weakest_first(
+                &mut cards,
+                &performances,
+                chrono::Utc::now(),
+                options.new_first,
+            );
+        }
+        DrillOrder::Overdue => {
+            let performances = db.get_performances(&cards).await?;
+            order_most_overdue_first(
+                &mut cards,
+                &performances,
+                chrono::Utc::now(),
+                options.new_first,
+            );
+        }
+    }
+    if options.round_robin {
+        cards = interleave_decks(cards);
+    }
+    if reviewed.is_empty()
+        && let Some(target) = &options.start_at
+        && !rotate_to_match(&mut cards, target)
+    {
+        eprintln!("warning: --start-at: no due card matches `{target}`; starting from the top");
+    }
+    if let Some(limit) = options.card_limit {
+        cards.truncate(limit);
+    }
+    Ok(cards)
+}
+
+/// Rotates `cards` so the first one whose hash is `target`, or whose text
+/// contains it, comes first; the rest keep their order after it. Returns
+/// whether any card matched.
+fn rotate_to_match(cards: &mut [Card], target: &str) -> bool {
+    let found = cards
+        .iter()
+        .position(|card| card.card_hash == target || card.matches_query(target));
+    if let Some(index) = found {
+        cards.rotate_left(index);
+    }
+    found.is_some()
+}
+
+/// Interleaves cards by the directory holding their file, taking one from
+/// each directory in turn. Directories take turns in the order their first
+/// card appears, and each keeps its own cards in their existing order.
+fn interleave_decks(cards: Vec<Card>) -> Vec<Card> {
+    let mut decks: Vec<(PathBuf, VecDeque<Card>)> = Vec::new();
+    for card in cards {
+        let dir = card
+            .file_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        match decks.iter_mut().find(|(known, _)| *known == dir) {
+            Some((_, queue)) => queue.push_back(card),
+            None => decks.push((dir, VecDeque::from([card]))),
+        }
+    }
+    let mut interleaved = Vec::new();
+    while !decks.is_empty() {
+        for (_, queue) in &mut decks {
+            interleaved.extend(queue.pop_front());
+        }
+        decks.retain(|(_, queue)| !queue.is_empty());
+    }
+    interleaved
+}
+
+fn exclude_reviewed(
+    hash_cards: &HashMap<String, Card>,
+    reviewed: &HashSet<String>,
+) -> HashMap<String, Card> {
+    hash_cards
+        .iter()
+        .filter(|(hash, _)| !reviewed.contains(*hash))
+        .map(|(hash, card)| (hash.clone(), card.clone()))
+        .collect()
+}
+
+/// Sorts cards by ascending retrievability. New cards have none and go last,
+/// or first with `new_first`. Ties keep their due order.
+fn order_weakest_first(
+    cards: &mut [Card],
+    performances: &HashMap<String, Performance>,
+    now: chrono::DateTime<chrono::Utc>,
+    new_first: bool,
+) {
+    let recall = |card: &Card| {
+        performances
+            .get(&card.card_hash)
+            .and_then(|perf| retrievability(perf, now))
+    };
+    sort_reviewed_then_new(cards, new_first, recall, |a, b| a.total_cmp(b));
+}
+
+/// Sorts cards by how long ago they fell due, most overdue first. New cards
+/// have no due date and go last, or first with `new_first`. Ties keep their
+/// due order.
+fn order_most_overdue_first(
+    cards: &mut [Card],
+    performances: &HashMap<String, Performance>,
+    now: chrono::DateTime<chrono::Utc>,
+    new_first: bool,
+) {
+    let overdue = |card: &Card| match performances.get(&card.card_hash) {
+        Some(Performance::Reviewed(perf)) => Some(now - perf.due_date),
+        _ => None,
+    };
+    sort_reviewed_then_new(cards, new_first, overdue, |a, b| b.cmp(a));
+}
+
+/// Stable sort of reviewed cards by `compare` on their `key`; cards without
+/// a key are new and go after them, or before with `new_first`.
+fn sort_reviewed_then_new<K>(
+    cards: &mut [Card],
+    new_first: bool,
+    key: impl Fn(&Card) -> Option<K>,
+    compare: impl Fn(&K, &K) -> Ordering,
+) {
+    cards.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a), Some(b)) => compare(&a, &b),
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) if new_first => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) if new_first => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+    });
+}
+
 struct DrillState<'a> {
     db: &'a DB,
     cards: Vec<Card>,
@@ -67,6 +590,39 @@ struct DrillState<'a> {
     show_answer: bool,
     last_action: Option<LastAction>,
     current_medias: Vec<Media>,
+    search_input: Option<String>,
+    /// Verbatim Markdown of the current card, shown over it while `Some`.
+    source_popup: Option<String>,
+    filter: Option<String>,
+    set_aside: Vec<Card>,
+    filter_notice: Option<(String, Instant)>,
+    grade_before_reveal: bool,
+    /// Never show a graded card again this session; failed cards just wait
+    /// for their next due date.
+    no_redo: bool,
+    /// End the session after this many grades, redos included.
+    max_presentations: Option<usize>,
+    time_budget: Option<TimeBudget>,
+    /// Set by the first grade after the time budget ran out.
+    out_of_time: bool,
+    /// Performance of each card when the session started, keyed by card hash.
+    performances: HashMap<String, Performance>,
+    passed: i64,
+    failed: i64,
+    /// How to take back the latest grade with `U`.
+    undo: Option<UndoEntry>,
+}
+
+/// What a grade changed in the session: the graded card, and the queue and
+/// tallies as they were, so undoing also drops any requeued copy. The card's
+/// earlier schedule comes back from the review log.
+struct UndoEntry {
+    card: Card,
+    cards: Vec<Card>,
+    redo_cards: Vec<Card>,
+    current_idx: usize,
+    passed: i64,
+    failed: i64,
 }
 struct LastAction {
     action: ReviewStatus,
@@ -101,36 +657,125 @@ impl<'a> DrillState<'a> {
             show_answer: false,
             last_action: None,
             current_medias: Vec::new(),
+            search_input: None,
+            source_popup: None,
+            filter: None,
+            set_aside: Vec::new(),
+            filter_notice: None,
+            grade_before_reveal: false,
+            no_redo: false,
+            max_presentations: None,
+            time_budget: None,
+            out_of_time: false,
+            performances: HashMap::new(),
+            passed: 0,
+            failed: 0,
+            undo: None,
         }
     }
 
     fn current_card(&mut self) -> Option<Card> {
+        if self.budget_used_up() {
+            return None;
+        }
         if self.current_idx >= self.cards.len() {
-            if self.redo_cards.is_empty() {
+            if !self.redo_cards.is_empty() {
+                self.cards = std::mem::take(&mut self.redo_cards);
+            } else if !self.set_aside.is_empty() {
+                // The filtered queue is exhausted, bring back everything else.
+                self.filter = None;
+                self.cards = std::mem::take(&mut self.set_aside);
+            } else {
                 return None;
             }
-            self.cards = std::mem::take(&mut self.redo_cards);
             self.current_idx = 0;
         }
         self.cards.get(self.current_idx).cloned()
     }
 
+    /// Narrows the remaining queue to cards matching `query`, setting the rest
+    /// aside. An empty query clears the filter and restores the set-aside cards.
+    fn apply_filter(&mut self, query: &str) {
+        let query = query.trim();
+        // The queue is rebuilt, so a snapshot taken before no longer fits it.
+        self.undo = None;
+        let start = self.current_idx.min(self.cards.len());
+        let mut pending = self.cards.split_off(start);
+        pending.append(&mut self.set_aside);
+        self.current_idx = 0;
+        self.show_answer = false;
+
+        if query.is_empty() {
+            self.filter = None;
+            self.cards = pending;
+            return;
+        }
+
+        let (matches, rest): (Vec<Card>, Vec<Card>) = pending
+            .into_iter()
+            .partition(|card| card.matches_query(query));
+        if matches.is_empty() {
+            self.filter_notice = Some((format!("No cards match \"{query}\""), Instant::now()));
+            self.filter = None;
+            self.cards = rest;
+            return;
+        }
+        self.filter = Some(query.to_string());
+        self.cards = matches;
+        self.set_aside = rest;
+    }
+
     fn reveal_answer(&mut self) {
         self.show_answer = true;
     }
 
+    fn can_grade(&self) -> bool {
+        self.show_answer || self.grade_before_reveal
+    }
+
+    /// Grades the current card, revealing it first if it is still hidden.
+    async fn reveal_and_grade(&mut self, action: ReviewStatus) -> Result<()> {
+        self.reveal_answer();
+        self.handle_review(action).await
+    }
+
     async fn handle_review(&mut self, action: ReviewStatus) -> Result<()> {
         let current_card = self
             .current_card()
             .expect("card should exist when handling review");
-        let show_again_duration = self
+        let undo = UndoEntry {
+            card: current_card.clone(),
+            cards: self.cards.clone(),
+            redo_cards: self.redo_cards.clone(),
+            current_idx: self.current_idx,
+            passed: self.passed,
+            failed: self.failed,
+        };
+        let outcome = self
             .db
             .update_card_performance(&current_card, action, None)
             .await?;
-        if action == ReviewStatus::Fail
-            || show_again_duration
+        let show_again_duration = outcome.interval_raw;
+        match action {
+            ReviewStatus::Fail => self.failed += 1,
+            ReviewStatus::Hard | ReviewStatus::Pass | ReviewStatus::Easy => self.passed += 1,
+        }
+        if self.no_redo {
+            // One pass only: the new schedule decides when the card comes back.
+        } else if let Some(fail_kind) = outcome.fail_kind {
+            match fail_kind {
+                // Still learning: show it again right away.
+                FailKind::Learning => self
+                    .cards
+                    .insert(self.current_idx + 1, current_card.clone()),
+                // Forgotten mature card: the lapse is recorded by the DB, see it again at the end.
+                FailKind::Lapse => self.redo_cards.push(current_card.clone()),
+            }
+        } else if action != ReviewStatus::Hard
+            && show_again_duration
                 < (LEARN_AHEAD_THRESHOLD_MINS.num_minutes() as f64 / MINUTES_PER_DAY)
         {
+            // Hard is a pass: its short step comes due later, not again this session.
             self.redo_cards.push(current_card.clone());
         }
 
@@ -139,13 +784,46 @@ impl<'a> DrillState<'a> {
             show_again_duration,
             last_reviewed_at: std::time::Instant::now(),
         });
+        self.undo = Some(undo);
         self.current_idx += 1;
         self.show_answer = false;
+        // Checked only after a grade, so the card on screen is never cut short.
+        self.out_of_time = self.time_budget.is_some_and(|budget| !budget.remains());
         Ok(())
     }
 
+    /// Takes back the latest grade: the card's schedule and log entry in the
+    /// database, any copy of it queued to come back, and the session tallies.
+    /// The card is shown again with its answer. Returns whether there was a
+    /// grade to undo.
+    async fn undo_last_review(&mut self) -> Result<bool> {
+        let Some(undo) = self.undo.take() else {
+            return Ok(false);
+        };
+        self.db.undo_last_review_of(&undo.card).await?;
+        self.cards = undo.cards;
+        self.redo_cards = undo.redo_cards;
+        self.current_idx = undo.current_idx;
+        self.passed = undo.passed;
+        self.failed = undo.failed;
+        self.last_action = None;
+        self.show_answer = true;
+        Ok(true)
+    }
+
     fn is_complete(&self) -> bool {
-        self.current_idx >= self.cards.len() && self.redo_cards.is_empty()
+        self.budget_used_up()
+            || (self.current_idx >= self.cards.len()
+                && self.redo_cards.is_empty()
+                && self.set_aside.is_empty())
+    }
+
+    /// Whether `--card-limit` presentations or the `--time` budget ended the session.
+    fn budget_used_up(&self) -> bool {
+        self.out_of_time
+            || self
+                .max_presentations
+                .is_some_and(|max| (self.passed + self.failed) as usize >= max)
     }
 
     fn apply_ai_update(&mut self, update: AiUpdate) {
@@ -167,6 +845,23 @@ impl<'a> DrillState<'a> {
     }
 }
 
+/// How a drill session ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SessionEnd {
+    /// Every queued card was graded.
+    Completed,
+    /// The user left early with Esc or Ctrl-C.
+    Quit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ImageKey {
+    idx: usize,
+    show_answer: bool,
+    path: PathBuf,
+    size: ratatui::layout::Size,
+}
+
 #[derive(Clone, Debug)]
 struct AiUpdate {
     card_hash: String,
@@ -175,10 +870,20 @@ struct AiUpdate {
 
 async fn start_drill_session(
     db: &DB,
-    cards: Vec<Card>,
-    drill_preprocessor: DrillPreprocessor,
-) -> Result<()> {
+    batch: Batch,
+    graphics: Option<GraphicsProtocol>,
+    label_roots: Option<&[PathBuf]>,
+    time_budget: Option<TimeBudget>,
+    options: &DrillOptions,
+) -> Result<SessionEnd> {
+    let Batch {
+        cards,
+        preprocessor: drill_preprocessor,
+        performances,
+    } = batch;
+    let started_at = chrono::Utc::now();
     enable_raw_mode().context("failed to enable raw mode")?;
+    let guard = TerminalGuard::for_terminal();
     let mut stdout = io::stdout();
     execute!(
         stdout,
@@ -204,11 +909,20 @@ async fn start_drill_session(
     };
 
     let mut state = DrillState::new(db, cards);
+    state.grade_before_reveal = options.grade_before_reveal;
+    state.no_redo = options.no_redo;
+    state.max_presentations = options.max_presentations();
+    state.time_budget = time_budget;
+    state.performances = performances;
+
+    // What the last inline image was drawn for, so it is only re-sent when the card,
+    // reveal state or terminal size changes.
+    let mut shown_image: Option<ImageKey> = None;
 
-    let loop_result: Result<()> = async {
+    let loop_result: Result<SessionEnd> = async {
         loop {
             if state.is_complete() {
-                break Ok(());
+                break Ok(SessionEnd::Completed);
             }
 
             while let Ok(update) = ai_updates_rx.try_recv() {
@@ -227,6 +941,33 @@ async fn start_drill_session(
                 ai_preprocess_handle = None;
             }
 
+            let image = match graphics {
+                Some(protocol) if !state.current_ai_pending() && state.source_popup.is_none() => {
+                    let card = state
+                        .current_card()
+                        .expect("card should exist while session is active");
+                    let content = format_card_text(&card, state.show_answer);
+                    let medias = extract_media(&content, card.file_path.parent());
+                    inline_image(&medias, Some(protocol))
+                        .map(|media| (protocol, media.path().to_path_buf()))
+                }
+                _ => None,
+            };
+            let image_key = image.as_ref().map(|(_, path)| ImageKey {
+                idx: state.current_idx,
+                show_answer: state.show_answer,
+                path: path.clone(),
+                size: terminal.size().unwrap_or_default(),
+            });
+            if image_key != shown_image
+                && let Some(protocol) = graphics
+                && shown_image.is_some()
+            {
+                write!(terminal.backend_mut(), "{}", clear_sequence(protocol))?;
+                terminal.clear().context("failed to clear terminal")?;
+            }
+            let mut image_area = None;
+
             terminal
                 .draw(|frame| {
                     let card = state
@@ -234,10 +975,25 @@ async fn start_drill_session(
                         .expect("card should exist while session is active");
                     let area = frame.area();
                     frame.render_widget(Theme::backdrop(), area);
+                    let area = centered_column(area, options.max_width);
+                    let image_rows = if image.is_some() {
+                        (area.height / 2).min(IMAGE_MAX_ROWS)
+                    } else {
+                        0
+                    };
                     let chunks = Layout::default()
                         .direction(Direction::Vertical)
-                        .constraints([Constraint::Min(5), Constraint::Length(5)])
+                        .constraints([
+                            Constraint::Min(5),
+                            Constraint::Length(image_rows),
+                            Constraint::Length(5),
+                        ])
                         .split(area);
+                    if image.is_some() {
+                        let block = Theme::panel_with_line(Theme::section_header("Image"));
+                        image_area = Some(block.inner(chunks[1]));
+                        frame.render_widget(block, chunks[1]);
+                    }
 
                     let mut header_vec = vec![
                         Theme::label_span(format!(
@@ -246,14 +1002,31 @@ async fn start_drill_session(
                             state.cards.len()
                         )),
                         Theme::bullet(),
+                        Theme::key_chip(review_label(
+                            state.performances.get(&card.card_hash),
+                            chrono::Utc::now(),
+                        )),
+                        difficulty_dot(state.performances.get(&card.card_hash)),
+                        Theme::bullet(),
                         Theme::span(format!("{} coming again", state.redo_cards.len())),
                         Theme::bullet(),
-                        Theme::span(card.file_path.display().to_string()),
+                        Theme::span(match label_roots {
+                            Some(roots) => deck_label(&card.file_path, roots),
+                            None => card.file_path.display().to_string(),
+                        }),
                     ];
                     if card.ai_status == AIStatus::AiEnhanced {
                         header_vec.push(Theme::bullet());
                         header_vec.push(Theme::key_chip("AI enhanced"));
                     }
+                    if let Some(filter) = &state.filter {
+                        header_vec.push(Theme::bullet());
+                        header_vec.push(Theme::span(format!(
+                            "Filter \"{}\" ({} set aside)",
+                            filter,
+                            state.set_aside.len()
+                        )));
+                    }
                     let header_line = Line::from(header_vec);
 
                     let ai_pending = state.current_ai_pending();
@@ -273,25 +1046,91 @@ async fn start_drill_session(
                     let instructions = instructions_text(&state);
                     let footer = Paragraph::new(instructions)
                         .block(Theme::panel_with_line(Theme::section_header("Controls")));
-                    frame.render_widget(footer, chunks[1]);
+                    frame.render_widget(footer, chunks[2]);
+
+                    if let Some(source) = &state.source_popup {
+                        let (path, start, end) = card.locator();
+                        let title = format!(
+                            "Source: {}:{}-{}",
+                            path.display(),
+                            start + 1,
+                            end.max(start + 1)
+                        );
+                        let popup_area = chunks[0].inner(Margin::new(2, 1));
+                        let popup = Paragraph::new(source.as_str())
+                            .block(Theme::panel_with_line(Theme::section_header(title)))
+                            .wrap(Wrap { trim: false });
+                        frame.render_widget(Clear, popup_area);
+                        frame.render_widget(popup, popup_area);
+                    }
                 })
                 .context("failed to render frame")?;
 
+            if image_key != shown_image {
+                if let (Some((protocol, path)), Some(area)) = (&image, image_area)
+                    && area.width > 0
+                    && area.height > 0
+                    // An image that fails to load keeps its text fallback in the card.
+                    && let Ok(encoded) = encode_image(*protocol, path, area.width, area.height)
+                {
+                    queue!(
+                        terminal.backend_mut(),
+                        MoveTo(area.x, area.y),
+                        Print(encoded)
+                    )?;
+                    terminal.backend_mut().flush()?;
+                }
+                shown_image = image_key;
+            }
+
             if event::poll(Duration::from_millis(16))?
-                && let Event::Key(key) = event::read()?
+                && let Some(key) = read_key(&mut terminal)?
             {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
 
-                if key.code == KeyCode::Esc
-                    || (key.code == KeyCode::Char('c')
-                        && key.modifiers.contains(KeyModifiers::CONTROL))
-                {
-                    break Ok(());
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    break Ok(SessionEnd::Quit);
+                }
+                if let Some(query) = state.search_input.as_mut() {
+                    match key.code {
+                        KeyCode::Esc => state.search_input = None,
+                        KeyCode::Enter => {
+                            let query = state.search_input.take().unwrap_or_default();
+                            state.apply_filter(&query);
+                        }
+                        KeyCode::Backspace => {
+                            query.pop();
+                        }
+                        KeyCode::Char(c) => query.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if state.source_popup.is_some() {
+                    if matches!(
+                        key.code,
+                        KeyCode::Esc | KeyCode::Char('r') | KeyCode::Char('R')
+                    ) {
+                        state.source_popup = None;
+                    }
+                    continue;
+                }
+                if key.code == KeyCode::Esc {
+                    break Ok(SessionEnd::Quit);
                 }
                 let ai_pending = state.current_ai_pending();
                 match key.code {
+                    KeyCode::Char('R') | KeyCode::Char('r') if !ai_pending => {
+                        let card = state
+                            .current_card()
+                            .expect("card should exist while session is active");
+                        state.source_popup = Some(read_card_source(&card));
+                    }
+                    KeyCode::Char('/') if !ai_pending => {
+                        state.search_input = Some(state.filter.clone().unwrap_or_default());
+                    }
                     KeyCode::Char(' ') | KeyCode::Enter if !ai_pending => {
                         if !state.show_answer {
                             state.reveal_answer();
@@ -302,6 +1141,21 @@ async fn start_drill_session(
                     KeyCode::Char('F') | KeyCode::Char('f') if state.show_answer && !ai_pending => {
                         state.handle_review(ReviewStatus::Fail).await?;
                     }
+                    KeyCode::Char('H') | KeyCode::Char('h') if state.show_answer && !ai_pending => {
+                        state.handle_review(ReviewStatus::Hard).await?;
+                    }
+                    KeyCode::Char('E') | KeyCode::Char('e') if state.show_answer && !ai_pending => {
+                        state.handle_review(ReviewStatus::Easy).await?;
+                    }
+                    KeyCode::Char('1') if state.can_grade() && !ai_pending => {
+                        state.reveal_and_grade(ReviewStatus::Fail).await?;
+                    }
+                    KeyCode::Char('2') if state.can_grade() && !ai_pending => {
+                        state.reveal_and_grade(ReviewStatus::Pass).await?;
+                    }
+                    KeyCode::Char('U') | KeyCode::Char('u') if state.undo.is_some() => {
+                        state.undo_last_review().await?;
+                    }
                     KeyCode::Char('O') | KeyCode::Char('o')
                         if !ai_pending
                             && !state.show_answer
@@ -317,11 +1171,167 @@ async fn start_drill_session(
     }
     .await;
 
+    if let Some(protocol) = graphics {
+        let _ = write!(terminal.backend_mut(), "{}", clear_sequence(protocol));
+    }
     teardown_terminal(&mut terminal)?;
+    guard.disarm();
+
+    record_session(db, &state, started_at).await?;
 
     loop_result
 }
 
+/// Prints the summary and saves the session, unless nothing was graded.
+async fn record_session(
+    db: &DB,
+    state: &DrillState<'_>,
+    started_at: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    let session = SessionRecord {
+        started_at,
+        ended_at: chrono::Utc::now(),
+        cards_reviewed: state.passed + state.failed,
+        passed: state.passed,
+        failed: state.failed,
+    };
+    if session.cards_reviewed > 0 {
+        println!("{}", session_summary(&session));
+        db.record_session(&session).await?;
+    }
+    Ok(())
+}
+
+/// The drill without the full-screen UI: each card is printed to `output` as
+/// plain text and answered by typing a line into `input`, so it works with
+/// screen readers and over dumb terminals.
+async fn run_plain_session<R: BufRead, W: Write>(
+    db: &DB,
+    batch: Batch,
+    time_budget: Option<TimeBudget>,
+    options: &DrillOptions,
+    mut input: R,
+    output: &mut W,
+) -> Result<SessionEnd> {
+    let Batch {
+        cards,
+        preprocessor: drill_preprocessor,
+        performances,
+    } = batch;
+    let started_at = chrono::Utc::now();
+    let mut state = DrillState::new(db, cards);
+    state.no_redo = options.no_redo;
+    state.max_presentations = options.max_presentations();
+    state.time_budget = time_budget;
+    state.performances = performances;
+
+    // Nothing is drawn while the LLM works, so wait for every card up front.
+    if drill_preprocessor.llm_required() {
+        writeln!(output, "Preparing cards with the LLM...")?;
+        let (updates_tx, mut updates_rx) = mpsc::unbounded_channel();
+        preprocess_cards_in_order(drill_preprocessor, state.cards.clone(), updates_tx).await?;
+        while let Ok(update) = updates_rx.try_recv() {
+            state.apply_ai_update(update);
+        }
+    }
+
+    let session_end = loop {
+        let Some(card) = state.current_card() else {
+            break SessionEnd::Completed;
+        };
+        let label = review_label(state.performances.get(&card.card_hash), chrono::Utc::now());
+        writeln!(
+            output,
+            "\nCard {}/{} · {label}\n",
+            state.current_idx + 1,
+            state.cards.len()
+        )?;
+        writeln!(output, "{}", plain_question_text(&card))?;
+        write!(output, "\nPress Enter to show the answer, or q to quit: ")?;
+        output.flush()?;
+        match read_plain_line(&mut input)? {
+            Some(line) if line.eq_ignore_ascii_case("q") => break SessionEnd::Quit,
+            Some(_) => {}
+            None => break SessionEnd::Quit,
+        }
+
+        writeln!(output, "\n{}", format_card_text(&card, true))?;
+        let status = loop {
+            write!(output, "\nGrade: 1 fail, 2 pass, h hard, e easy, q quit: ")?;
+            output.flush()?;
+            let Some(line) = read_plain_line(&mut input)? else {
+                break None;
+            };
+            match line.to_ascii_lowercase().as_str() {
+                "1" | "f" => break Some(ReviewStatus::Fail),
+                "2" | "p" => break Some(ReviewStatus::Pass),
+                "h" => break Some(ReviewStatus::Hard),
+                "e" => break Some(ReviewStatus::Easy),
+                "q" => break None,
+                _ => writeln!(output, "Type 1, 2, h, e or q.")?,
+            }
+        };
+        let Some(status) = status else {
+            break SessionEnd::Quit;
+        };
+        state.reveal_and_grade(status).await?;
+        writeln!(output, "Marked {}.", status.label())?;
+    };
+
+    record_session(db, &state, started_at).await?;
+    Ok(session_end)
+}
+
+/// The card as asked, without the answer placeholder meant for the full-screen UI.
+fn plain_question_text(card: &Card) -> String {
+    match &card.content {
+        CardContent::Basic { question, .. } => format!("Q:\n{question}"),
+        CardContent::Cloze { .. } => format_card_text(card, false),
+    }
+}
+
+/// The next line of `input`, trimmed, or `None` once it is exhausted.
+fn read_plain_line(input: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+/// The card's lines exactly as they appear in its file, or why they can't be shown.
+fn read_card_source(card: &Card) -> String {
+    match std::fs::read_to_string(&card.file_path) {
+        Ok(contents) => source_lines(&contents, card.file_card_range),
+        Err(err) => format!("Could not read {}: {err}", card.file_path.display()),
+    }
+}
+
+/// Lines `start..end` (0-based, end exclusive) of `contents`, line endings kept.
+fn source_lines(contents: &str, (start, end): (usize, usize)) -> String {
+    contents
+        .split_inclusive('\n')
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .collect()
+}
+
+fn session_summary(session: &SessionRecord) -> String {
+    let minutes = (session.ended_at - session.started_at).num_minutes();
+    format!(
+        "Reviewed {} in {}: {} passed, {} failed ({:.0}% pass)",
+        pluralize("card", session.cards_reviewed as usize),
+        if minutes < 1 {
+            "under a minute".to_string()
+        } else {
+            pluralize("minute", minutes as usize)
+        },
+        session.passed,
+        session.failed,
+        session.pass_rate().unwrap_or_default() * 100.0
+    )
+}
+
 fn teardown_terminal(terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>) -> Result<()> {
     disable_raw_mode().context("failed to disable raw mode")?;
     execute!(
@@ -336,6 +1346,31 @@ fn teardown_terminal(terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>)
 
 fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
     let mut lines = Vec::new();
+    if state.source_popup.is_some() {
+        lines.push(Line::from(vec![
+            Theme::span("Viewing the card's Markdown"),
+            Theme::bullet(),
+            Theme::key_chip("R"),
+            Theme::span(" / "),
+            Theme::key_chip("Esc"),
+            Theme::span(" close"),
+        ]));
+        return lines;
+    }
+    if let Some(query) = &state.search_input {
+        lines.push(Line::from(vec![
+            Theme::label_span("Search: "),
+            Theme::span(format!("{query}_")),
+        ]));
+        lines.push(Line::from(vec![
+            Theme::key_chip("Enter"),
+            Theme::span(" apply (empty clears)"),
+            Theme::bullet(),
+            Theme::key_chip("Esc"),
+            Theme::span(" cancel"),
+        ]));
+        return lines;
+    }
     if state.current_ai_pending() {
         lines.push(Line::from(vec![
             Theme::span("Enhancing card with AI"),
@@ -355,6 +1390,17 @@ fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
             Theme::key_chip("F"),
             Span::styled(" Fail", Theme::danger()),
             Theme::bullet(),
+            Theme::key_chip("H"),
+            Theme::span(" Hard"),
+            Theme::bullet(),
+            Theme::key_chip("E"),
+            Span::styled(" Easy", Theme::success()),
+            Theme::bullet(),
+            Theme::key_chip("1"),
+            Theme::span("/"),
+            Theme::key_chip("2"),
+            Theme::span(" fail/pass"),
+            Theme::bullet(),
             Theme::key_chip("Esc"),
             Theme::span(" / "),
             Theme::key_chip("Ctrl+C"),
@@ -367,11 +1413,35 @@ fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
             Theme::key_chip("Enter"),
             Theme::span(" show answer"),
             Theme::bullet(),
+        ];
+        if state.grade_before_reveal {
+            line.extend([
+                Theme::key_chip("1"),
+                Theme::span("/"),
+                Theme::key_chip("2"),
+                Theme::span(" fail/pass now"),
+                Theme::bullet(),
+            ]);
+        }
+        if state.undo.is_some() {
+            line.extend([
+                Theme::key_chip("U"),
+                Theme::span(" undo grade"),
+                Theme::bullet(),
+            ]);
+        }
+        line.extend([
+            Theme::key_chip("/"),
+            Theme::span(" filter"),
+            Theme::bullet(),
+            Theme::key_chip("R"),
+            Theme::span(" source"),
+            Theme::bullet(),
             Theme::key_chip("Esc"),
             Theme::span(" / "),
             Theme::key_chip("Ctrl+C"),
             Theme::span(" exit"),
-        ];
+        ]);
         if !state.current_medias.is_empty() {
             let num_media = state.current_medias.len();
             line.push(Theme::bullet());
@@ -385,12 +1455,22 @@ fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
         lines.push(Line::from(line));
     }
 
+    if let Some((notice, at)) = &state.filter_notice
+        && at.elapsed().as_secs_f64() < FLASH_SECS
+    {
+        lines.push(Line::from(vec![Span::styled(
+            notice.clone(),
+            Theme::danger(),
+        )]));
+    }
+
     if let Some(action) = &state.last_action
         && action.last_reviewed_at.elapsed().as_secs_f64() < FLASH_SECS
     {
         let style = match action.action {
-            ReviewStatus::Pass => Theme::success(),
             ReviewStatus::Fail => Theme::danger(),
+            ReviewStatus::Hard => Theme::emphasis(),
+            ReviewStatus::Pass | ReviewStatus::Easy => Theme::success(),
         };
         lines.push(Line::from(vec![
             Theme::span("Last:"),
@@ -401,18 +1481,89 @@ fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
     lines
 }
 
+/// `NEW` for unseen cards, otherwise `REVIEW`, noting how overdue the card was
+/// or, for a card reviewed ahead of schedule, how soon it was due.
+fn review_label(performance: Option<&Performance>, now: chrono::DateTime<chrono::Utc>) -> String {
+    match performance {
+        Some(Performance::Reviewed(reviewed)) => {
+            let overdue_days = (now - reviewed.due_date).num_days();
+            if overdue_days > 0 {
+                format!("REVIEW ({overdue_days}d overdue)")
+            } else if overdue_days < 0 {
+                format!("REVIEW (early, due in {}d)", -overdue_days)
+            } else {
+                "REVIEW".to_string()
+            }
+        }
+        _ => "NEW".to_string(),
+    }
+}
+
+/// Colour for a card's FSRS difficulty (1 to 10): green below 4, yellow
+/// below 7, red above. New cards have no difficulty yet and get grey.
+fn difficulty_color(performance: Option<&Performance>) -> PaletteColor {
+    match performance {
+        Some(Performance::Reviewed(reviewed)) if reviewed.difficulty < 4.0 => Palette::SUCCESS,
+        Some(Performance::Reviewed(reviewed)) if reviewed.difficulty < 7.0 => Palette::WARNING,
+        Some(Performance::Reviewed(_)) => Palette::DANGER,
+        _ => Palette::BORDER,
+    }
+}
+
+fn difficulty_dot(performance: Option<&Performance>) -> Span<'static> {
+    Span::styled(
+        " ●",
+        Style::default().fg(difficulty_color(performance).tui()),
+    )
+}
+
 fn format_card_text(card: &Card, show_answer: bool) -> String {
     match &card.content {
         CardContent::Basic { question, answer } => {
             let mut text = format!("Q:\n{}\n\nA:\n", question);
             if show_answer {
                 text.push_str(answer);
+                return text;
+            }
+            let mut hints = Vec::new();
+            if !ANSWER_PLACEHOLDER.is_empty() {
+                hints.push(ANSWER_PLACEHOLDER.clone());
+            }
+            if *ANSWER_LENGTH_HINT {
+                hints.push(answer_length_hint(answer));
+            }
+            if !hints.is_empty() {
+                // Rendered as a block quote, which the markdown renderer dims.
+                text.push_str(&format!("> {}", hints.join(" · ")));
             }
             text
         }
+        CardContent::Cloze { text, .. } if card.reversed => {
+            if show_answer {
+                return format!("C:\n{}", text);
+            }
+            let terms: Vec<&str> = find_cloze_ranges(text)
+                .into_iter()
+                .filter_map(|(start, end)| text.get(start..end))
+                .collect();
+            format!(
+                "C (reverse):\n{}\n\n> {}",
+                terms.join("\n"),
+                REVERSE_CLOZE_PROMPT
+            )
+        }
+        CardContent::Cloze {
+            text,
+            cloze_range: Some(range),
+        } if card.directives.cloze_mode == ClozeMode::Occlude => {
+            format!("C:\n{}", occlude_cloze_text(text, range, show_answer))
+        }
         CardContent::Cloze { text, cloze_range } => {
-            let body = match (cloze_range, show_answer) {
+            // `recall-term` swaps which side masks the deletion.
+            let recall_term = card.directives.cloze_mode == ClozeMode::RecallTerm;
+            let body = match (cloze_range, show_answer != recall_term) {
                 (Some(range), false) => mask_cloze_text(text, range),
+                (Some(range), true) if recall_term => unbracket_cloze_text(text, range),
                 _ => text.clone(),
             };
             format!("C:\n{}", body)
@@ -420,6 +1571,25 @@ fn format_card_text(card: &Card, show_answer: bool) -> String {
     }
 }
 
+/// How much there is to recall, e.g. `answer: ~12 words` or
+/// `answer: ~40 words, 3 lines`, so a one-word answer isn't mistaken for an essay.
+fn answer_length_hint(answer: &str) -> String {
+    let words = answer.split_whitespace().count();
+    let lines = answer
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count();
+    if lines > 1 {
+        format!(
+            "answer: ~{}, {}",
+            pluralize("word", words),
+            pluralize("line", lines)
+        )
+    } else {
+        format!("answer: ~{}", pluralize("word", words))
+    }
+}
+
 async fn preprocess_cards_in_order(
     drill_preprocessor: DrillPreprocessor,
     cards: Vec<Card>,
@@ -463,6 +1633,55 @@ mod tests {
         Card::new(PathBuf::from("test.md"), (0, 1), content, "hash".into())
     }
 
+    /// A basic card whose hash is its question, for tests that key by hash.
+    fn hashed_by_question(question: &str) -> Card {
+        let mut card = basic_card(question, "answer");
+        card.card_hash = question.to_string();
+        card
+    }
+
+    /// The question of a basic card or the text of a cloze card.
+    fn prompt(card: &Card) -> String {
+        match &card.content {
+            CardContent::Basic { question, .. } => question.clone(),
+            CardContent::Cloze { text, .. } => text.clone(),
+        }
+    }
+
+    /// Basic cards `Q: name?` / `A: name`, one after another in `test.md`,
+    /// each added to `db`.
+    async fn add_named_cards(db: &DB, names: &[&str]) -> Vec<Card> {
+        let path = PathBuf::from("test.md");
+        let mut cards = Vec::new();
+        for (idx, name) in names.iter().enumerate() {
+            let text = format!("Q: {name}?\nA: {name}");
+            let card = crate::parser::content_to_card(&path, &text, idx * 3, idx * 3 + 2).unwrap();
+            db.add_card(&card).await.unwrap();
+            cards.push(card);
+        }
+        cards
+    }
+
+    /// Passes `card` three times, two days apart, so it is past its learning
+    /// steps and a fail counts as a lapse.
+    async fn make_mature(db: &DB, card: &Card) {
+        let mut reviewed_at = chrono::Utc::now() - chrono::Duration::days(30);
+        for _ in 0..3 {
+            db.update_card_performance(card, ReviewStatus::Pass, Some(reviewed_at))
+                .await
+                .unwrap();
+            reviewed_at += chrono::Duration::days(2);
+        }
+    }
+
+    /// `cards` keyed by hash, as `register_all_cards` returns them.
+    fn by_hash(cards: &[Card]) -> HashMap<String, Card> {
+        cards
+            .iter()
+            .map(|card| (card.card_hash.clone(), card.clone()))
+            .collect()
+    }
+
     fn cloze_card(text: &str) -> Card {
         let start = text.find('[').unwrap();
         let end = text[start..].find(']').unwrap() + start + 1;
@@ -477,15 +1696,34 @@ mod tests {
         )
     }
 
+    #[test]
+    fn answer_length_hint_counts_the_stored_answer() {
+        let card = basic_card("Capital of France?", "Paris");
+        let CardContent::Basic { answer, .. } = &card.content else {
+            panic!("expected a basic card");
+        };
+        assert_eq!(answer_length_hint(answer), "answer: ~1 word");
+
+        let card = basic_card(
+            "Why is the sky blue?",
+            "Rayleigh scattering favours short wavelengths,\nso blue light is scattered most.",
+        );
+        let CardContent::Basic { answer, .. } = &card.content else {
+            panic!("expected a basic card");
+        };
+        assert_eq!(answer_length_hint(answer), "answer: ~11 words, 2 lines");
+    }
+
     #[test]
     fn basic_card_hides_answer_until_revealed() {
         let card = basic_card("What?", "Answer");
 
         let hidden = format_card_text(&card, false);
         assert!(!hidden.contains("Answer"));
+        assert!(hidden.ends_with(&format!("A:\n> {DEFAULT_ANSWER_PLACEHOLDER}")));
 
         let shown = format_card_text(&card, true);
-        assert!(shown.contains("Answer"));
+        assert_eq!(shown, "Q:\nWhat?\n\nA:\nAnswer");
     }
 
     #[test]
@@ -501,6 +1739,543 @@ mod tests {
         assert!(revealed.contains("[東京]"));
     }
 
+    #[test]
+    fn reverse_cloze_shows_the_term_and_hides_the_context() {
+        let card = cloze_card("The capital of Japan is [東京].")
+            .reversed_cloze()
+            .unwrap();
+        assert_ne!(card.card_hash, "hash");
+        assert_eq!(card.drill_items().len(), 1);
+
+        let hidden = format_card_text(&card, false);
+        assert!(hidden.contains("[東京]"));
+        assert!(!hidden.contains("capital of Japan"));
+        assert!(hidden.contains(REVERSE_CLOZE_PROMPT));
+
+        let shown = format_card_text(&card, true);
+        assert!(shown.contains("The capital of Japan is [東京]."));
+        assert!(basic_card("Q", "A").reversed_cloze().is_none());
+    }
+
+    #[test]
+    fn recall_term_mode_flips_which_side_masks_the_cloze() {
+        let mut card = cloze_card("The capital of Japan is [東京].");
+        assert_eq!(
+            format_card_text(&card, false),
+            "C:\nThe capital of Japan is [___]."
+        );
+        assert_eq!(
+            format_card_text(&card, true),
+            "C:\nThe capital of Japan is [東京]."
+        );
+
+        card.directives.cloze_mode = ClozeMode::RecallTerm;
+        assert_eq!(
+            format_card_text(&card, false),
+            "C:\nThe capital of Japan is 東京."
+        );
+        assert_eq!(
+            format_card_text(&card, true),
+            "C:\nThe capital of Japan is [___]."
+        );
+    }
+
+    #[test]
+    fn occlude_mode_keeps_other_deletions_masked() {
+        let mut card = cloze_card("[A] and [B]");
+        card.directives.cloze_mode = ClozeMode::Occlude;
+        let items = card.drill_items();
+        assert_eq!(items.len(), 2);
+
+        let shown: Vec<(String, String)> = items
+            .iter()
+            .map(|item| (format_card_text(item, false), format_card_text(item, true)))
+            .collect();
+        assert_eq!(
+            shown,
+            vec![
+                ("C:\n[___] and [___]".into(), "C:\n[A] and [___]".into()),
+                ("C:\n[___] and [___]".into(), "C:\n[___] and [B]".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn multi_cloze_card_counts_each_deletion() {
+        let db = DB::new_in_memory().await.unwrap();
+        let two_cloze = Card::new(
+            PathBuf::from("test.md"),
+            (0, 1),
+            CardContent::Cloze {
+                text: "[Tokyo] is the capital of [Japan]".into(),
+                cloze_range: Some(ClozeRange::new(0, 7).unwrap()),
+            },
+            "two".into(),
+        );
+        let basic = basic_card("Q", "A");
+        db.add_cards_batch(&[two_cloze.clone(), basic.clone()])
+            .await
+            .unwrap();
+        let hash_cards = HashMap::from([
+            (two_cloze.card_hash.clone(), two_cloze.clone()),
+            (basic.card_hash.clone(), basic),
+        ]);
+        let expanded = expand_cloze_deletions(&db, hash_cards).await.unwrap();
+        assert_eq!(expanded.len(), 3);
+        let mut items = two_cloze.drill_items();
+        assert_eq!(items[0].card_hash, "two");
+        assert_ne!(items[1].card_hash, "two");
+        assert!(expanded.contains_key(&items[1].card_hash));
+
+        // Each deletion keeps its own schedule: grading one leaves the other new.
+        db.update_card_performance(&items[0], ReviewStatus::Pass, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            db.get_card_performance(&items[0]).await.unwrap(),
+            Performance::Reviewed(_)
+        ));
+        assert!(matches!(
+            db.get_card_performance(&items[1]).await.unwrap(),
+            Performance::New
+        ));
+
+        items.push(basic_card("Q", "A"));
+        let state = DrillState::new(&db, items);
+        assert_eq!(state.cards.len(), 3);
+
+        let masked: Vec<String> = state.cards[..2]
+            .iter()
+            .map(|card| format_card_text(card, false))
+            .collect();
+        assert!(masked[0].contains("[_____] is the capital of [Japan]"));
+        assert!(masked[1].contains("[Tokyo] is the capital of [_____]"));
+    }
+
+    #[test]
+    fn card_matches_is_case_insensitive_over_question_answer_and_cloze() {
+        let cards = [
+            basic_card("What is the capital of France?", "Paris"),
+            basic_card("Largest planet?", "Jupiter"),
+            cloze_card("The [mitochondria] is the powerhouse"),
+        ];
+        let matching = |query: &str| {
+            cards
+                .iter()
+                .filter(|card| card.matches_query(query))
+                .count()
+        };
+
+        assert_eq!(matching("paris"), 1);
+        assert_eq!(matching("CAPITAL"), 1);
+        assert_eq!(matching("Mitochondria"), 1);
+        assert_eq!(matching("the"), 2);
+        assert_eq!(matching("neptune"), 0);
+    }
+
+    #[test]
+    fn filter_sets_aside_non_matches_and_clearing_restores_them() {
+        let db = in_memory_db();
+        let mut state = DrillState::new(
+            &db,
+            vec![
+                basic_card("Capital of France?", "Paris"),
+                basic_card("Largest planet?", "Jupiter"),
+                basic_card("Capital of Spain?", "Madrid"),
+            ],
+        );
+
+        state.apply_filter("capital");
+        assert_eq!(state.cards.len(), 2);
+        assert_eq!(state.set_aside.len(), 1);
+        assert_eq!(state.filter.as_deref(), Some("capital"));
+
+        state.apply_filter("");
+        assert_eq!(state.cards.len(), 3);
+        assert!(state.set_aside.is_empty());
+        assert!(state.filter.is_none());
+    }
+
+    #[tokio::test]
+    async fn failing_new_and_mature_cards_take_different_paths() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["mature", "new", "c", "d", "e"]).await;
+        make_mature(&db, &cards[0]).await;
+
+        let mut state = DrillState::new(&db, cards.clone());
+
+        // Mature card: goes to the end of the session and counts as a lapse.
+        state.handle_review(ReviewStatus::Fail).await.unwrap();
+        assert_eq!(state.cards.len(), 5);
+        assert_eq!(state.redo_cards.len(), 1);
+        assert_eq!(state.redo_cards[0].card_hash, cards[0].card_hash);
+        assert_eq!(db.card_lapses(&cards[0]).await.unwrap(), 1);
+
+        // New card: shown again right away, no lapse.
+        state.handle_review(ReviewStatus::Fail).await.unwrap();
+        assert_eq!(state.cards.len(), 6);
+        assert_eq!(state.current_card().unwrap().card_hash, cards[1].card_hash);
+        assert_eq!(state.redo_cards.len(), 1);
+        assert_eq!(db.card_lapses(&cards[1]).await.unwrap(), 0);
+        assert_eq!((state.passed, state.failed), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn undo_after_fail_drops_the_requeue_and_restores_the_schedule() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card = add_named_cards(&db, &["mature"]).await.remove(0);
+        make_mature(&db, &card).await;
+        let before = db.get_card_performance(&card).await.unwrap();
+
+        let mut state = DrillState::new(&db, vec![card.clone()]);
+        assert!(!state.undo_last_review().await.unwrap());
+        state.handle_review(ReviewStatus::Fail).await.unwrap();
+        assert_eq!(state.redo_cards.len(), 1);
+        assert_eq!(db.card_lapses(&card).await.unwrap(), 1);
+
+        assert!(state.undo_last_review().await.unwrap());
+        assert!(state.redo_cards.is_empty());
+        assert_eq!(state.current_idx, 0);
+        assert!(state.show_answer);
+        assert_eq!((state.passed, state.failed), (0, 0));
+        assert_eq!(db.get_card_performance(&card).await.unwrap(), before);
+        assert_eq!(db.card_lapses(&card).await.unwrap(), 0);
+        assert!(!state.undo_last_review().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn card_limit_counts_cards_or_presentations() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["first", "second"]).await;
+        for card in &cards {
+            make_mature(&db, card).await;
+        }
+
+        let mut grades = Vec::new();
+        for limit_counts in [LimitCounts::Cards, LimitCounts::Presentations] {
+            let options = DrillOptions {
+                card_limit: Some(2),
+                limit_counts,
+                ..Default::default()
+            };
+            let mut state = DrillState::new(&db, cards.clone());
+            state.max_presentations = options.max_presentations();
+            // Fail the first card so it comes back, pass everything after.
+            state.handle_review(ReviewStatus::Fail).await.unwrap();
+            while !state.is_complete() {
+                state.current_card().unwrap();
+                state.handle_review(ReviewStatus::Pass).await.unwrap();
+            }
+            grades.push(state.passed + state.failed);
+        }
+        // Counting cards, the failed card's second showing is extra.
+        assert_eq!(grades, vec![3, 2]);
+    }
+
+    #[tokio::test]
+    async fn no_redo_finishes_after_one_pass() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["new", "mature", "passed"]).await;
+        make_mature(&db, &cards[1]).await;
+
+        let mut state = DrillState::new(&db, cards.clone());
+        state.no_redo = true;
+        state.handle_review(ReviewStatus::Fail).await.unwrap();
+        state.handle_review(ReviewStatus::Fail).await.unwrap();
+        // A new card passed once is normally seen again within minutes.
+        state.handle_review(ReviewStatus::Pass).await.unwrap();
+
+        assert_eq!(state.cards.len(), 3);
+        assert!(state.redo_cards.is_empty());
+        assert!(state.is_complete());
+        assert!(state.current_card().is_none());
+        // The failures were still recorded.
+        assert_eq!(db.card_lapses(&cards[1]).await.unwrap(), 1);
+        assert_eq!((state.passed, state.failed), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn plain_session_reads_grades_from_input() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["capital", "river"]).await;
+        let options = DrillOptions {
+            no_redo: true,
+            ..Default::default()
+        };
+
+        // Reveal, mistype a grade, fail; reveal, pass.
+        let input = io::Cursor::new("\nx\n1\n\n2\n");
+        let mut output = Vec::new();
+        let preprocessor = DrillPreprocessor::new(&cards, false).unwrap();
+        let batch = Batch {
+            cards: cards.clone(),
+            preprocessor,
+            performances: HashMap::new(),
+        };
+        let end = run_plain_session(&db, batch, None, &options, input, &mut output)
+            .await
+            .unwrap();
+        assert_eq!(end, SessionEnd::Completed);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Card 1/2 · NEW\n\nQ:\ncapital?\n"));
+        assert!(output.contains("Type 1, 2, h, e or q."));
+        assert!(output.contains("A:\ncapital\n"));
+        assert!(output.contains("Marked Fail."));
+        assert!(output.contains("Card 2/2 · NEW\n\nQ:\nriver?\n"));
+        assert!(output.contains("Marked Pass."));
+        // The placeholder is a hint for the full-screen UI's space bar.
+        assert!(!output.contains(ANSWER_PLACEHOLDER.as_str()));
+        for card in &cards {
+            assert!(matches!(
+                db.get_card_performance(card).await.unwrap(),
+                Performance::Reviewed(_)
+            ));
+        }
+
+        // Quitting before the answer leaves the card unreviewed.
+        let fresh = add_named_cards(&db, &["lake"]).await.remove(0);
+        let mut output = Vec::new();
+        let preprocessor = DrillPreprocessor::new(std::slice::from_ref(&fresh), false).unwrap();
+        let batch = Batch {
+            cards: vec![fresh.clone()],
+            preprocessor,
+            performances: HashMap::new(),
+        };
+        let end = run_plain_session(
+            &db,
+            batch,
+            None,
+            &options,
+            io::Cursor::new("q\n"),
+            &mut output,
+        )
+        .await
+        .unwrap();
+        assert_eq!(end, SessionEnd::Quit);
+        assert!(matches!(
+            db.get_card_performance(&fresh).await.unwrap(),
+            Performance::New
+        ));
+    }
+
+    #[tokio::test]
+    async fn hard_on_a_mature_card_is_a_pass_not_a_requeue() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card = add_named_cards(&db, &["hard"]).await.remove(0);
+        make_mature(&db, &card).await;
+
+        let mut state = DrillState::new(&db, vec![card.clone()]);
+        state.handle_review(ReviewStatus::Hard).await.unwrap();
+        assert_eq!(state.cards.len(), 1);
+        assert!(state.redo_cards.is_empty());
+        assert_eq!(db.card_lapses(&card).await.unwrap(), 0);
+        assert_eq!((state.passed, state.failed), (1, 0));
+        assert!(state.last_action.unwrap().show_again_duration >= 1.0);
+    }
+
+    #[tokio::test]
+    async fn hard_on_a_new_or_learning_card_is_not_requeued() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["new", "learning"]).await;
+        db.update_card_performance(&cards[1], ReviewStatus::Pass, None)
+            .await
+            .unwrap();
+
+        let mut state = DrillState::new(&db, cards.clone());
+        state.handle_review(ReviewStatus::Hard).await.unwrap();
+        state.handle_review(ReviewStatus::Hard).await.unwrap();
+        assert_eq!(state.cards.len(), 2);
+        assert!(state.redo_cards.is_empty());
+        assert_eq!((state.passed, state.failed), (2, 0));
+        assert!(state.is_complete());
+    }
+
+    #[test]
+    fn session_summary_reports_counts_and_pass_rate() {
+        let started_at = chrono::Utc::now();
+        let session = SessionRecord {
+            started_at,
+            ended_at: started_at + chrono::Duration::minutes(8),
+            cards_reviewed: 12,
+            passed: 10,
+            failed: 2,
+        };
+        assert_eq!(
+            session_summary(&session),
+            "Reviewed 12 cards in 8 minutes: 10 passed, 2 failed (83% pass)"
+        );
+    }
+
+    #[tokio::test]
+    async fn grade_before_reveal_reveals_and_records_in_one_step() {
+        use crate::fsrs::Performance;
+
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["a", "b"]).await;
+
+        let mut state = DrillState::new(&db, cards.clone());
+        assert!(!state.can_grade());
+        state.grade_before_reveal = true;
+        assert!(state.can_grade());
+
+        state.reveal_and_grade(ReviewStatus::Pass).await.unwrap();
+        assert_eq!(state.current_idx, 1);
+        assert!(!state.show_answer);
+        assert_eq!(
+            state.last_action.as_ref().map(|a| a.action),
+            Some(ReviewStatus::Pass)
+        );
+        let Performance::Reviewed(reviewed) = db.get_card_performance(&cards[0]).await.unwrap()
+        else {
+            panic!("card should be reviewed");
+        };
+        assert_eq!(reviewed.review_count, 1);
+    }
+
+    #[test]
+    fn weakest_order_sorts_by_retrievability_with_new_cards_positioned() {
+        let now = chrono::Utc::now();
+        let (strong, weak, new) = (
+            hashed_by_question("strong"),
+            hashed_by_question("weak"),
+            hashed_by_question("new"),
+        );
+        let reviewed_ago = |days: i64| {
+            let mut perf = crate::fsrs::update_performance(
+                Performance::New,
+                ReviewStatus::Pass,
+                now - chrono::Duration::days(days),
+                TARGET_RECALL,
+            );
+            perf.stability = 2.0;
+            Performance::Reviewed(perf)
+        };
+        let performances = HashMap::from([
+            (strong.card_hash.clone(), reviewed_ago(1)),
+            (weak.card_hash.clone(), reviewed_ago(20)),
+            (new.card_hash.clone(), Performance::New),
+        ]);
+        let questions = |cards: &[Card]| -> Vec<String> { cards.iter().map(prompt).collect() };
+
+        let mut cards = vec![new.clone(), strong.clone(), weak.clone()];
+        order_weakest_first(&mut cards, &performances, now, false);
+        assert_eq!(questions(&cards), ["weak", "strong", "new"]);
+
+        order_weakest_first(&mut cards, &performances, now, true);
+        assert_eq!(questions(&cards), ["new", "weak", "strong"]);
+    }
+
+    #[test]
+    fn overdue_order_puts_the_most_overdue_card_first() {
+        let now = chrono::Utc::now();
+        let due_days_ago = |days: i64| {
+            let mut perf = crate::fsrs::update_performance(
+                Performance::New,
+                ReviewStatus::Pass,
+                now - chrono::Duration::days(days + 3),
+                TARGET_RECALL,
+            );
+            perf.due_date = now - chrono::Duration::days(days);
+            Performance::Reviewed(perf)
+        };
+        let (slightly, badly, new) = (
+            hashed_by_question("1 day"),
+            hashed_by_question("10 days"),
+            hashed_by_question("new"),
+        );
+        let performances = HashMap::from([
+            (slightly.card_hash.clone(), due_days_ago(1)),
+            (badly.card_hash.clone(), due_days_ago(10)),
+            (new.card_hash.clone(), Performance::New),
+        ]);
+        let hashes = |cards: &[Card]| -> Vec<String> {
+            cards.iter().map(|card| card.card_hash.clone()).collect()
+        };
+
+        let mut cards = vec![new.clone(), slightly.clone(), badly.clone()];
+        order_most_overdue_first(&mut cards, &performances, now, false);
+        assert_eq!(hashes(&cards), ["10 days", "1 day", "new"]);
+
+        order_most_overdue_first(&mut cards, &performances, now, true);
+        assert_eq!(hashes(&cards), ["new", "10 days", "1 day"]);
+    }
+
+    #[test]
+    fn next_due_is_described_relative_to_the_calendar_day() {
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        let utc = DayZone::Fixed(FixedOffset::east_opt(0).unwrap());
+        // A Monday morning.
+        let now = Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap();
+        let at = |days: i64, hours: i64, minutes: i64| {
+            now + chrono::Duration::days(days)
+                + chrono::Duration::hours(hours)
+                + chrono::Duration::minutes(minutes)
+        };
+
+        assert_eq!(format_next_due(at(0, 0, 20), now, utc), "in 20 minutes");
+        assert_eq!(format_next_due(at(0, 5, 30), now, utc), "in 5 hours");
+        assert_eq!(format_next_due(at(0, 16, 0), now, utc), "tomorrow (Tue)");
+        assert_eq!(format_next_due(at(3, 0, 0), now, utc), "in 3 days (Thu)");
+        // Calendar days count, not 24-hour periods.
+        assert_eq!(format_next_due(at(1, 16, 0), now, utc), "in 2 days (Wed)");
+    }
+
+    #[test]
+    fn difficulty_maps_to_a_palette_bucket() {
+        let reviewed = crate::fsrs::update_performance(
+            Performance::New,
+            ReviewStatus::Pass,
+            chrono::Utc::now(),
+            TARGET_RECALL,
+        );
+        let color = |difficulty| {
+            let performance = Performance::Reviewed(crate::fsrs::ReviewedPerformance {
+                difficulty,
+                ..reviewed
+            });
+            difficulty_color(Some(&performance))
+        };
+        assert_eq!(color(1.0), Palette::SUCCESS);
+        assert_eq!(color(5.5), Palette::WARNING);
+        assert_eq!(color(9.2), Palette::DANGER);
+        assert_eq!(difficulty_color(Some(&Performance::New)), Palette::BORDER);
+        assert_eq!(difficulty_color(None), Palette::BORDER);
+    }
+
+    #[test]
+    fn review_label_distinguishes_new_and_overdue_cards() {
+        let now = chrono::Utc::now();
+        let mut reviewed = crate::fsrs::update_performance(
+            Performance::New,
+            ReviewStatus::Pass,
+            now,
+            TARGET_RECALL,
+        );
+
+        assert_eq!(review_label(None, now), "NEW");
+        assert_eq!(review_label(Some(&Performance::New), now), "NEW");
+
+        reviewed.due_date = now - chrono::Duration::days(3) - chrono::Duration::hours(2);
+        assert_eq!(
+            review_label(Some(&Performance::Reviewed(reviewed)), now),
+            "REVIEW (3d overdue)"
+        );
+
+        reviewed.due_date = now - chrono::Duration::hours(5);
+        assert_eq!(
+            review_label(Some(&Performance::Reviewed(reviewed)), now),
+            "REVIEW"
+        );
+
+        reviewed.due_date = now + chrono::Duration::days(2) + chrono::Duration::hours(1);
+        assert_eq!(
+            review_label(Some(&Performance::Reviewed(reviewed)), now),
+            "REVIEW (early, due in 2d)"
+        );
+    }
+
     #[test]
     fn last_action_prints_human_friendly_intervals() {
         fn formatted(minutes: f64, status: ReviewStatus) -> String {
@@ -579,6 +2354,216 @@ mod tests {
             .collect::<String>()
     }
 
+    #[tokio::test]
+    async fn next_batch_skips_cards_already_drilled_this_session() {
+        let db = DB::new_in_memory().await.unwrap();
+        let hash_cards = by_hash(&add_named_cards(&db, &["a", "b", "c", "d", "e"]).await);
+        let options = DrillOptions {
+            card_limit: Some(2),
+            ..Default::default()
+        };
+        let mut reviewed = HashSet::new();
+
+        let first = next_batch(&db, &hash_cards, &reviewed, &options)
+            .await
+            .unwrap();
+        assert_eq!(first.len(), 2);
+        // Failed cards come due again right away, so only the exclusion keeps them out.
+        for card in &first {
+            db.update_card_performance(card, ReviewStatus::Fail, None)
+                .await
+                .unwrap();
+            reviewed.insert(card.card_hash.clone());
+        }
+
+        let second = next_batch(&db, &hash_cards, &reviewed, &options)
+            .await
+            .unwrap();
+        assert_eq!(second.len(), 2);
+        assert!(
+            second
+                .iter()
+                .all(|card| !reviewed.contains(&card.card_hash))
+        );
+        reviewed.extend(second.iter().map(|card| card.card_hash.clone()));
+
+        let last = next_batch(&db, &hash_cards, &reviewed, &options)
+            .await
+            .unwrap();
+        assert_eq!(last.len(), 1);
+        assert!(!reviewed.contains(&last[0].card_hash));
+    }
+
+    #[test]
+    fn source_lines_returns_the_card_range_verbatim() {
+        let contents =
+            "# Notes\n\nQ: What is **ATP**?\nA: Energy\nSource: textbook\n---\nQ: next?\nA: x";
+        assert_eq!(
+            source_lines(contents, (2, 5)),
+            "Q: What is **ATP**?\nA: Energy\nSource: textbook\n"
+        );
+        assert_eq!(source_lines(contents, (6, 8)), "Q: next?\nA: x");
+        assert_eq!(source_lines(contents, (20, 22)), "");
+    }
+
+    #[tokio::test]
+    async fn start_at_finds_a_card_past_the_card_limit() {
+        let db = DB::new_in_memory().await.unwrap();
+        let hash_cards = by_hash(&add_named_cards(&db, &["a", "b", "c", "d", "e"]).await);
+        let options = DrillOptions {
+            card_limit: Some(2),
+            start_at: Some("d?".to_string()),
+            ..Default::default()
+        };
+
+        let batch = next_batch(&db, &hash_cards, &HashSet::new(), &options)
+            .await
+            .unwrap();
+        let questions: Vec<String> = batch.iter().map(prompt).collect();
+        assert_eq!(questions, ["d?", "e?"]);
+    }
+
+    #[test]
+    fn start_at_rotates_the_queue_to_the_matching_card() {
+        use crate::parser::content_to_card;
+
+        let path = Path::new("deck.md");
+        let mut cards: Vec<Card> = ["Peru", "Chile", "Bolivia", "Chad"]
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let text = format!("Q: Capital of {name}?\nA: x");
+                content_to_card(path, &text, idx * 3, idx * 3 + 2).unwrap()
+            })
+            .collect();
+
+        assert!(rotate_to_match(&mut cards, "Bolivia"));
+        let order: Vec<String> = cards.iter().map(prompt).collect();
+        assert_eq!(
+            order,
+            [
+                "Capital of Bolivia?",
+                "Capital of Chad?",
+                "Capital of Peru?",
+                "Capital of Chile?"
+            ]
+        );
+
+        let chile = cards[3].card_hash.clone();
+        assert!(rotate_to_match(&mut cards, &chile));
+        assert_eq!(prompt(&cards[0]), "Capital of Chile?");
+
+        // The first match wins, and a miss leaves the queue alone.
+        assert!(rotate_to_match(&mut cards, "Ch"));
+        assert_eq!(prompt(&cards[0]), "Capital of Chile?");
+        let before: Vec<String> = cards.iter().map(prompt).collect();
+        assert!(!rotate_to_match(&mut cards, "Narnia"));
+        assert_eq!(cards.iter().map(prompt).collect::<Vec<_>>(), before);
+    }
+
+    #[test]
+    fn round_robin_interleaves_cards_from_each_deck() {
+        use crate::parser::content_to_card;
+
+        let card = |path: &str, name: &str| {
+            content_to_card(Path::new(path), &format!("Q: {name}?\nA: {name}"), 0, 2).unwrap()
+        };
+        let cards = vec![
+            card("bio/cells.md", "b1"),
+            card("bio/genes.md", "b2"),
+            card("bio/cells.md", "b3"),
+            card("bio/cells.md", "b4"),
+            card("chem/acids.md", "c1"),
+            card("chem/acids.md", "c2"),
+        ];
+
+        let order: Vec<String> = interleave_decks(cards).iter().map(prompt).collect();
+        assert_eq!(order, ["b1?", "c1?", "b2?", "c2?", "b3?", "b4?"]);
+    }
+
+    #[test]
+    fn time_budget_stops_once_elapsed_reaches_it() {
+        let budget = Duration::from_secs(15 * 60);
+        assert!(within_time_budget(Duration::ZERO, budget));
+        assert!(within_time_budget(
+            Duration::from_secs(14 * 60 + 59),
+            budget
+        ));
+        assert!(!within_time_budget(budget, budget));
+        assert!(!within_time_budget(Duration::from_secs(20 * 60), budget));
+    }
+
+    #[tokio::test]
+    async fn time_budget_ends_the_session_after_the_grade_that_crosses_it() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = add_named_cards(&db, &["a", "b", "c"]).await;
+
+        let mut state = DrillState::new(&db, cards.clone());
+        state.time_budget = Some(TimeBudget {
+            started: Instant::now(),
+            budget: Duration::ZERO,
+        });
+
+        // The budget is already spent, but the card on screen is still graded.
+        assert_eq!(state.current_card().unwrap().card_hash, cards[0].card_hash);
+        assert!(!state.is_complete());
+        state.handle_review(ReviewStatus::Pass).await.unwrap();
+        assert!(state.current_card().is_none());
+        assert!(state.is_complete());
+        assert_eq!((state.passed, state.failed), (1, 0));
+        assert!(matches!(
+            db.get_card_performance(&cards[0]).await.unwrap(),
+            Performance::Reviewed(_)
+        ));
+        assert_eq!(
+            db.get_card_performance(&cards[1]).await.unwrap(),
+            Performance::New
+        );
+    }
+
+    #[test]
+    fn deck_label_relativizes_against_the_drilled_root() {
+        let roots = vec![PathBuf::from("/home/me/notes"), PathBuf::from("extra.md")];
+        let label = |file: &str| deck_label(Path::new(file), &roots);
+
+        assert_eq!(
+            label("/home/me/notes/lang/french.md"),
+            Path::new("lang").join("french.md").display().to_string()
+        );
+        assert_eq!(label("/home/me/notes/geo.md"), "geo.md");
+        // A file passed directly, or outside every root, falls back to its folder.
+        assert_eq!(label("extra.md"), "extra.md");
+        assert_eq!(
+            label("/srv/shared/decks/bio.md"),
+            Path::new("decks").join("bio.md").display().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn launch_summary_reports_the_due_count() {
+        use crate::scheduler::SchedulerKind;
+
+        // SM-2 schedules a first pass a day out, so the learned card is not due.
+        let db = DB::new_in_memory()
+            .await
+            .unwrap()
+            .with_scheduler(SchedulerKind::Sm2);
+        let hash_cards = by_hash(&add_named_cards(&db, &["a", "b", "c"]).await);
+        let learned = hash_cards.values().next().unwrap();
+        db.update_card_performance(learned, ReviewStatus::Pass, None)
+            .await
+            .unwrap();
+
+        let due = next_batch(&db, &hash_cards, &HashSet::new(), &DrillOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            launch_summary(hash_cards.len(), due.len()),
+            "Loading 3 cards, 2 due..."
+        );
+        assert_eq!(launch_summary(1, 1), "Loading 1 card, 1 due...");
+    }
+
     fn in_memory_db() -> DB {
         tokio::runtime::Runtime::new()
             .unwrap()
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::card::Card;
+use crate::crud::DB;
+use crate::crud::positions::EditedCard;
+use crate::utils::{ask_yn, can_prompt, pluralize};
+
+/// Handles what `--track-edits` found: asks, card by card, whether an edited
+/// card should keep the review history of the text it replaced, then records
+/// where every card sits for the next tracked run. Without a terminal to ask
+/// on, nothing is moved or recorded, so the same edits are offered next time.
+pub async fn carry_over(
+    db: &DB,
+    hash_cards: &HashMap<String, Card>,
+    edits: &[EditedCard],
+) -> Result<()> {
+    if !edits.is_empty() && !can_prompt() {
+        eprintln!(
+            "warning: --track-edits: no terminal to ask about {}; run it interactively to carry their history over",
+            pluralize("edited card", edits.len())
+        );
+        return Ok(());
+    }
+    for edit in edits {
+        let (path, start, _) = edit.card.locator();
+        let prompt = format!(
+            "{}:{} was edited since it was last seen. Carry its review history over to the new text?",
+            path.display(),
+            start + 1
+        );
+        if ask_yn(prompt) {
+            db.migrate_history(&edit.old_hash, &edit.card.card_hash)
+                .await?;
+        }
+    }
+    db.record_positions(hash_cards).await
+}
@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::card::{Card, CardContent};
+use crate::parser::{CollectOptions, collect_cards};
+use crate::utils::pluralize;
+
+/// Characters of the matching line shown after each location.
+const SNIPPET_CHARS: usize = 70;
+
+/// Prints the cards under `paths` whose text fuzzily matches `query`, best
+/// match first, as `path:line  snippet`. The database is not touched.
+pub fn run(query: &str, paths: Vec<PathBuf>, limit: usize) -> Result<()> {
+    let (cards, _) = collect_cards(paths, CollectOptions::default())?;
+    let ranked = rank_cards(query, &cards);
+    if ranked.is_empty() {
+        println!("No cards match `{query}`.");
+        return Ok(());
+    }
+    for (card, found) in ranked.iter().take(limit) {
+        let (path, start, _) = card.locator();
+        println!("{}:{}  {}", path.display(), start + 1, found.snippet);
+    }
+    if ranked.len() > limit {
+        println!(
+            "… and {} more; raise --limit to see them",
+            pluralize("match", ranked.len() - limit)
+        );
+    }
+    Ok(())
+}
+
+/// A card field matching the query, with the line it was found on.
+#[derive(Debug)]
+struct Found {
+    score: FuzzyScore,
+    snippet: String,
+}
+
+/// How well a query matched some text. A contiguous match always beats a
+/// scattered one; within each kind, higher `points` is better.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct FuzzyScore {
+    contiguous: bool,
+    points: i64,
+}
+
+/// Cards with at least one field matching `query`, best first; ties keep
+/// file order.
+fn rank_cards<'a>(query: &str, cards: &'a [Card]) -> Vec<(&'a Card, Found)> {
+    let mut ranked: Vec<(&Card, Found)> = cards
+        .iter()
+        .filter_map(|card| {
+            let fields: Vec<&str> = match &card.content {
+                CardContent::Basic { question, answer } => vec![question, answer],
+                CardContent::Cloze { text, .. } => vec![text],
+            };
+            let found = fields
+                .into_iter()
+                .filter_map(|field| find_in(query, field))
+                .max_by_key(|found| found.score)?;
+            Some((card, found))
+        })
+        .collect();
+    ranked.sort_by(|(card_a, found_a), (card_b, found_b)| {
+        found_b
+            .score
+            .cmp(&found_a.score)
+            .then_with(|| card_a.locator().cmp(&card_b.locator()))
+    });
+    ranked
+}
+
+fn find_in(query: &str, text: &str) -> Option<Found> {
+    let (score, start) = fuzzy_score(query, text)?;
+    Some(Found {
+        score,
+        snippet: snippet_at(text, start),
+    })
+}
+
+/// Scores `query` against `text`, ignoring case, and returns the score with
+/// the char index where the match starts. Text containing the query as is
+/// scores highest, earlier and at a word start being better. Otherwise the
+/// query's characters (spaces aside) must appear in order: runs of adjacent
+/// characters and word starts earn points, and gaps between them cost some.
+fn fuzzy_score(query: &str, text: &str) -> Option<(FuzzyScore, usize)> {
+    let lower = |ch: char| ch.to_lowercase().next().unwrap_or(ch);
+    let query: Vec<char> = query.trim().chars().map(lower).collect();
+    let text: Vec<char> = text.chars().map(lower).collect();
+    if query.is_empty() {
+        return None;
+    }
+    let word_start = |idx: usize| idx == 0 || !text[idx - 1].is_alphanumeric();
+
+    if let Some(start) = text
+        .windows(query.len())
+        .position(|window| window == query.as_slice())
+    {
+        let boundary_bonus = if word_start(start) { 100 } else { 0 };
+        let points = boundary_bonus - start.min(100) as i64;
+        return Some((
+            FuzzyScore {
+                contiguous: true,
+                points,
+            },
+            start,
+        ));
+    }
+
+    let mut points = 0i64;
+    let mut first = None;
+    let mut previous: Option<usize> = None;
+    let mut next = 0;
+    for &wanted in query.iter().filter(|ch| !ch.is_whitespace()) {
+        let offset = text[next..].iter().position(|&ch| ch == wanted)?;
+        let idx = next + offset;
+        points += 1;
+        if word_start(idx) {
+            points += 8;
+        }
+        match previous {
+            Some(prev) if prev + 1 == idx => points += 5,
+            Some(prev) => points -= (idx - prev - 1).min(10) as i64,
+            None => {}
+        }
+        first.get_or_insert(idx);
+        previous = Some(idx);
+        next = idx + 1;
+    }
+    Some((
+        FuzzyScore {
+            contiguous: false,
+            points,
+        },
+        first?,
+    ))
+}
+
+/// The line of `text` holding char `start`, trimmed and cut to fit.
+fn snippet_at(text: &str, start: usize) -> String {
+    let mut seen = 0;
+    let line = text
+        .lines()
+        .find(|line| {
+            // `lines` drops the line break, which counts as one char here.
+            seen += line.chars().count() + 1;
+            seen > start
+        })
+        .unwrap_or_default()
+        .trim();
+    if line.chars().count() <= SNIPPET_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(SNIPPET_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::content_to_card;
+    use std::path::Path;
+
+    #[test]
+    fn substring_matches_outrank_scattered_ones() {
+        let exact = fuzzy_score("mito", "The mitochondria make ATP").unwrap();
+        let scattered = fuzzy_score("mito", "Many islands tower offshore").unwrap();
+        assert!(exact.0.contiguous && !scattered.0.contiguous);
+        assert!(exact.0 > scattered.0);
+        assert_eq!(exact.1, 4);
+
+        // Case is ignored, and the characters must appear in order.
+        assert!(fuzzy_score("MITO", "mitosis").is_some());
+        assert!(fuzzy_score("otim", "mitosis").is_none());
+        assert!(fuzzy_score("  ", "anything").is_none());
+
+        // Among scattered matches, word starts and runs score higher.
+        let initials = fuzzy_score("bcl", "Basal cell layer").unwrap();
+        let buried = fuzzy_score("bcl", "abacus oracle").unwrap();
+        assert!(initials.0 > buried.0);
+
+        let path = Path::new("deck.md");
+        let cards = vec![
+            content_to_card(path, "Q: Many islands tower where?\nA: offshore", 0, 2).unwrap(),
+            content_to_card(path, "C: The [mitochondria] make ATP", 3, 4).unwrap(),
+            content_to_card(path, "Q: unrelated?\nA: no", 5, 7).unwrap(),
+        ];
+        let ranked = rank_cards("mito", &cards);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].1.snippet, "The [mitochondria] make ATP");
+        assert!(ranked[0].1.score > ranked[1].1.score);
+        assert_eq!(ranked[1].1.snippet, "Many islands tower where?");
+    }
+
+    #[test]
+    fn snippets_show_the_matching_line() {
+        let text = "first line\nsecond line with the term\nthird";
+        let (_, start) = fuzzy_score("term", text).unwrap();
+        assert_eq!(snippet_at(text, start), "second line with the term");
+        let long = "word ".repeat(30);
+        let snippet = snippet_at(&long, 0);
+        assert!(snippet.ends_with('…'));
+        assert!(snippet.chars().count() <= SNIPPET_CHARS);
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+
+use crate::card::{Card, CardContent, ClozeRange};
+use crate::cloze_utils::{find_cloze_ranges, unbracket_cloze_text};
+use crate::llm::drill_preprocessor::does_card_need_cloze;
+use crate::llm::prompt_user::cloze_user_prompt;
+use crate::llm::{ensure_client, request_cloze};
+use crate::parser::{CollectOptions, STDIN_LABEL, collect_cards};
+use crate::tui::{Spinner, checklist};
+use crate::utils::pluralize;
+
+const MAX_CONCURRENT_LLM_REQUESTS: usize = 4;
+
+/// Set to `1` (or `true`) to make `fix` send every malformed cloze without
+/// asking, as if `--auto` were passed.
+pub const AUTO_CLOZE_ENV: &str = "REPEATER_AUTO_CLOZE";
+
+fn auto_cloze_from_env() -> bool {
+    std::env::var(AUTO_CLOZE_ENV).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        )
+    })
+}
+
+/// Lets the user pick which cloze cards without a `[deletion]` under `paths`
+/// to send to the LLM, then writes the bracketed text back into their files.
+/// With `auto` (or `REPEATER_AUTO_CLOZE`) every such card is sent without the
+/// checklist; with `dry_run` the rewrites are printed instead of written.
+pub async fn run(paths: Vec<PathBuf>, auto: bool, dry_run: bool) -> Result<()> {
+    let (cards, _) = collect_cards(paths, CollectOptions::default())?;
+    let malformed: Vec<Card> = cards
+        .into_iter()
+        .filter(|card| does_card_need_cloze(card) && card.file_path != Path::new(STDIN_LABEL))
+        .collect();
+    if malformed.is_empty() {
+        println!("No cloze cards are missing a bracketed deletion.");
+        return Ok(());
+    }
+
+    let chosen = if auto || auto_cloze_from_env() {
+        malformed
+    } else {
+        let labels: Vec<String> = malformed.iter().map(Card::location_label).collect();
+        let Some(selected) = checklist::pick("Cloze cards missing a deletion", &labels)? else {
+            println!("Nothing changed.");
+            return Ok(());
+        };
+        if selected.is_empty() {
+            println!("No cards selected; nothing changed.");
+            return Ok(());
+        }
+        selected
+            .into_iter()
+            .map(|index| malformed[index].clone())
+            .collect()
+    };
+
+    let prompt = cloze_user_prompt(&chosen, chosen.len()).unwrap_or_default();
+    let client = ensure_client(&prompt)?;
+    let spinner = Spinner::start(format!(
+        "Generating clozes for {}",
+        pluralize("card", chosen.len())
+    ));
+    let rewrites = suggest_rewrites(chosen, |text| {
+        let client = &client;
+        async move { request_cloze(client, &text).await }
+    })
+    .await;
+    drop(spinner);
+
+    if dry_run {
+        print!("{}", format_rewrites(&rewrites));
+        println!(
+            "Dry run: would add a deletion to {}.",
+            pluralize("card", rewrites.len())
+        );
+        return Ok(());
+    }
+    let written = write_rewrites(&rewrites)?;
+    println!("Added a deletion to {}.", pluralize("card", written));
+    Ok(())
+}
+
+/// Asks `suggest` for each card's bracketed text and keeps the first
+/// suggestion that only adds brackets; the rest are skipped with a warning.
+async fn suggest_rewrites<F, Fut>(cards: Vec<Card>, suggest: F) -> Vec<(Card, String)>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let suggestions: Vec<(Card, Result<String>)> = stream::iter(cards)
+        .map(|card| {
+            let text = cloze_text(&card).unwrap_or_default().to_string();
+            let suggestion = suggest(text);
+            async move { (card, suggestion.await) }
+        })
+        .buffer_unordered(MAX_CONCURRENT_LLM_REQUESTS)
+        .collect()
+        .await;
+
+    let mut rewrites: Vec<(Card, String)> = Vec::new();
+    for (card, suggestion) in suggestions {
+        let location = card.location_label();
+        match suggestion {
+            Ok(suggestion) => match accepted_cloze(&card, &suggestion) {
+                Some(text) => rewrites.push((card, text)),
+                None => eprintln!(
+                    "warning: skipped {location}; the suggestion changed more than the brackets: {suggestion}"
+                ),
+            },
+            Err(err) => eprintln!("warning: skipped {location}: {err:#}"),
+        }
+    }
+    // Concurrent requests finish in any order; report and write in file order.
+    rewrites.sort_by(|(a, _), (b, _)| a.locator().cmp(&b.locator()));
+    rewrites
+}
+
+/// Each rewrite as its `path:line` label followed by the new text, for `--dry-run`.
+fn format_rewrites(rewrites: &[(Card, String)]) -> String {
+    rewrites
+        .iter()
+        .map(|(card, text)| format!("{}\n  -> {text}\n", card.location_label()))
+        .collect()
+}
+
+fn cloze_text(card: &Card) -> Option<&str> {
+    match &card.content {
+        CardContent::Cloze { text, .. } => Some(text),
+        CardContent::Basic { .. } => None,
+    }
+}
+
+/// The LLM's text if it is the card's text with exactly one bracket pair
+/// added, so a reply that rewords the card is never written to disk.
+fn accepted_cloze(card: &Card, suggestion: &str) -> Option<String> {
+    let original = cloze_text(card)?;
+    let suggestion = suggestion.trim();
+    let suggestion = suggestion.strip_prefix("C:").unwrap_or(suggestion).trim();
+    let &(start, end) = find_cloze_ranges(suggestion).first()?;
+    let range = ClozeRange::new(start, end).ok()?;
+    (unbracket_cloze_text(suggestion, &range) == original).then(|| suggestion.to_string())
+}
+
+/// Replaces each card's text inside its own line range, one write per file.
+/// Returns how many cards were rewritten.
+fn write_rewrites(rewrites: &[(Card, String)]) -> Result<usize> {
+    let mut by_file: BTreeMap<&Path, Vec<&(Card, String)>> = BTreeMap::new();
+    for rewrite in rewrites {
+        by_file
+            .entry(rewrite.0.file_path.as_path())
+            .or_default()
+            .push(rewrite);
+    }
+    let mut written = 0;
+    for (path, rewrites) in by_file {
+        let mut contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        for (card, text) in rewrites {
+            match rewrite_card(&contents, card, text) {
+                Some(updated) => {
+                    contents = updated;
+                    written += 1;
+                }
+                None => eprintln!(
+                    "warning: skipped {}; its text could not be found in the file",
+                    card.location_label()
+                ),
+            }
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(written)
+}
+
+/// `contents` with the card's cloze text replaced by `new_text`. Only the
+/// card's own lines are searched, so identical text elsewhere is untouched.
+fn rewrite_card(contents: &str, card: &Card, new_text: &str) -> Option<String> {
+    let original = cloze_text(card)?;
+    let (start, end) = card.file_card_range;
+    let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let before: String = lines.iter().take(start).copied().collect();
+    let block: String = lines.iter().take(end).skip(start).copied().collect();
+    let after: String = lines.iter().skip(end).copied().collect();
+    if !block.contains(original) {
+        return None;
+    }
+    Some(format!(
+        "{before}{}{after}",
+        block.replacen(original, new_text, 1)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{cards_from_md, content_to_card};
+
+    #[test]
+    fn accepted_suggestions_only_add_brackets() {
+        let card = content_to_card(
+            Path::new("deck.md"),
+            "C: Speech is made in Broca's area",
+            0,
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            accepted_cloze(&card, "C: Speech is made in [Broca's] area\n").as_deref(),
+            Some("Speech is made in [Broca's] area")
+        );
+        assert_eq!(
+            accepted_cloze(&card, "Speech is produced in [Broca's] area"),
+            None
+        );
+        assert_eq!(
+            accepted_cloze(&card, "Speech is made in Broca's area"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn auto_fix_takes_each_suggestion_and_rewrites_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deck.md");
+        let contents =
+            "C: Paris is in France\n---\nC: Water boils at 100 degrees\n---\nC: Rome is in Italy\n";
+        fs::write(&path, contents).unwrap();
+        let cards: Vec<Card> = cards_from_md(&path)
+            .unwrap()
+            .into_iter()
+            .filter(does_card_need_cloze)
+            .collect();
+        assert_eq!(cards.len(), 3);
+
+        // Brackets the first word; the Rome reply rewords the card and is refused.
+        let suggest = |text: String| async move {
+            if text.starts_with("Rome") {
+                return Ok("C: [Rome] is the capital of Italy".to_string());
+            }
+            let (first, rest) = text.split_once(' ').unwrap();
+            Ok(format!("C: [{first}] {rest}"))
+        };
+        let rewrites = suggest_rewrites(cards, suggest).await;
+        assert_eq!(
+            format_rewrites(&rewrites),
+            format!(
+                "{0}:1  Paris is in France\n  -> [Paris] is in France\n{0}:3  Water boils at 100 degrees\n  -> [Water] boils at 100 degrees\n",
+                path.display()
+            )
+        );
+        // Printing the dry run leaves the file alone.
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+
+        assert_eq!(write_rewrites(&rewrites).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "C: [Paris] is in France\n---\nC: [Water] boils at 100 degrees\n---\nC: Rome is in Italy\n"
+        );
+    }
+
+    #[test]
+    fn selected_cards_are_rewritten_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deck.md");
+        let contents = "C: The moon orbits the earth\n@tags space\n---\nQ: kept?\nA: yes\n---\nC: The sun is a star\n";
+        fs::write(&path, contents).unwrap();
+        let cards = cards_from_md(&path).unwrap();
+        let moon = cards[0].clone();
+        let sun = cards[2].clone();
+
+        let rewrites = vec![
+            (sun, "The sun is a [star]".to_string()),
+            (moon, "The moon orbits the [earth]".to_string()),
+        ];
+        assert_eq!(write_rewrites(&rewrites).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "C: The moon orbits the [earth]\n@tags space\n---\nQ: kept?\nA: yes\n---\nC: The sun is a [star]\n"
+        );
+
+        let reparsed = cards_from_md(&path).unwrap();
+        assert!(reparsed.iter().all(|card| !does_card_need_cloze(card)));
+        assert_eq!(reparsed.len(), 3);
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::card::Card;
+use crate::crud::DB;
+use crate::fsrs::ReviewStatus;
+use crate::palette::Palette;
+use crate::parser::{CollectOptions, register_all_cards};
+
+const MAX_LISTED_MATCHES: usize = 5;
+
+pub async fn run(
+    db: &DB,
+    target: &str,
+    review_status: ReviewStatus,
+    paths: Vec<PathBuf>,
+) -> Result<()> {
+    let (card, interval) = grade(db, target, review_status, paths).await?;
+    println!(
+        "Graded {} as {} (see again in {:.1} days)",
+        Palette::paint(Palette::ACCENT, describe(&card)),
+        review_status.label(),
+        interval
+    );
+    Ok(())
+}
+
+async fn grade(
+    db: &DB,
+    target: &str,
+    review_status: ReviewStatus,
+    paths: Vec<PathBuf>,
+) -> Result<(Card, f64)> {
+    let card = resolve_target(db, target, paths).await?;
+    let interval = db.review(&card, review_status).await?;
+    Ok((card, interval))
+}
+
+/// Finds the single card `target` refers to. A target can be `file.md:LINE`,
+/// a file holding exactly one card, a card hash, or text that appears in
+/// exactly one card under `paths`.
+async fn resolve_target(db: &DB, target: &str, paths: Vec<PathBuf>) -> Result<Card> {
+    let mut matches: Vec<Card> = if let Some((file, line)) = parse_file_line(target) {
+        collect(db, vec![file])
+            .await?
+            .into_iter()
+            .filter(|card| contains_line(card, line))
+            .collect()
+    } else if Path::new(target).is_file() {
+        collect(db, vec![PathBuf::from(target)]).await?
+    } else {
+        collect(db, paths)
+            .await?
+            .into_iter()
+            .filter(|card| card.card_hash == target || card.matches_query(target))
+            .collect()
+    };
+    matches.sort_by(|a, b| a.locator().cmp(&b.locator()));
+
+    match matches.len() {
+        0 => bail!("No card matches `{target}`"),
+        1 => Ok(matches.remove(0)),
+        n => {
+            let mut message = format!("`{target}` matches {n} cards; narrow it down:");
+            for card in matches.iter().take(MAX_LISTED_MATCHES) {
+                message.push_str(&format!("\n  {}", describe(card)));
+            }
+            if n > MAX_LISTED_MATCHES {
+                message.push_str(&format!("\n  ...and {} more", n - MAX_LISTED_MATCHES));
+            }
+            bail!(message)
+        }
+    }
+}
+
+async fn collect(db: &DB, paths: Vec<PathBuf>) -> Result<Vec<Card>> {
+    let (cards, _) = register_all_cards(db, paths, CollectOptions::default()).await?;
+    Ok(cards.into_values().collect())
+}
+
+fn parse_file_line(target: &str) -> Option<(PathBuf, usize)> {
+    let (file, line) = target.rsplit_once(':')?;
+    let line = line.parse().ok()?;
+    let file = PathBuf::from(file);
+    file.is_file().then_some((file, line))
+}
+
+/// Whether the 1-based `line` falls inside the card's range.
+fn contains_line(card: &Card, line: usize) -> bool {
+    let (_, start, end) = card.locator();
+    (start + 1..=end.max(start + 1)).contains(&line)
+}
+
+fn describe(card: &Card) -> String {
+    let (path, start, _) = card.locator();
+    format!("{}:{}", path.display(), start + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsrs::Performance;
+
+    const DECK: &str = "Q: What is the capital of France?\nA: Paris\n\nQ: What is the capital of Italy?\nA: Rome\n";
+
+    #[tokio::test]
+    async fn grading_a_unique_match_advances_review_count() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("deck.md"), DECK).unwrap();
+        let paths = vec![dir.path().to_path_buf()];
+
+        let (card, _) = grade(&db, "france", ReviewStatus::Pass, paths.clone())
+            .await
+            .unwrap();
+        let Performance::Reviewed(reviewed) = db.get_card_performance(&card).await.unwrap() else {
+            panic!("card should have been reviewed");
+        };
+        assert_eq!(reviewed.review_count, 1);
+
+        let target = format!("{}:5", dir.path().join("deck.md").display());
+        let (card, _) = grade(&db, &target, ReviewStatus::Fail, paths)
+            .await
+            .unwrap();
+        assert!(card.matches_query("Italy"));
+    }
+
+    #[tokio::test]
+    async fn ambiguous_or_missing_targets_are_rejected() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("deck.md"), DECK).unwrap();
+        let paths = vec![dir.path().to_path_buf()];
+
+        let err = grade(&db, "capital", ReviewStatus::Pass, paths.clone())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("matches 2 cards"));
+
+        let err = grade(&db, "berlin", ReviewStatus::Pass, paths)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No card matches"));
+    }
+}
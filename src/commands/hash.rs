@@ -0,0 +1,64 @@
+use std::io::{self, Read};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::parser::{card_hash_source, get_hash, normalize_for_hash};
+
+/// Prints the hash a card with this text gets, reading stdin when no text is
+/// given. With `tokens`, also prints the normalized words that feed the hash,
+/// to see why two cards collide or differ.
+pub fn run(text: Option<String>, tokens: bool) -> Result<()> {
+    let text = match text {
+        Some(text) => text,
+        None => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .context("failed to read text from stdin")?;
+            input
+        }
+    };
+    print!("{}", format_hash(&text, tokens)?);
+    Ok(())
+}
+
+fn format_hash(text: &str, tokens: bool) -> Result<String> {
+    let source = card_hash_source(text);
+    let hash = get_hash(&source).ok_or_else(|| anyhow!("Nothing to hash: the card is blank"))?;
+    let mut out = format!("{hash}\n");
+    if tokens {
+        let normalized = normalize_for_hash(&source);
+        let words: Vec<&str> = normalized.split(' ').collect();
+        out.push_str(&format!("tokens: {words:?}\n"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::parser::content_to_card;
+
+    #[test]
+    fn prints_the_hash_the_parsed_card_gets() {
+        for text in [
+            "Q: What is  the capital\nof France?\nA: Paris",
+            "Q: What is 2+2?\n@hard\nA: 4",
+            "Q: x? | A: y",
+        ] {
+            let card = content_to_card(Path::new("deck.md"), text, 0, 3).unwrap();
+            assert_eq!(
+                format_hash(text, false).unwrap(),
+                format!("{}\n", card.card_hash),
+                "{text:?}"
+            );
+        }
+
+        let tokens = format_hash("Q: What is 2+2?\n@hard\nA: 4", true).unwrap();
+        assert!(tokens.ends_with("tokens: [\"q:\", \"what\", \"is\", \"2+2?\", \"a:\", \"4\"]\n"));
+        assert!(format_hash(" \n\t", false).is_err());
+        assert!(format_hash("@hard\n", false).is_err());
+    }
+}
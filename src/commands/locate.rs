@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::crud::DB;
+use crate::crud::db::{DB_FILE_NAME, HOME_ENV};
+use crate::llm::secrets::keyring_entry;
+
+/// Label/value pairs printed by `repeater where`.
+fn where_rows(data_dir: &Path) -> Vec<(&'static str, String)> {
+    vec![
+        ("Data dir", data_dir.display().to_string()),
+        (
+            "Database",
+            data_dir.join(DB_FILE_NAME).display().to_string(),
+        ),
+        (
+            "Config",
+            format!(
+                "none; settings are REPEATER_* environment variables (set {HOME_ENV} to move the data dir)"
+            ),
+        ),
+        ("Keyring", keyring_entry()),
+    ]
+}
+
+fn format_where(data_dir: &Path) -> String {
+    where_rows(data_dir)
+        .into_iter()
+        .map(|(label, value)| format!("{:<10}{value}\n", format!("{label}:")))
+        .collect()
+}
+
+/// Prints where the database and the stored API key live.
+pub fn print_where() -> Result<()> {
+    print!("{}", format_where(&DB::data_dir()?));
+    Ok(())
+}
+
+/// Opens the data directory in the OS file manager, creating it if needed.
+pub fn open_data_dir() -> Result<()> {
+    let data_dir = DB::data_dir()?;
+    std::fs::create_dir_all(&data_dir)
+        .with_context(|| format!("Failed to create {}", data_dir.display()))?;
+    open::that(&data_dir).with_context(|| format!("Failed to open {}", data_dir.display()))?;
+    println!("Opened {}", data_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn where_reports_paths_under_the_home_override() {
+        let home = PathBuf::from("/tmp/repeater-home");
+        let data_dir = DB::data_dir_with(Some(home.clone().into_os_string())).unwrap();
+        assert_eq!(data_dir, home);
+
+        let report = format_where(&data_dir);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Data dir: /tmp/repeater-home");
+        assert_eq!(
+            lines[1],
+            format!("Database: {}", home.join("cards.db").display())
+        );
+        assert_eq!(lines[3], "Keyring:  com.repeater/openai:default");
+
+        // An empty override falls back to the OS data directory.
+        assert_ne!(DB::data_dir_with(Some("".into())).unwrap(), PathBuf::new());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::card::{Card, CardDirectives};
+use crate::parser::{CollectOptions, STDIN_LABEL, collect_cards};
+use crate::utils::{confirm, pluralize};
+
+/// Copies of one card: the occurrence that stays and the ones to delete.
+#[derive(Debug)]
+struct DuplicateGroup {
+    keep: Card,
+    remove: Vec<Card>,
+}
+
+/// Deletes every extra copy of a card under `paths`, keeping one occurrence
+/// per card. Copies share a hash and so already share their review history.
+pub fn run(paths: Vec<PathBuf>, dry_run: bool, yes: bool) -> Result<()> {
+    let (cards, _) = collect_cards(paths, CollectOptions::default())?;
+    let groups = duplicate_groups(cards);
+    print!("{}", format_merge(&groups));
+    if dry_run || groups.is_empty() {
+        return Ok(());
+    }
+    let removed: usize = groups.iter().map(|group| group.remove.len()).sum();
+    let prompt = format!(
+        "Delete {} from your card files?",
+        pluralize("duplicate", removed)
+    );
+    if !confirm(prompt, yes)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+    remove_occurrences(&groups)?;
+    println!("Deleted {}.", pluralize("duplicate", removed));
+    Ok(())
+}
+
+/// Groups cards that share a hash, in document order of their first copy.
+/// Cards read from stdin can't be edited and are left out.
+fn duplicate_groups(cards: Vec<Card>) -> Vec<DuplicateGroup> {
+    let mut by_hash: HashMap<String, Vec<Card>> = HashMap::new();
+    for card in cards {
+        if card.file_path == Path::new(STDIN_LABEL) {
+            continue;
+        }
+        by_hash
+            .entry(card.card_hash.clone())
+            .or_default()
+            .push(card);
+    }
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_values()
+        .filter(|copies| copies.len() > 1)
+        .map(|mut copies| {
+            let keep = copies.remove(canonical_index(&copies));
+            copies.sort_by(|a, b| a.locator().cmp(&b.locator()));
+            DuplicateGroup {
+                keep,
+                remove: copies,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.keep.locator().cmp(&b.keep.locator()));
+    groups
+}
+
+/// The copy to keep: one carrying directives such as `@tags` or `@important`
+/// if there is one, since deleting it would drop them, otherwise the first
+/// copy in document order.
+fn canonical_index(copies: &[Card]) -> usize {
+    let has_directives = |card: &Card| card.directives != CardDirectives::default();
+    copies
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            has_directives(b)
+                .cmp(&has_directives(a))
+                .then_with(|| a.locator().cmp(&b.locator()))
+        })
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+/// Deletes the lines of every copy being removed, one write per file.
+fn remove_occurrences(groups: &[DuplicateGroup]) -> Result<()> {
+    let mut ranges: BTreeMap<&Path, Vec<(usize, usize)>> = BTreeMap::new();
+    for card in groups.iter().flat_map(|group| &group.remove) {
+        ranges
+            .entry(card.file_path.as_path())
+            .or_default()
+            .push(card.file_card_range);
+    }
+    for (path, ranges) in ranges {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        fs::write(path, without_lines(&contents, &ranges))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// `contents` minus the lines in the half-open `ranges`, keeping line endings.
+fn without_lines(contents: &str, ranges: &[(usize, usize)]) -> String {
+    contents
+        .split_inclusive('\n')
+        .enumerate()
+        .filter(|(index, _)| {
+            !ranges
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(index))
+        })
+        .map(|(_, line)| line)
+        .collect()
+}
+
+fn format_location(card: &Card) -> String {
+    let (path, start, end) = card.locator();
+    format!("{}:{}-{}", path.display(), start + 1, end.max(start + 1))
+}
+
+fn format_merge(groups: &[DuplicateGroup]) -> String {
+    if groups.is_empty() {
+        return "No duplicate cards found.\n".to_string();
+    }
+    let mut out = format!(
+        "{} with copies in several places:\n",
+        pluralize("card", groups.len())
+    );
+    for group in groups {
+        out.push_str(&format!("keep    {}\n", format_location(&group.keep)));
+        for card in &group.remove {
+            out.push_str(&format!("delete  {}\n", format_location(card)));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::content_to_card;
+
+    #[test]
+    fn canonical_copy_prefers_directives_then_document_order() {
+        let a = Path::new("a.md");
+        let b = Path::new("b.md");
+        let cards = vec![
+            content_to_card(b, "Q: Capital of Peru?\nA: Lima", 0, 2).unwrap(),
+            content_to_card(a, "Q: Capital of Peru?\nA: Lima", 4, 6).unwrap(),
+            content_to_card(a, "Q: Capital of Chile?\nA: Santiago", 0, 2).unwrap(),
+            content_to_card(b, "Q: Capital of Chile?\nA: Santiago", 3, 6).unwrap(),
+            content_to_card(a, "Q: Capital of Chile?\nA: Santiago\n@tags geo", 8, 11).unwrap(),
+            content_to_card(a, "Q: Unique?\nA: yes", 12, 14).unwrap(),
+        ];
+
+        let groups = duplicate_groups(cards);
+        assert_eq!(groups.len(), 2);
+
+        // Peru: neither copy has directives, so the first in document order stays.
+        assert_eq!(groups[0].keep.locator(), (a, 4, 6));
+        let removed: Vec<_> = groups[0].remove.iter().map(Card::locator).collect();
+        assert_eq!(removed, vec![(b, 0, 2)]);
+
+        // Chile: the tagged copy stays even though it comes last.
+        assert_eq!(groups[1].keep.locator(), (a, 8, 11));
+        let removed: Vec<_> = groups[1].remove.iter().map(Card::locator).collect();
+        assert_eq!(removed, vec![(a, 0, 2), (b, 3, 6)]);
+    }
+
+    #[test]
+    fn removing_copies_keeps_the_surrounding_lines() {
+        let contents = "Q: a?\nA: a\n---\nQ: a?\nA: a\n---\nnotes";
+        assert_eq!(
+            without_lines(contents, &[(3, 5)]),
+            "Q: a?\nA: a\n---\n---\nnotes"
+        );
+    }
+}
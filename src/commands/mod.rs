@@ -1,3 +1,16 @@
 pub mod check;
 pub mod create;
+pub mod doctor;
 pub mod drill;
+pub mod edits;
+pub mod find;
+pub mod fix;
+pub mod grade;
+pub mod hash;
+pub mod locate;
+pub mod merge;
+pub mod prune;
+pub mod reset;
+pub mod tags;
+pub mod undo;
+pub mod validate;
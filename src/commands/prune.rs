@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::card::Card;
+use crate::commands::check::last_reviewed_label;
+use crate::crud::DB;
+use crate::parser::{CollectOptions, register_all_cards};
+use crate::stats::{DayZone, GhostCard};
+use crate::utils::{confirm, pluralize};
+
+/// Deletes the DB rows of cards that no longer exist under `paths`.
+pub async fn run(db: &DB, paths: Vec<PathBuf>, dry_run: bool, yes: bool) -> Result<()> {
+    let (hash_cards, _) = register_all_cards(db, paths, CollectOptions::default()).await?;
+    let ghosts = prune(db, &hash_cards, true).await?;
+    print!("{}", format_prune(&ghosts, true));
+    if dry_run || ghosts.is_empty() {
+        return Ok(());
+    }
+    let prompt = format!(
+        "Delete {} and their review history? Cards outside the given paths count as ghosts too.",
+        pluralize("ghost card", ghosts.len())
+    );
+    if !confirm(prompt, yes)? {
+        println!("Nothing deleted.");
+        return Ok(());
+    }
+    let deleted = prune(db, &hash_cards, false).await?;
+    print!("{}", format_prune(&deleted, false));
+    Ok(())
+}
+
+/// The ghost cards of `hash_cards`, deleted unless `dry_run` is set.
+async fn prune(
+    db: &DB,
+    hash_cards: &HashMap<String, Card>,
+    dry_run: bool,
+) -> Result<Vec<GhostCard>> {
+    let ghosts = db
+        .collection_stats(hash_cards, DayZone::from_env())
+        .await?
+        .ghosts;
+    if !dry_run && !ghosts.is_empty() {
+        let hashes: Vec<String> = ghosts.iter().map(|g| g.card_hash.clone()).collect();
+        db.delete_cards(&hashes).await?;
+    }
+    Ok(ghosts)
+}
+
+fn format_prune(ghosts: &[GhostCard], dry_run: bool) -> String {
+    if ghosts.is_empty() {
+        return "No ghost cards to prune.\n".to_string();
+    }
+    let verb = if dry_run { "Would delete" } else { "Deleted" };
+    let mut out = format!("{verb} {}:\n", pluralize("ghost card", ghosts.len()));
+    for ghost in ghosts {
+        out.push_str(&format!(
+            "{}  {}\n",
+            ghost.card_hash,
+            last_reviewed_label(ghost)
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::fsrs::ReviewStatus;
+    use crate::parser::content_to_card;
+
+    #[tokio::test]
+    async fn dry_run_lists_ghosts_without_deleting_them() {
+        let db = DB::new_in_memory().await.unwrap();
+        let path = PathBuf::from("deck.md");
+        let kept = content_to_card(&path, "Q: kept?\nA: yes", 0, 2).unwrap();
+        let gone = content_to_card(&path, "Q: gone?\nA: yes", 3, 5).unwrap();
+        for card in [&kept, &gone] {
+            db.add_card(card).await.unwrap();
+            db.review(card, ReviewStatus::Pass).await.unwrap();
+        }
+        let hash_cards = HashMap::from([(kept.card_hash.clone(), kept.clone())]);
+
+        let preview = prune(&db, &hash_cards, true).await.unwrap();
+        assert_eq!(preview.len(), 1);
+        assert_eq!(preview[0].card_hash, gone.card_hash);
+        assert!(format_prune(&preview, true).starts_with("Would delete 1 ghost card:\n"));
+        assert!(db.card_exists(&gone).await.unwrap());
+
+        let deleted = prune(&db, &hash_cards, false).await.unwrap();
+        assert_eq!(deleted, preview);
+        assert!(!db.card_exists(&gone).await.unwrap());
+        assert!(db.card_exists(&kept).await.unwrap());
+        assert!(prune(&db, &hash_cards, true).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::card::Card;
+use crate::crud::DB;
+use crate::fsrs::Performance;
+use crate::parser::{CollectOptions, register_all_cards};
+use crate::utils::{confirm, pluralize};
+
+/// Makes every reviewed card under `paths` new again.
+pub async fn run(db: &DB, paths: Vec<PathBuf>, dry_run: bool, yes: bool) -> Result<()> {
+    let (hash_cards, _) = register_all_cards(db, paths, CollectOptions::default()).await?;
+    let cards = reset(db, &hash_cards, true).await?;
+    print!("{}", format_reset(&cards, true));
+    if dry_run || cards.is_empty() {
+        return Ok(());
+    }
+    let prompt = format!("Reset the progress of {}?", pluralize("card", cards.len()));
+    if !confirm(prompt, yes)? {
+        println!("Nothing reset.");
+        return Ok(());
+    }
+    let reset_cards = reset(db, &hash_cards, false).await?;
+    print!("{}", format_reset(&reset_cards, false));
+    Ok(())
+}
+
+/// The cards of `hash_cards` that have review progress, in document order;
+/// their progress is cleared unless `dry_run` is set.
+async fn reset(db: &DB, hash_cards: &HashMap<String, Card>, dry_run: bool) -> Result<Vec<Card>> {
+    let cards: Vec<Card> = hash_cards.values().cloned().collect();
+    let performances = db.get_performances(&cards).await?;
+    let mut reviewed: Vec<Card> = cards
+        .into_iter()
+        .filter(|card| {
+            matches!(
+                performances.get(&card.card_hash),
+                Some(Performance::Reviewed(_))
+            )
+        })
+        .collect();
+    reviewed.sort_by(|a, b| a.locator().cmp(&b.locator()));
+    if !dry_run && !reviewed.is_empty() {
+        let hashes: Vec<String> = reviewed.iter().map(|c| c.card_hash.clone()).collect();
+        db.reset_cards(&hashes).await?;
+    }
+    Ok(reviewed)
+}
+
+fn format_reset(cards: &[Card], dry_run: bool) -> String {
+    if cards.is_empty() {
+        return "No reviewed cards to reset.\n".to_string();
+    }
+    let verb = if dry_run { "Would reset" } else { "Reset" };
+    let mut out = format!("{verb} {}:\n", pluralize("card", cards.len()));
+    for card in cards {
+        let (path, start, _) = card.locator();
+        out.push_str(&format!(
+            "{}:{}  {}\n",
+            path.display(),
+            start + 1,
+            card.card_hash
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::fsrs::ReviewStatus;
+    use crate::parser::content_to_card;
+
+    #[tokio::test]
+    async fn dry_run_lists_reviewed_cards_without_resetting_them() {
+        let db = DB::new_in_memory().await.unwrap();
+        let path = PathBuf::from("deck.md");
+        let reviewed = content_to_card(&path, "Q: reviewed?\nA: yes", 0, 2).unwrap();
+        let fresh = content_to_card(&path, "Q: fresh?\nA: yes", 3, 5).unwrap();
+        for card in [&reviewed, &fresh] {
+            db.add_card(card).await.unwrap();
+        }
+        db.review(&reviewed, ReviewStatus::Pass).await.unwrap();
+        let hash_cards: HashMap<String, Card> = [&reviewed, &fresh]
+            .into_iter()
+            .map(|card| (card.card_hash.clone(), card.clone()))
+            .collect();
+
+        let preview = reset(&db, &hash_cards, true).await.unwrap();
+        assert_eq!(preview.len(), 1);
+        assert_eq!(preview[0].card_hash, reviewed.card_hash);
+        assert_eq!(
+            format_reset(&preview, true),
+            format!("Would reset 1 card:\ndeck.md:1  {}\n", reviewed.card_hash)
+        );
+        assert!(matches!(
+            db.get_card_performance(&reviewed).await.unwrap(),
+            Performance::Reviewed(_)
+        ));
+
+        reset(&db, &hash_cards, false).await.unwrap();
+        assert_eq!(
+            db.get_card_performance(&reviewed).await.unwrap(),
+            Performance::New
+        );
+        assert!(reset(&db, &hash_cards, true).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::card::Card;
+use crate::parser::{CollectOptions, collect_cards};
+
+/// Label for cards without a `@tags` line.
+const UNTAGGED: &str = "(untagged)";
+
+/// Prints every tag under `paths` with the number of cards carrying it.
+pub fn run(paths: Vec<PathBuf>) -> Result<()> {
+    let (cards, _) = collect_cards(paths, CollectOptions::default())?;
+    print!("{}", format_tags(&count_tags(&cards)));
+    Ok(())
+}
+
+/// Card count per tag, most used first, ties by name. Cards with no tags are
+/// counted under `(untagged)`.
+fn count_tags(cards: &[Card]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for card in cards {
+        if card.directives.tags.is_empty() {
+            *counts.entry(UNTAGGED).or_default() += 1;
+        }
+        for tag in &card.directives.tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(tag, count)| (tag.to_string(), count))
+        .collect();
+    counts.sort_by(|(tag_a, count_a), (tag_b, count_b)| {
+        count_b.cmp(count_a).then_with(|| tag_a.cmp(tag_b))
+    });
+    counts
+}
+
+fn format_tags(counts: &[(String, usize)]) -> String {
+    if counts.is_empty() {
+        return "No cards found.\n".to_string();
+    }
+    let width = counts
+        .iter()
+        .map(|(_, count)| count.to_string().len())
+        .max()
+        .unwrap_or(1);
+    counts
+        .iter()
+        .map(|(tag, count)| format!("{count:>width$}  {tag}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::parser::content_to_card;
+
+    #[test]
+    fn tags_are_counted_across_cards_by_frequency() {
+        let path = Path::new("deck.md");
+        let cards: Vec<Card> = [
+            "Q: a?\nA: a\n@tags bio, cells",
+            "Q: b?\nA: b\n@tags cells",
+            "Q: c?\nA: c\n@tags #cells exam",
+            "Q: d?\nA: d",
+        ]
+        .iter()
+        .map(|content| content_to_card(path, content, 0, 3).unwrap())
+        .collect();
+
+        let counts = count_tags(&cards);
+        let expected = [("cells", 3), ("(untagged)", 1), ("bio", 1), ("exam", 1)];
+        assert_eq!(
+            counts,
+            expected.map(|(tag, count)| (tag.to_string(), count))
+        );
+        assert_eq!(
+            format_tags(&counts),
+            "3  cells\n1  (untagged)\n1  bio\n1  exam\n"
+        );
+        assert_eq!(format_tags(&[]), "No cards found.\n");
+    }
+}
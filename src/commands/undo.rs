@@ -0,0 +1,25 @@
+use anyhow::Result;
+
+use crate::crud::DB;
+use crate::crud::cards::UndoneReview;
+use crate::utils::pluralize;
+
+/// Takes back the latest `count` reviews, from `drill` or `grade`.
+pub async fn run(db: &DB, count: usize) -> Result<()> {
+    let undone = db.undo_last_reviews(count).await?;
+    print!("{}", format_undone(&undone));
+    Ok(())
+}
+
+fn format_undone(undone: &[UndoneReview]) -> String {
+    let mut out = format!("Undid {}:\n", pluralize("review", undone.len()));
+    for review in undone {
+        out.push_str(&format!(
+            "  {:<5} {}  reviewed {}\n",
+            review.review_status,
+            review.card_hash,
+            review.reviewed_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+    }
+    out
+}
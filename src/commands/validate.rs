@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+
+use crate::parser::{CollectOptions, ValidationReport, find_card_errors};
+use crate::utils::pluralize;
+
+/// Parses every card under `paths` and lists each malformed one, failing if
+/// there are any. The database is never opened, so this is safe to run in CI.
+pub fn run(paths: Vec<PathBuf>, lossy: bool) -> Result<()> {
+    let options = CollectOptions {
+        lossy,
+        ..Default::default()
+    };
+    let report = find_card_errors(paths, &options)?;
+    print!("{}", format_report(&report));
+    if !report.errors.is_empty() {
+        bail!(
+            "validate found {}",
+            pluralize("problem", report.errors.len())
+        );
+    }
+    Ok(())
+}
+
+fn format_report(report: &ValidationReport) -> String {
+    let mut out: String = report
+        .errors
+        .iter()
+        .map(|error| format!("error: {error}\n"))
+        .chain(
+            report
+                .warnings
+                .iter()
+                .map(|warning| format!("warning: {warning}\n")),
+        )
+        .collect();
+    out.push_str(&format!(
+        "Checked {} in {}.\n",
+        pluralize("card", report.cards),
+        pluralize("file", report.files)
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn malformed_cards_fail_and_are_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("good.md"), "Q: one?\nA: yes\n").unwrap();
+        assert!(run(vec![dir.path().to_path_buf()], false).is_ok());
+
+        let bad = dir.path().join("bad.md");
+        fs::write(
+            &bad,
+            "Q: fine?\nA: yes\n---\nQ: no answer\n---\nC: empty []\n---\nQ: fine?\nA: yes\n",
+        )
+        .unwrap();
+        let report =
+            find_card_errors(vec![dir.path().to_path_buf()], &CollectOptions::default()).unwrap();
+        assert_eq!((report.files, report.cards), (2, 3));
+        assert_eq!(
+            report.errors,
+            [
+                format!(
+                    "{} (lines 4-4): found a question with no answer; add an `A:` section",
+                    bad.display()
+                ),
+                format!(
+                    "{} (lines 6-6): found a cloze with empty brackets; put the hidden text inside `[...]`",
+                    bad.display()
+                ),
+            ]
+        );
+        // A repeated card is only a warning.
+        assert_eq!(
+            report.warnings,
+            [format!(
+                "{} (lines 1-2) and lines 8-9 contain the same card; only one will be scheduled",
+                bad.display()
+            )]
+        );
+        assert!(
+            format_report(&report)
+                .ends_with("only one will be scheduled\nChecked 3 cards in 2 files.\n")
+        );
+
+        let err = run(vec![dir.path().to_path_buf()], false).unwrap_err();
+        assert_eq!(err.to_string(), "validate found 2 problems");
+    }
+}
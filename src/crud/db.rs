@@ -3,38 +3,135 @@ use directories::ProjectDirs;
 use sqlx::SqlitePool;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::anyhow;
+use anyhow::{Context, anyhow};
+
+use crate::scheduler::SchedulerKind;
+
+/// Overrides the directory holding `cards.db`.
+pub const HOME_ENV: &str = "REPEATER_HOME";
+/// File name of the card database inside the data directory.
+pub const DB_FILE_NAME: &str = "cards.db";
 
 #[derive(Clone)]
 pub struct DB {
     pub(super) pool: SqlitePool,
+    pub(super) scheduler: SchedulerKind,
 }
 
 impl DB {
     pub async fn new() -> Result<Self> {
-        let proj_dirs = ProjectDirs::from("", "", "repeater")
-            .ok_or_else(|| anyhow!("Could not determine project directory"))?;
-
-        let data_dir = proj_dirs.data_dir();
-        std::fs::create_dir_all(data_dir)?;
+        let data_dir = Self::data_dir()?;
+        std::fs::create_dir_all(&data_dir)?;
 
-        let db_path = data_dir.join("cards.db");
+        let db_path = data_dir.join(DB_FILE_NAME);
 
         let options =
             SqliteConnectOptions::from_str(&db_path.to_string_lossy())?.create_if_missing(true);
 
         Self::connect(options).await
     }
-    async fn connect(options: SqliteConnectOptions) -> Result<Self> {
+
+    /// Directory holding the card database: `REPEATER_HOME` if set, otherwise
+    /// the OS data directory. Not created by this call.
+    pub fn data_dir() -> Result<PathBuf> {
+        Self::data_dir_with(std::env::var_os(HOME_ENV))
+    }
+
+    pub(crate) fn data_dir_with(home: Option<OsString>) -> Result<PathBuf> {
+        if let Some(home) = home.filter(|home| !home.is_empty()) {
+            return Ok(PathBuf::from(home));
+        }
+        let proj_dirs = ProjectDirs::from("", "", "repeater")
+            .ok_or_else(|| anyhow!("Could not determine project directory"))?;
+        Ok(proj_dirs.data_dir().to_path_buf())
+    }
+
+    /// Opens an existing database read-only, without running migrations.
+    pub async fn open_read_only(db_path: &Path) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(&db_path.to_string_lossy())?.read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        Ok(Self {
+            pool,
+            scheduler: SchedulerKind::from_env(),
+        })
+    }
+
+    /// Uses `scheduler` instead of the one picked by `REPEATER_SCHEDULER`.
+    pub fn with_scheduler(mut self, scheduler: SchedulerKind) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Waits for in-flight queries and closes every connection. Safe to call
+    /// more than once.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.pool.is_closed()
+    }
+
+    /// Number of bundled migrations that have not been applied to this database,
+    /// all of them for one that has never been migrated. Fails if the
+    /// migrations table is there but can't be read.
+    pub async fn pending_migrations(&self) -> Result<usize> {
+        let applied: Vec<i64> = if self.has_migrations_table().await? {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+                .fetch_all(&self.pool)
+                .await
+                .context("Failed to read the applied migrations")?
+        } else {
+            Vec::new()
+        };
+        Ok(sqlx::migrate!("./migrations")
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .count())
+    }
+
+    /// Version of the newest migration applied to this database, 0 for one
+    /// that has never been migrated. Fails if the migrations table is there
+    /// but can't be read.
+    pub async fn schema_version(&self) -> Result<i64> {
+        if !self.has_migrations_table().await? {
+            return Ok(0);
+        }
+        let version: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+                .fetch_one(&self.pool)
+                .await
+                .context("Failed to read the applied migrations")?;
+        Ok(version.unwrap_or(0))
+    }
+
+    async fn has_migrations_table(&self) -> Result<bool> {
+        let exists = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    pub(crate) async fn connect(options: SqliteConnectOptions) -> Result<Self> {
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
             .connect_with(options)
             .await?;
 
         sqlx::migrate!("./migrations").run(&pool).await?;
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            scheduler: SchedulerKind::from_env(),
+        })
     }
 }
 
@@ -62,4 +159,74 @@ mod tests {
 
         DB::connect(options).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn opening_an_old_schema_migrates_it_and_keeps_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let options =
+            SqliteConnectOptions::from_str(&dir.path().join(DB_FILE_NAME).to_string_lossy())
+                .unwrap()
+                .create_if_missing(true);
+
+        // A database from before lapses were tracked: only the first three
+        // migrations applied, with one reviewed card in it.
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options.clone())
+            .await
+            .unwrap();
+        let mut old = sqlx::migrate!("./migrations");
+        old.migrations = old.migrations[..3].to_vec().into();
+        old.run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO cards (card_hash, added_at, last_reviewed_at, stability, difficulty, \
+             interval_raw, interval_days, due_date, review_count) \
+             VALUES ('abc', '2024-01-01T00:00:00Z', '2024-01-02T00:00:00Z', 3.0, 5.0, 3.0, 3, \
+             '2024-01-05T00:00:00Z', 2)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let db = DB::connect(options).await.unwrap();
+        let latest = sqlx::migrate!("./migrations")
+            .iter()
+            .map(|migration| migration.version)
+            .max()
+            .unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), latest);
+        assert_eq!(db.pending_migrations().await.unwrap(), 0);
+
+        let (review_count, lapses): (i64, i64) =
+            sqlx::query_as("SELECT review_count, lapses FROM cards WHERE card_hash = 'abc'")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!((review_count, lapses), (2, 0));
+    }
+
+    #[tokio::test]
+    async fn schema_version_fails_on_an_unreadable_migrations_table() {
+        let db = DB::new_in_memory().await.unwrap();
+        assert!(db.schema_version().await.unwrap() > 0);
+
+        sqlx::raw_sql("DROP TABLE _sqlx_migrations; CREATE TABLE _sqlx_migrations (junk TEXT);")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(db.schema_version().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn pending_migrations_fails_on_an_unreadable_migrations_table() {
+        let db = DB::new_in_memory().await.unwrap();
+        assert_eq!(db.pending_migrations().await.unwrap(), 0);
+
+        sqlx::raw_sql("DROP TABLE _sqlx_migrations; CREATE TABLE _sqlx_migrations (junk TEXT);")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let err = db.pending_migrations().await.unwrap_err();
+        assert!(err.to_string().contains("applied migrations"));
+    }
 }
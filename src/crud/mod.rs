@@ -1,5 +1,7 @@
 pub mod cards;
 pub mod db;
+pub mod positions;
+pub mod sessions;
 pub mod stats;
 pub mod version;
 
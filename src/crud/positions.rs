@@ -0,0 +1,229 @@
+use super::DB;
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+
+use crate::card::Card;
+use crate::fsrs::Performance;
+
+/// A card whose text changed in place: it sits where a reviewed card used to,
+/// and that card's hash no longer appears anywhere.
+#[derive(Clone, Debug)]
+pub struct EditedCard {
+    pub card: Card,
+    pub old_hash: String,
+}
+
+impl DB {
+    /// Cards that look like edits of a reviewed card recorded by
+    /// `record_positions`: same file and starting line, a different hash, no
+    /// history of their own, and the old hash is gone from `card_hashes`.
+    /// An old card also recorded in a file outside `card_hashes` may still be
+    /// there, so it is never treated as edited.
+    pub async fn edited_cards(
+        &self,
+        card_hashes: &HashMap<String, Card>,
+    ) -> Result<Vec<EditedCard>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                card_positions.file_path,
+                card_positions.start_line,
+                card_positions.card_hash
+            FROM card_positions
+            JOIN cards ON cards.card_hash = card_positions.card_hash
+            WHERE cards.review_count > 0
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let scanned: HashSet<String> = card_hashes
+            .values()
+            .map(|card| position_key(card).0)
+            .collect();
+        let seen_elsewhere: HashSet<&str> = rows
+            .iter()
+            .filter(|row| !scanned.contains(&row.file_path))
+            .map(|row| row.card_hash.as_str())
+            .collect();
+        let previous: HashMap<(String, i64), String> = rows
+            .iter()
+            .filter(|row| !seen_elsewhere.contains(row.card_hash.as_str()))
+            .map(|row| {
+                (
+                    (row.file_path.clone(), row.start_line),
+                    row.card_hash.clone(),
+                )
+            })
+            .collect();
+
+        let mut candidates: Vec<EditedCard> = card_hashes
+            .values()
+            .filter_map(|card| {
+                let key = position_key(card);
+                let old_hash = previous.get(&key)?;
+                (*old_hash != card.card_hash && !card_hashes.contains_key(old_hash)).then(|| {
+                    EditedCard {
+                        card: card.clone(),
+                        old_hash: old_hash.clone(),
+                    }
+                })
+            })
+            .collect();
+        let cards: Vec<Card> = candidates.iter().map(|edit| edit.card.clone()).collect();
+        let performances = self.get_performances(&cards).await?;
+        candidates.retain(|edit| {
+            matches!(
+                performances.get(&edit.card.card_hash),
+                Some(Performance::New)
+            )
+        });
+        candidates.sort_by(|a, b| a.card.locator().cmp(&b.card.locator()));
+        Ok(candidates)
+    }
+
+    /// Moves the scheduling state and review log of `old_hash` to `new_hash`,
+    /// replacing the new card's empty row.
+    pub async fn migrate_history(&self, old_hash: &str, new_hash: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM cards WHERE card_hash = ?", new_hash)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "UPDATE cards SET card_hash = ? WHERE card_hash = ?",
+            new_hash,
+            old_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE review_log SET card_hash = ? WHERE card_hash = ?",
+            new_hash,
+            old_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replaces the stored positions for every file in `cards`.
+    pub async fn record_positions(&self, cards: &HashMap<String, Card>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let files: HashSet<String> = cards.values().map(|card| position_key(card).0).collect();
+        for file_path in &files {
+            sqlx::query!("DELETE FROM card_positions WHERE file_path = ?", file_path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for card in cards.values() {
+            let (file_path, start_line) = position_key(card);
+            sqlx::query!(
+                "INSERT OR REPLACE INTO card_positions (file_path, start_line, card_hash) VALUES (?, ?, ?)",
+                file_path,
+                start_line,
+                card.card_hash
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+fn position_key(card: &Card) -> (String, i64) {
+    (
+        card.file_path.to_string_lossy().into_owned(),
+        card.file_card_range.0 as i64,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::fsrs::ReviewStatus;
+    use crate::parser::content_to_card;
+
+    fn by_hash(cards: &[&Card]) -> HashMap<String, Card> {
+        cards
+            .iter()
+            .map(|card| (card.card_hash.clone(), (*card).clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn edited_cards_are_matched_by_position_and_keep_their_history() {
+        let db = DB::new_in_memory().await.unwrap();
+        let path = PathBuf::from("deck.md");
+        let original = content_to_card(&path, "Q: Capital of Peru?\nA: Lima", 0, 2).unwrap();
+        let neighbour = content_to_card(&path, "Q: Capital of Chile?\nA: Santiago", 3, 5).unwrap();
+        for card in [&original, &neighbour] {
+            db.add_card(card).await.unwrap();
+            db.review(card, ReviewStatus::Pass).await.unwrap();
+        }
+        db.record_positions(&by_hash(&[&original, &neighbour]))
+            .await
+            .unwrap();
+        let before = db.get_card_performance(&original).await.unwrap();
+
+        // The first card is reworded in place; the second is untouched.
+        let edited =
+            content_to_card(&path, "Q: What is the capital of Peru?\nA: Lima", 0, 2).unwrap();
+        db.add_card(&edited).await.unwrap();
+        let current = by_hash(&[&edited, &neighbour]);
+        let edits = db.edited_cards(&current).await.unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].old_hash, original.card_hash);
+        assert_eq!(edits[0].card.card_hash, edited.card_hash);
+
+        db.migrate_history(&edits[0].old_hash, &edited.card_hash)
+            .await
+            .unwrap();
+        let after = db.get_card_performance(&edited).await.unwrap();
+        let (Performance::Reviewed(before), Performance::Reviewed(after)) = (before, after) else {
+            panic!("history should carry over");
+        };
+        assert_eq!(before.due_date, after.due_date);
+        assert_eq!(before.stability, after.stability);
+        assert!(!db.card_exists(&original).await.unwrap());
+        assert!(db.edited_cards(&current).await.unwrap().is_empty());
+
+        // A card that moved rather than changed is not an edit.
+        let moved = content_to_card(&path, "Q: Capital of Chile?\nA: Santiago", 0, 2).unwrap();
+        db.record_positions(&current).await.unwrap();
+        assert!(
+            db.edited_cards(&by_hash(&[&moved, &edited]))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn a_card_still_recorded_outside_the_scan_is_not_an_edit() {
+        let db = DB::new_in_memory().await.unwrap();
+        let deck = PathBuf::from("deck.md");
+        let original = content_to_card(&deck, "Q: Capital of Peru?\nA: Lima", 0, 2).unwrap();
+        let mut copy = original.clone();
+        copy.file_path = PathBuf::from("other.md");
+        db.add_card(&original).await.unwrap();
+        db.review(&original, ReviewStatus::Pass).await.unwrap();
+        db.record_positions(&by_hash(&[&original])).await.unwrap();
+        db.record_positions(&by_hash(&[&copy])).await.unwrap();
+
+        // Only deck.md is scanned; the card still lives in other.md.
+        let edited =
+            content_to_card(&deck, "Q: What is the capital of Peru?\nA: Lima", 0, 2).unwrap();
+        db.add_card(&edited).await.unwrap();
+        assert!(
+            db.edited_cards(&by_hash(&[&edited]))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use super::DB;
+
+use anyhow::Result;
+
+/// Totals for one finished drill session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub ended_at: chrono::DateTime<chrono::Utc>,
+    pub cards_reviewed: i64,
+    pub passed: i64,
+    pub failed: i64,
+}
+
+impl SessionRecord {
+    /// Share of reviews graded pass, or `None` when nothing was graded.
+    pub fn pass_rate(&self) -> Option<f64> {
+        (self.cards_reviewed > 0).then(|| self.passed as f64 / self.cards_reviewed as f64)
+    }
+}
+
+impl DB {
+    pub async fn record_session(&self, session: &SessionRecord) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (started_at, ended_at, cards_reviewed, passed, failed)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            session.started_at,
+            session.ended_at,
+            session.cards_reviewed,
+            session.passed,
+            session.failed,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The most recently finished session, if any were recorded.
+    pub async fn last_session(&self) -> Result<Option<SessionRecord>> {
+        let session = sqlx::query_as!(
+            SessionRecord,
+            r#"
+            SELECT
+                started_at as "started_at: chrono::DateTime<chrono::Utc>",
+                ended_at as "ended_at: chrono::DateTime<chrono::Utc>",
+                cards_reviewed,
+                passed,
+                failed
+            FROM sessions
+            ORDER BY ended_at DESC, id DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_sessions_and_reads_back_the_latest() {
+        let db = DB::new_in_memory().await.unwrap();
+        assert_eq!(db.last_session().await.unwrap(), None);
+
+        let started_at = chrono::Utc::now() - chrono::Duration::hours(2);
+        let earlier = SessionRecord {
+            started_at,
+            ended_at: started_at + chrono::Duration::minutes(10),
+            cards_reviewed: 5,
+            passed: 5,
+            failed: 0,
+        };
+        let latest = SessionRecord {
+            started_at: started_at + chrono::Duration::hours(1),
+            ended_at: started_at + chrono::Duration::minutes(75),
+            cards_reviewed: 34,
+            passed: 30,
+            failed: 4,
+        };
+        db.record_session(&latest).await.unwrap();
+        db.record_session(&earlier).await.unwrap();
+
+        let read_back = db.last_session().await.unwrap().unwrap();
+        assert_eq!(read_back, latest);
+        assert_eq!(
+            format!("{:.0}%", read_back.pass_rate().unwrap() * 100.0),
+            "88%"
+        );
+    }
+}
@@ -1,12 +1,12 @@
 use super::DB;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::card::Card;
+use crate::card::{Card, CardContent, reverse_cloze_hash};
 
 use futures::TryStreamExt;
 
-use crate::stats::CardStats;
+use crate::stats::{CardStats, DayZone, GhostCard};
 use anyhow::Result;
 
 pub struct CardStatsRow {
@@ -20,9 +20,14 @@ pub struct CardStatsRow {
 }
 
 impl DB {
-    pub async fn collection_stats(&self, card_hashes: &HashMap<String, Card>) -> Result<CardStats> {
+    pub async fn collection_stats(
+        &self,
+        card_hashes: &HashMap<String, Card>,
+        day_zone: DayZone,
+    ) -> Result<CardStats> {
         let mut stats = CardStats {
             num_cards: card_hashes.len() as i64,
+            day_zone,
             ..Default::default()
         };
 
@@ -42,15 +47,194 @@ impl DB {
         )
         .fetch(&self.pool);
 
+        // Rows created by `drill --reverse-clozes`, and for the extra deletions
+        // of a multi-cloze card, belong to a live cloze card.
+        let derived_hashes: HashSet<String> = card_hashes
+            .values()
+            .filter(|card| matches!(card.content, CardContent::Cloze { .. }))
+            .flat_map(|card| {
+                card.drill_items()
+                    .into_iter()
+                    .map(|item| item.card_hash)
+                    .chain([reverse_cloze_hash(&card.card_hash)])
+            })
+            .filter(|hash| !card_hashes.contains_key(hash))
+            .collect();
+
         while let Some(row) = rows.try_next().await? {
             stats.total_cards_in_db += 1;
             let card = match card_hashes.get(&row.card_hash) {
                 Some(card) => card,
-                None => continue,
+                None if derived_hashes.contains(&row.card_hash) => continue,
+                None => {
+                    stats.ghosts.push(GhostCard {
+                        card_hash: row.card_hash,
+                        last_reviewed_at: row.last_reviewed_at,
+                    });
+                    continue;
+                }
             };
             stats.update(card, &row);
         }
+        stats.ghosts.sort_by(|a, b| a.card_hash.cmp(&b.card_hash));
 
         Ok(stats)
     }
+
+    /// Number of cards first indexed during the current day in `day_zone`.
+    pub async fn added_today(&self, day_zone: DayZone) -> Result<i64> {
+        let (start, end) = day_zone.day_bounds(chrono::Utc::now());
+        let count: i64 = sqlx::query_scalar!(
+            r#"SELECT COUNT(1) as "count!: i64" FROM cards WHERE added_at >= ? AND added_at < ?"#,
+            start,
+            end
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+
+    /// When each review logged at or after `since` happened.
+    pub async fn review_times_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<chrono::DateTime<chrono::Utc>>> {
+        let times = sqlx::query_scalar!(
+            r#"SELECT reviewed_at as "reviewed_at!: chrono::DateTime<chrono::Utc>" FROM review_log WHERE reviewed_at >= ?"#,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(times)
+    }
+
+    /// Number of `card_hashes` reviewed as new cards during the current day in
+    /// `day_zone`, i.e. introduced today. A card reset and learned again today
+    /// counts; reviews logged before the pre-review state was recorded don't.
+    pub async fn introduced_today(
+        &self,
+        card_hashes: &HashMap<String, Card>,
+        day_zone: DayZone,
+    ) -> Result<usize> {
+        let (start, end) = day_zone.day_bounds(chrono::Utc::now());
+        let introduced: Vec<String> = sqlx::query_scalar!(
+            r#"SELECT DISTINCT card_hash as "card_hash!" FROM review_log
+            WHERE prev_review_count = 0 AND reviewed_at >= ? AND reviewed_at < ?"#,
+            start,
+            end
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(introduced
+            .iter()
+            .filter(|hash| card_hashes.contains_key(*hash))
+            .count())
+    }
+
+    /// Number of reviews logged during the current day in `day_zone`.
+    pub async fn reviewed_today(&self, day_zone: DayZone) -> Result<i64> {
+        let (start, end) = day_zone.day_bounds(chrono::Utc::now());
+        let count: i64 = sqlx::query_scalar!(
+            r#"SELECT COUNT(1) as "count!: i64" FROM review_log WHERE reviewed_at >= ? AND reviewed_at < ?"#,
+            start,
+            end
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use crate::fsrs::ReviewStatus;
+    use crate::parser::content_to_card;
+    use crate::stats::DayZone;
+
+    use super::DB;
+
+    #[tokio::test]
+    async fn counts_cards_added_and_reviewed_today() {
+        let db = DB::new_in_memory().await.unwrap();
+        let yesterday = (chrono::Local::now() - chrono::Duration::days(1)).to_utc();
+        sqlx::query(
+            "INSERT INTO cards (card_hash, added_at, interval_days, review_count) VALUES (?, ?, 0, 0)",
+        )
+        .bind("yesterday")
+        .bind(yesterday)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO review_log (card_hash, reviewed_at, review_status) VALUES (?, ?, ?)",
+        )
+        .bind("yesterday")
+        .bind(yesterday)
+        .bind("Pass")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let card = content_to_card(&PathBuf::from("test.md"), "Q: today?\nA: yes", 0, 2).unwrap();
+        db.add_card(&card).await.unwrap();
+        db.update_card_performance(&card, ReviewStatus::Pass, None)
+            .await
+            .unwrap();
+
+        assert_eq!(db.added_today(DayZone::Local).await.unwrap(), 1);
+        assert_eq!(db.reviewed_today(DayZone::Local).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_db_rows_without_a_card_as_ghosts() {
+        let db = DB::new_in_memory().await.unwrap();
+        let kept = content_to_card(&PathBuf::from("deck.md"), "Q: kept?\nA: yes", 0, 2).unwrap();
+        let deleted =
+            content_to_card(&PathBuf::from("deck.md"), "Q: deleted?\nA: yes", 3, 5).unwrap();
+        db.add_card(&kept).await.unwrap();
+        db.add_card(&deleted).await.unwrap();
+        db.update_card_performance(&deleted, ReviewStatus::Pass, None)
+            .await
+            .unwrap();
+
+        let card_hashes = HashMap::from([(kept.card_hash.clone(), kept)]);
+        let stats = db
+            .collection_stats(&card_hashes, DayZone::Local)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.total_cards_in_db, 2);
+        assert_eq!(stats.ghosts.len(), 1);
+        assert_eq!(stats.ghosts[0].card_hash, deleted.card_hash);
+        assert!(stats.ghosts[0].last_reviewed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn rows_derived_from_a_live_cloze_are_not_ghosts() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cloze =
+            content_to_card(&PathBuf::from("deck.md"), "C: [Paris] is in [France]", 0, 1).unwrap();
+        db.add_card(&cloze).await.unwrap();
+        // The reverse review and the second deletion have rows of their own.
+        db.add_card(&cloze.reversed_cloze().unwrap()).await.unwrap();
+        db.add_card(&cloze.drill_items()[1]).await.unwrap();
+
+        let card_hashes = HashMap::from([(cloze.card_hash.clone(), cloze.clone())]);
+        let stats = db
+            .collection_stats(&card_hashes, DayZone::Local)
+            .await
+            .unwrap();
+        assert_eq!(stats.total_cards_in_db, 3);
+        assert!(stats.ghosts.is_empty());
+
+        // Once the cloze is gone, so is every row derived from it.
+        let stats = db
+            .collection_stats(&HashMap::new(), DayZone::Local)
+            .await
+            .unwrap();
+        assert_eq!(stats.ghosts.len(), 3);
+    }
 }
@@ -7,21 +7,35 @@ pub const WEIGHTS: [f64; 19] = [
 
 const F: f64 = 19.0 / 81.0;
 const C: f64 = -0.5;
-const TARGET_RECALL: f64 = 0.9;
+/// Recall probability FSRS schedules reviews for.
+pub const TARGET_RECALL: f64 = 0.9;
 const MIN_INTERVAL: f64 = 1.0;
 const MAX_INTERVAL: f64 = 256.0;
 pub const LEARN_AHEAD_THRESHOLD_MINS: Duration = Duration::minutes(20);
+/// Reviews covered by the short learning steps in `early_interval_cap`.
+pub const LEARNING_STEPS: usize = 3;
 
+/// `Easy` skips the learning steps and goes straight to the FSRS interval.
+/// `Hard` always gets a step of its own between `Fail` and `Pass`.
 fn early_interval_cap(review_count: usize, review_status: ReviewStatus) -> Option<Duration> {
+    if review_status == ReviewStatus::Easy {
+        return None;
+    }
     match review_count {
-        0 => Some(Duration::minutes(1)),
+        0 => match review_status {
+            ReviewStatus::Pass => Some(Duration::minutes(10)),
+            ReviewStatus::Hard => Some(Duration::minutes(5)),
+            _ => Some(Duration::minutes(1)),
+        },
         1 => match review_status {
             ReviewStatus::Pass => Some(Duration::minutes(10)),
-            ReviewStatus::Fail => Some(Duration::minutes(1)),
+            ReviewStatus::Hard => Some(Duration::minutes(5)),
+            _ => Some(Duration::minutes(1)),
         },
         2 => match review_status {
             ReviewStatus::Pass => Some(Duration::days(1)),
-            ReviewStatus::Fail => Some(Duration::minutes(10)),
+            ReviewStatus::Hard => Some(Duration::hours(12)),
+            _ => Some(Duration::minutes(10)),
         },
         _ => None,
     }
@@ -30,6 +44,19 @@ pub fn calculate_recall(interval: f64, stability: f64) -> f64 {
     (1.0 + F * (interval / stability)).powf(C)
 }
 
+/// Probability of recalling a reviewed card at `now`, from its stability and the
+/// time since its last review. New cards have no stability and return `None`.
+pub fn retrievability(perf: &Performance, now: chrono::DateTime<chrono::Utc>) -> Option<f64> {
+    let Performance::Reviewed(reviewed) = perf else {
+        return None;
+    };
+    let elapsed_days = now
+        .signed_duration_since(reviewed.last_reviewed_at)
+        .num_seconds() as f64
+        / 86_400.0;
+    Some(calculate_recall(elapsed_days.max(0.0), reviewed.stability))
+}
+
 pub fn calulate_interval(recall: f64, stability: f64) -> f64 {
     (stability / F) * (recall.powf(1.0 / C) - 1.0)
 }
@@ -37,7 +64,9 @@ pub fn calulate_interval(recall: f64, stability: f64) -> f64 {
 pub fn initial_stability(review_status: ReviewStatus) -> f64 {
     match review_status {
         ReviewStatus::Fail => WEIGHTS[0],
+        ReviewStatus::Hard => WEIGHTS[1],
         ReviewStatus::Pass => WEIGHTS[2],
+        ReviewStatus::Easy => WEIGHTS[3],
     }
 }
 
@@ -58,8 +87,16 @@ fn calculate_stability(
     let t_d = 11.0 - difficulty;
     let t_s = stability.powf(-WEIGHTS[9]);
     let t_r = f64::exp(WEIGHTS[10] * (1.0 - recall)) - 1.0;
-    let h = 1.0;
-    let b = 1.0;
+    let h = if review_status == ReviewStatus::Hard {
+        WEIGHTS[15]
+    } else {
+        1.0
+    };
+    let b = if review_status == ReviewStatus::Easy {
+        WEIGHTS[16]
+    } else {
+        1.0
+    };
     let c = f64::exp(WEIGHTS[8]);
     let alpha = 1.0 + t_d * t_s * t_r * h * b * c;
     stability * alpha
@@ -90,10 +127,14 @@ fn delta_d(review_status: ReviewStatus) -> f64 {
     -WEIGHTS[6] * (g - 3.0)
 }
 
-#[derive(Copy, Clone, PartialEq)]
+/// The grade given to a review. `Pass` is FSRS's "Good"; `Hard` and `Easy`
+/// are passes that shrink or stretch the next interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ReviewStatus {
     Pass,
     Fail,
+    Hard,
+    Easy,
 }
 
 impl ReviewStatus {
@@ -101,12 +142,32 @@ impl ReviewStatus {
         match self {
             ReviewStatus::Pass => "Pass",
             ReviewStatus::Fail => "Fail",
+            ReviewStatus::Hard => "Hard",
+            ReviewStatus::Easy => "Easy",
         }
     }
     pub fn score(&self) -> usize {
         match self {
-            ReviewStatus::Pass => 3,
             ReviewStatus::Fail => 1,
+            ReviewStatus::Hard => 2,
+            ReviewStatus::Pass => 3,
+            ReviewStatus::Easy => 4,
+        }
+    }
+}
+
+impl std::str::FromStr for ReviewStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "pass" => Ok(ReviewStatus::Pass),
+            "fail" => Ok(ReviewStatus::Fail),
+            "hard" => Ok(ReviewStatus::Hard),
+            "easy" => Ok(ReviewStatus::Easy),
+            other => Err(format!(
+                "expected `fail`, `hard`, `pass` or `easy`, got `{other}`"
+            )),
         }
     }
 }
@@ -129,10 +190,31 @@ pub enum Performance {
     Reviewed(ReviewedPerformance),
 }
 
+/// What a failed review means for a card, based on its state before the review.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailKind {
+    /// The card is new or still in its learning steps.
+    Learning,
+    /// A graduated card was forgotten.
+    Lapse,
+}
+
+pub fn classify_fail(perf: &Performance) -> FailKind {
+    match perf {
+        Performance::Reviewed(reviewed) if reviewed.review_count >= LEARNING_STEPS => {
+            FailKind::Lapse
+        }
+        _ => FailKind::Learning,
+    }
+}
+
+/// The schedule after a review, with intervals aimed at `target_recall`
+/// (normally [`TARGET_RECALL`]).
 pub fn update_performance(
     perf: Performance,
     review_status: ReviewStatus,
     reviewed_at: chrono::DateTime<chrono::Utc>,
+    target_recall: f64,
 ) -> ReviewedPerformance {
     let (stability, difficulty, review_count): (f64, f64, usize) = match perf {
         Performance::New => (
@@ -157,7 +239,7 @@ pub fn update_performance(
             (stability, difficulty, review_count)
         }
     };
-    let interval_raw: f64 = calulate_interval(TARGET_RECALL, stability);
+    let interval_raw: f64 = calulate_interval(target_recall, stability);
     let interval_rounded: f64 = interval_raw.round();
     let interval_clamped: f64 = interval_rounded.clamp(MIN_INTERVAL, MAX_INTERVAL);
     let fsrs_duration = Duration::days(interval_clamped as i64);
@@ -186,8 +268,8 @@ pub fn update_performance(
 mod tests {
 
     use super::{
-        MAX_INTERVAL, MIN_INTERVAL, Performance, ReviewStatus, ReviewedPerformance,
-        update_performance,
+        FailKind, MAX_INTERVAL, MIN_INTERVAL, Performance, ReviewStatus, ReviewedPerformance,
+        TARGET_RECALL, classify_fail, retrievability, update_performance,
     };
 
     use chrono::Duration;
@@ -199,7 +281,12 @@ mod tests {
     #[test]
     fn test_update_new_card() {
         let reviewed_at = chrono::Utc::now();
-        let result = update_performance(Performance::New, ReviewStatus::Pass, reviewed_at);
+        let result = update_performance(
+            Performance::New,
+            ReviewStatus::Pass,
+            reviewed_at,
+            TARGET_RECALL,
+        );
         let ReviewedPerformance {
             last_reviewed_at,
             stability,
@@ -212,7 +299,7 @@ mod tests {
         assert_eq!(last_reviewed_at, reviewed_at);
         assert!(approx_eq(stability, 3.17));
         assert!(approx_eq(difficulty, 5.28));
-        assert!(approx_eq(interval_raw, 0.0006944444444444445));
+        assert!(approx_eq(interval_raw, 0.006944444444444444));
         assert_eq!(interval_days, 1);
         assert_eq!(review_count, 1);
     }
@@ -236,6 +323,7 @@ mod tests {
             Performance::Reviewed(initial_perf),
             ReviewStatus::Pass,
             reviewed_at,
+            TARGET_RECALL,
         );
         let ReviewedPerformance {
             last_reviewed_at,
@@ -254,10 +342,49 @@ mod tests {
         assert_eq!(review_count, 2);
     }
 
+    #[test]
+    fn retrievability_decays_with_time_since_review() {
+        let now = chrono::Utc::now();
+        assert_eq!(retrievability(&Performance::New, now), None);
+
+        let reviewed = Performance::Reviewed(update_performance(
+            Performance::New,
+            ReviewStatus::Pass,
+            now,
+            TARGET_RECALL,
+        ));
+        let fresh = retrievability(&reviewed, now).unwrap();
+        let later = retrievability(&reviewed, now + chrono::Duration::days(5)).unwrap();
+        let much_later = retrievability(&reviewed, now + chrono::Duration::days(50)).unwrap();
+        assert!((fresh - 1.0).abs() < 1e-9);
+        assert!(fresh > later && later > much_later);
+        assert!(much_later > 0.0);
+    }
+
+    #[test]
+    fn classify_fail_separates_learning_from_lapses() {
+        let now = chrono::Utc::now();
+        assert_eq!(classify_fail(&Performance::New), FailKind::Learning);
+
+        let mut perf = update_performance(Performance::New, ReviewStatus::Pass, now, TARGET_RECALL);
+        assert_eq!(
+            classify_fail(&Performance::Reviewed(perf)),
+            FailKind::Learning
+        );
+
+        perf.review_count = 3;
+        assert_eq!(classify_fail(&Performance::Reviewed(perf)), FailKind::Lapse);
+    }
+
     #[test]
     fn test_reviews() {
         let mut reviewed_at = chrono::Utc::now();
-        let mut performance = update_performance(Performance::New, ReviewStatus::Pass, reviewed_at);
+        let mut performance = update_performance(
+            Performance::New,
+            ReviewStatus::Pass,
+            reviewed_at,
+            TARGET_RECALL,
+        );
         for _ in 0..100 {
             let interval_raw = performance.interval_raw;
             let interval_rounded: f64 = interval_raw.round();
@@ -269,6 +396,7 @@ mod tests {
                 Performance::Reviewed(performance),
                 ReviewStatus::Pass,
                 reviewed_at,
+                TARGET_RECALL,
             );
         }
         assert_eq!(performance.review_count, 101);
@@ -287,6 +415,7 @@ mod tests {
                 Performance::Reviewed(performance),
                 ReviewStatus::Fail,
                 reviewed_at,
+                TARGET_RECALL,
             );
         }
         assert_eq!(performance.review_count, 201);
@@ -294,4 +423,79 @@ mod tests {
         assert!(approx_eq(performance.difficulty, 9.9337));
         assert!(approx_eq(performance.stability, 0.148424));
     }
+
+    #[test]
+    fn reviewing_ahead_of_schedule_adds_a_shorter_interval() {
+        let last_reviewed_at = chrono::Utc::now();
+        let mature = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at,
+            stability: 30.0,
+            difficulty: 5.0,
+            interval_raw: 30.0,
+            interval_days: 30,
+            due_date: last_reviewed_at + Duration::days(30),
+            review_count: 5,
+        });
+        let interval_after = |days| {
+            let reviewed_at = last_reviewed_at + Duration::days(days);
+            update_performance(mature, ReviewStatus::Pass, reviewed_at, TARGET_RECALL).interval_raw
+        };
+        let early = interval_after(28);
+        let on_time = interval_after(30);
+        // Recall is still high two days early, so the review proves less.
+        assert!(early < on_time, "early {early} < on time {on_time}");
+        assert!(interval_after(10) < early);
+    }
+
+    #[test]
+    fn grades_order_the_next_interval_fail_hard_pass_easy() {
+        let reviewed_at = chrono::Utc::now();
+        let mature = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: reviewed_at - Duration::days(30),
+            stability: 30.0,
+            difficulty: 5.0,
+            interval_raw: 30.0,
+            interval_days: 30,
+            due_date: reviewed_at,
+            review_count: 5,
+        });
+        let interval =
+            |status| update_performance(mature, status, reviewed_at, TARGET_RECALL).interval_raw;
+        let (fail, hard, pass, easy) = (
+            interval(ReviewStatus::Fail),
+            interval(ReviewStatus::Hard),
+            interval(ReviewStatus::Pass),
+            interval(ReviewStatus::Easy),
+        );
+        assert!(fail < hard, "fail {fail} < hard {hard}");
+        assert!(hard < pass, "hard {hard} < pass {pass}");
+        assert!(pass < easy, "pass {pass} < easy {easy}");
+
+        // Hard is a pass: it never counts as a lapse or drops below a day here.
+        assert!(hard >= MIN_INTERVAL);
+    }
+
+    #[test]
+    fn hard_sits_between_fail_and_pass_in_the_learning_steps() {
+        let reviewed_at = chrono::Utc::now();
+        let once = Performance::Reviewed(update_performance(
+            Performance::New,
+            ReviewStatus::Pass,
+            reviewed_at,
+            TARGET_RECALL,
+        ));
+        for (label, perf) in [("new", Performance::New), ("learning", once)] {
+            let step =
+                |status| update_performance(perf, status, reviewed_at, TARGET_RECALL).interval_raw;
+            let (fail, hard, pass, easy) = (
+                step(ReviewStatus::Fail),
+                step(ReviewStatus::Hard),
+                step(ReviewStatus::Pass),
+                step(ReviewStatus::Easy),
+            );
+            assert!(fail < hard, "{label}: fail {fail} < hard {hard}");
+            assert!(hard < pass, "{label}: hard {hard} < pass {pass}");
+            assert!(pass < easy, "{label}: pass {pass} < easy {easy}");
+        }
+    }
 }
@@ -5,17 +5,31 @@ use serde_json::Value;
 use sqlx::{Row, SqlitePool};
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use tempfile::NamedTempFile;
 use zip::ZipArchive;
 
 use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
 
 use crate::crud::DB;
 use crate::palette::Palette;
 use crate::parser::get_hash;
+use crate::utils::pluralize;
+
+/// Overrides where AnkiConnect is reached, e.g. `http://192.168.1.5:8765`.
+pub const ANKICONNECT_URL_ENV: &str = "REPEATER_ANKICONNECT_URL";
+const DEFAULT_ANKICONNECT_URL: &str = "http://127.0.0.1:8765";
+const ANKICONNECT_VERSION: u8 = 6;
+const ANKICONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// AnkiConnect imports a single deck, so it gets a fixed id.
+const ANKICONNECT_DECK_ID: i64 = 1;
+/// Cards converted between two `convert` progress events.
+const PROGRESS_EVERY: usize = 250;
 
 static TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<[^>]+>").unwrap());
+static TEMPLATE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{([^{}]*)\}\}").unwrap());
 static CLOZE_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?s)\{\{c\d+::(.*?)(?:::(.*?))?\}\}").unwrap());
 
@@ -31,6 +45,29 @@ enum ModelKind {
     Cloze,
 }
 
+#[derive(Clone)]
+struct ModelInfo {
+    kind: ModelKind,
+    /// Field names in note order, e.g. `Front`, `Back`, `Ref`.
+    field_names: Vec<String>,
+}
+
+/// A converted note: the markdown written to disk plus the field text it was
+/// built from. Identity comes from the field text so that tweaking the
+/// markdown layout never changes which notes count as duplicates.
+#[derive(Clone, Debug)]
+struct Entry {
+    markdown: String,
+    /// Question and answer, or the cloze text, trimmed and in display order.
+    content: Vec<String>,
+}
+
+impl Entry {
+    fn content_hash(&self) -> Option<String> {
+        get_hash(&self.content.join("\u{1f}"))
+    }
+}
+
 #[derive(Clone, Debug)]
 struct CardRecord {
     deck_id: i64,
@@ -39,20 +76,503 @@ struct CardRecord {
     fields: Vec<String>,
 }
 
-pub async fn run(_db: &DB, anki_path: &Path, export_path: &Path) -> Result<()> {
-    validate_path(anki_path)?;
-    let db_path = extract_collection_db(anki_path)?;
-    let db_url = format!("sqlite://{}", db_path.path().display());
-    let export_db = SqlitePool::connect(&db_url)
-        .await
-        .context("failed to connect to Anki database")?;
-    let (decks, models) = load_metadata(&export_db).await?;
-    let cards = load_cards(&export_db).await?;
-    let exports = build_exports(cards, &models);
-    write_exports(export_path, &decks, exports)?;
+/// Per-file tallies printed after each apkg is converted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct ImportCounts {
+    /// Cards read from the collection, before any filtering.
+    found: usize,
+    kept: usize,
+    duplicates: usize,
+    /// Cards whose note type is missing from the collection's models.
+    unknown_model: usize,
+    unexportable: usize,
+    /// Unrecognised `{{...}}` template tokens removed from field text.
+    unknown_templates: usize,
+}
+
+impl ImportCounts {
+    fn add(&mut self, other: ImportCounts) {
+        self.found += other.found;
+        self.kept += other.kept;
+        self.duplicates += other.duplicates;
+        self.unknown_model += other.unknown_model;
+        self.unexportable += other.unexportable;
+        self.unknown_templates += other.unknown_templates;
+    }
+
+    fn to_json(self) -> Value {
+        serde_json::json!({
+            "cards_found": self.found,
+            "cards_kept": self.kept,
+            "duplicates": self.duplicates,
+            "unknown_model": self.unknown_model,
+            "unexportable": self.unexportable,
+            "unknown_templates": self.unknown_templates,
+        })
+    }
+}
+
+/// One apkg's contribution, for `--report`.
+struct FileSummary {
+    path: PathBuf,
+    decks: usize,
+    models: usize,
+    counts: ImportCounts,
+}
+
+/// A markdown file written by the import.
+#[derive(Debug)]
+struct WrittenDeck {
+    path: PathBuf,
+    deck_id: i64,
+    deck: String,
+    cards: usize,
+}
+
+/// Name of the file in the export directory recording where each Markdown
+/// file came from.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Notes already converted, by content hash, so a note exported in several
+/// decks or apkgs is written once; the copies skipped are kept per deck id.
+#[derive(Debug, Default)]
+struct SeenNotes {
+    hashes: HashSet<String>,
+    duplicates: HashMap<i64, Vec<String>>,
+}
+
+/// Machine-readable progress for `import --progress`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    /// One JSON object per line on stderr: `phase`, `processed`, `total`.
+    Json,
+}
+
+/// Sends progress events to a wrapping script; does nothing by default.
+#[derive(Default)]
+struct Progress {
+    sink: Option<Box<dyn Write + Send>>,
+}
+
+impl Progress {
+    fn json(sink: Box<dyn Write + Send>) -> Self {
+        Self { sink: Some(sink) }
+    }
+
+    /// Writes one event line. Progress is best effort, so write errors are
+    /// ignored rather than failing the import.
+    fn report(&mut self, phase: &str, processed: usize, total: usize) {
+        if let Some(sink) = &mut self.sink {
+            let event = serde_json::json!({
+                "phase": phase,
+                "processed": processed,
+                "total": total,
+            });
+            let _ = writeln!(sink, "{event}");
+        }
+    }
+}
+
+/// Where `import` reads Anki notes from.
+pub enum ImportSource {
+    /// `.apkg` exports, or directories containing them.
+    Apkgs(Vec<PathBuf>),
+    /// One deck of a running Anki instance, through the AnkiConnect add-on.
+    AnkiConnect { deck: String },
+}
+
+/// Decks and cards gathered from every source before they are written.
+#[derive(Default)]
+struct ImportRun {
+    decks: HashMap<i64, DeckInfo>,
+    exports: HashMap<i64, Vec<String>>,
+    // Shared across files so a note exported in several apkgs is written once.
+    seen: SeenNotes,
+    total: ImportCounts,
+    summaries: Vec<FileSummary>,
+    progress: Progress,
+}
+
+impl ImportRun {
+    fn add_source(
+        &mut self,
+        path: PathBuf,
+        decks: HashMap<i64, DeckInfo>,
+        models: HashMap<i64, ModelInfo>,
+        cards: Vec<CardRecord>,
+        card_limit: Option<usize>,
+    ) {
+        let deck_count = decks.len();
+        let cards = merge_decks(&mut self.decks, decks, cards);
+        let remaining = card_limit.map(|limit| limit.saturating_sub(self.total.kept));
+        let (exports, counts) = build_exports(
+            cards,
+            &models,
+            remaining,
+            &mut self.seen,
+            &mut self.progress,
+        );
+        for (deck_id, cards) in exports {
+            self.exports.entry(deck_id).or_default().extend(cards);
+        }
+        print_counts(&path.display().to_string(), counts);
+        self.total.add(counts);
+        self.summaries.push(FileSummary {
+            path,
+            decks: deck_count,
+            models: models.len(),
+            counts,
+        });
+    }
+}
+
+pub async fn run(
+    _db: &DB,
+    source: &ImportSource,
+    export_path: &Path,
+    card_limit: Option<usize>,
+    report_path: Option<&Path>,
+    progress: Option<ProgressFormat>,
+) -> Result<()> {
+    let mut import = ImportRun::default();
+    if progress == Some(ProgressFormat::Json) {
+        import.progress = Progress::json(Box::new(io::stderr()));
+    }
+    match source {
+        ImportSource::Apkgs(anki_paths) => {
+            let apkgs = collect_apkgs(anki_paths)?;
+            import.progress.report("read", 0, apkgs.len());
+            for (idx, apkg) in apkgs.iter().enumerate() {
+                println!(
+                    "Importing {}",
+                    Palette::paint(Palette::ACCENT, apkg.display())
+                );
+                let db_path = extract_collection_db(apkg)?;
+                let db_url = format!("sqlite://{}", db_path.path().display());
+                let export_db = SqlitePool::connect(&db_url)
+                    .await
+                    .context("failed to connect to Anki database")?;
+                let (decks, models) = load_metadata(&export_db).await?;
+                let cards = load_cards(&export_db).await?;
+                export_db.close().await;
+                import.add_source(apkg.clone(), decks, models, cards, card_limit);
+                import.progress.report("read", idx + 1, apkgs.len());
+            }
+            if apkgs.len() > 1 {
+                print_counts("Total", import.total);
+            }
+        }
+        ImportSource::AnkiConnect { deck } => {
+            let url = ankiconnect_url();
+            println!(
+                "Importing deck {} from AnkiConnect at {}",
+                Palette::paint(Palette::ACCENT, deck),
+                Palette::paint(Palette::ACCENT, &url)
+            );
+            let (decks, models, cards) = load_ankiconnect_deck(&url, deck).await?;
+            let label = PathBuf::from(format!("ankiconnect:{deck}"));
+            import.add_source(label, decks, models, cards, card_limit);
+            import.progress.report("read", 1, 1);
+        }
+    }
+    let written = write_exports(
+        export_path,
+        &import.decks,
+        import.exports,
+        &mut import.progress,
+    )?;
+    let manifest_path = export_path.join(MANIFEST_FILE_NAME);
+    let manifest = format_manifest(
+        export_path,
+        &written,
+        &import.decks,
+        &import.seen.duplicates,
+    )?;
+    fs::write(&manifest_path, manifest)
+        .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+    if let Some(report_path) = report_path {
+        let report = format_report(&import.summaries, import.decks.len(), &written)?;
+        fs::write(report_path, report)
+            .with_context(|| format!("failed to write report: {}", report_path.display()))?;
+        println!(
+            "Wrote import report to {}",
+            Palette::paint(Palette::ACCENT, report_path.display())
+        );
+    }
     Ok(())
 }
 
+/// Where AnkiConnect listens; overridden by `REPEATER_ANKICONNECT_URL`.
+fn ankiconnect_url() -> String {
+    std::env::var(ANKICONNECT_URL_ENV).unwrap_or_else(|_| DEFAULT_ANKICONNECT_URL.to_string())
+}
+
+/// Calls one AnkiConnect action and returns its `result`.
+async fn ankiconnect_request(
+    client: &reqwest::Client,
+    url: &str,
+    action: &str,
+    params: Value,
+) -> Result<Value> {
+    let body = serde_json::json!({
+        "action": action,
+        "version": ANKICONNECT_VERSION,
+        "params": params,
+    });
+    let response = client.post(url).json(&body).send().await.map_err(|err| {
+        if err.is_connect() || err.is_timeout() {
+            anyhow!(
+                "could not reach AnkiConnect at {url}; make sure Anki is running with the AnkiConnect add-on installed"
+            )
+        } else {
+            anyhow!("AnkiConnect request failed: {err}")
+        }
+    })?;
+    let mut reply: Value = response
+        .error_for_status()
+        .context("AnkiConnect returned an error status")?
+        .json()
+        .await
+        .context("AnkiConnect returned a response that is not JSON")?;
+    if let Some(error) = reply.get("error").and_then(|v| v.as_str()) {
+        bail!("AnkiConnect {action} failed: {error}");
+    }
+    Ok(reply["result"].take())
+}
+
+async fn load_ankiconnect_deck(
+    url: &str,
+    deck: &str,
+) -> Result<(
+    HashMap<i64, DeckInfo>,
+    HashMap<i64, ModelInfo>,
+    Vec<CardRecord>,
+)> {
+    let client = reqwest::Client::builder()
+        .timeout(ANKICONNECT_TIMEOUT)
+        .build()?;
+    let query = format!("deck:\"{}\"", deck.replace('"', "\\\""));
+    let note_ids = ankiconnect_request(
+        &client,
+        url,
+        "findNotes",
+        serde_json::json!({ "query": query }),
+    )
+    .await?;
+    if note_ids.as_array().is_none_or(|ids| ids.is_empty()) {
+        bail!("no notes found in Anki deck {deck:?}");
+    }
+    let notes = ankiconnect_request(
+        &client,
+        url,
+        "notesInfo",
+        serde_json::json!({ "notes": note_ids }),
+    )
+    .await?;
+    let decks = HashMap::from([(
+        ANKICONNECT_DECK_ID,
+        DeckInfo {
+            name: deck.to_string(),
+            components: deck_components(deck),
+        },
+    )]);
+    let (models, cards) = records_from_notes_info(&notes, ANKICONNECT_DECK_ID)?;
+    println!(
+        "{} cards from {} notes",
+        Palette::paint(Palette::WARNING, cards.len()),
+        Palette::paint(Palette::WARNING, note_ids.as_array().map_or(0, Vec::len))
+    );
+    Ok((decks, models, cards))
+}
+
+/// Turns a `notesInfo` result into the records the apkg path produces. Note
+/// types get ids in the order they are first seen, and a note with several
+/// cards (such as a reversed basic note) yields one record per card.
+fn records_from_notes_info(
+    notes: &Value,
+    deck_id: i64,
+) -> Result<(HashMap<i64, ModelInfo>, Vec<CardRecord>)> {
+    let notes = notes
+        .as_array()
+        .ok_or_else(|| anyhow!("notesInfo did not return a list of notes"))?;
+    let mut model_ids: HashMap<String, i64> = HashMap::new();
+    let mut models = HashMap::new();
+    let mut cards = Vec::new();
+    for note in notes {
+        let model_name = note
+            .get("modelName")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Basic");
+        let mut fields: Vec<(i64, String, String)> = note
+            .get("fields")
+            .and_then(|v| v.as_object())
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|(name, field)| {
+                        let order = field.get("order").and_then(|v| v.as_i64()).unwrap_or(0);
+                        let value = field.get("value").and_then(|v| v.as_str()).unwrap_or("");
+                        (order, name.trim().to_string(), value.to_string())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        fields.sort_by_key(|(order, _, _)| *order);
+
+        let next_id = model_ids.len() as i64 + 1;
+        let model_id = *model_ids.entry(model_name.to_string()).or_insert(next_id);
+        models.entry(model_id).or_insert_with(|| {
+            let is_cloze = model_name.to_lowercase().contains("cloze")
+                || fields.iter().any(|(_, _, value)| CLOZE_RE.is_match(value));
+            ModelInfo {
+                kind: if is_cloze {
+                    ModelKind::Cloze
+                } else {
+                    ModelKind::Basic
+                },
+                field_names: fields.iter().map(|(_, name, _)| name.clone()).collect(),
+            }
+        });
+
+        let card_count = note
+            .get("cards")
+            .and_then(|v| v.as_array())
+            .map_or(1, |cards| cards.len().max(1));
+        let values: Vec<String> = fields.into_iter().map(|(_, _, value)| value).collect();
+        for card_order in 0..card_count as i64 {
+            cards.push(CardRecord {
+                deck_id,
+                model_id,
+                card_order,
+                fields: values.clone(),
+            });
+        }
+    }
+    Ok((models, cards))
+}
+
+/// The machine-readable summary written by `--report`.
+fn format_report(
+    summaries: &[FileSummary],
+    deck_count: usize,
+    written: &[WrittenDeck],
+) -> Result<String> {
+    let mut total = ImportCounts::default();
+    let files: Vec<Value> = summaries
+        .iter()
+        .map(|summary| {
+            total.add(summary.counts);
+            let mut value = summary.counts.to_json();
+            value["path"] = summary.path.display().to_string().into();
+            value["decks"] = summary.decks.into();
+            value["models"] = summary.models.into();
+            value
+        })
+        .collect();
+    let mut totals = total.to_json();
+    totals["decks"] = deck_count.into();
+    totals["files_written"] = written.len().into();
+    let written: Vec<Value> = written
+        .iter()
+        .map(|deck| {
+            serde_json::json!({
+                "path": deck.path.display().to_string(),
+                "deck": deck.deck,
+                "cards": deck.cards,
+            })
+        })
+        .collect();
+    let report = serde_json::json!({
+        "files": files,
+        "totals": totals,
+        "written": written,
+    });
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+/// JSON listing each Markdown file written, relative to `export_path`, with
+/// the Anki deck it came from, its card count, and the content hashes of the
+/// deck's notes skipped as duplicates. Decks whose every note was a duplicate
+/// are listed with a `null` path.
+fn format_manifest(
+    export_path: &Path,
+    written: &[WrittenDeck],
+    decks: &HashMap<i64, DeckInfo>,
+    duplicates: &HashMap<i64, Vec<String>>,
+) -> Result<String> {
+    let skipped = |deck_id: i64| duplicates.get(&deck_id).cloned().unwrap_or_default();
+    let mut files: Vec<Value> = written
+        .iter()
+        .map(|deck| {
+            let path = deck.path.strip_prefix(export_path).unwrap_or(&deck.path);
+            serde_json::json!({
+                "path": path.display().to_string(),
+                "deck": deck.deck,
+                "deck_id": deck.deck_id,
+                "cards": deck.cards,
+                "skipped_duplicates": skipped(deck.deck_id),
+            })
+        })
+        .collect();
+    let mut unwritten: Vec<(&i64, &Vec<String>)> = duplicates
+        .iter()
+        .filter(|(deck_id, _)| !written.iter().any(|deck| deck.deck_id == **deck_id))
+        .collect();
+    unwritten.sort();
+    for (deck_id, hashes) in unwritten {
+        files.push(serde_json::json!({
+            "path": Value::Null,
+            "deck": decks.get(deck_id).map(|deck| deck.name.clone()),
+            "deck_id": deck_id,
+            "cards": 0,
+            "skipped_duplicates": hashes,
+        }));
+    }
+    Ok(serde_json::to_string_pretty(
+        &serde_json::json!({ "files": files }),
+    )?)
+}
+
+fn print_counts(label: &str, counts: ImportCounts) {
+    println!(
+        "{}: {} cards, {} duplicates removed, {} unexportable",
+        Palette::paint(Palette::ACCENT, label),
+        Palette::paint(Palette::WARNING, counts.kept),
+        Palette::paint(Palette::WARNING, counts.duplicates),
+        Palette::paint(Palette::WARNING, counts.unexportable)
+    );
+    if counts.unknown_templates > 0 {
+        eprintln!(
+            "warning: {label}: removed {} from field text",
+            pluralize("unrecognised `{{...}}` token", counts.unknown_templates)
+        );
+    }
+}
+
+/// Expands the command line paths into apkg files. Directories contribute the
+/// apkg files directly inside them, in name order.
+fn collect_apkgs(anki_paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut apkgs = Vec::new();
+    for anki_path in anki_paths {
+        if !anki_path.is_dir() {
+            validate_path(anki_path)?;
+            apkgs.push(anki_path.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = fs::read_dir(anki_path)
+            .with_context(|| format!("failed to read directory: {}", anki_path.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension() == Some("apkg".as_ref()))
+            .collect();
+        if found.is_empty() {
+            bail!("No apkg files found in {}", anki_path.display());
+        }
+        found.sort();
+        apkgs.extend(found);
+    }
+    Ok(apkgs)
+}
+
 fn validate_path(anki_path: &Path) -> Result<()> {
     if !anki_path.exists() {
         bail!("Anki path does not exist: {}", anki_path.display());
@@ -66,6 +586,41 @@ fn validate_path(anki_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Folds one file's decks into `decks` and rewrites the cards' deck ids to
+/// match. A deck whose name is already known reuses that id, so the same deck
+/// exported from several files ends up in one markdown file; other decks keep
+/// their id unless an earlier file already took it.
+fn merge_decks(
+    decks: &mut HashMap<i64, DeckInfo>,
+    file_decks: HashMap<i64, DeckInfo>,
+    mut cards: Vec<CardRecord>,
+) -> Vec<CardRecord> {
+    let mut file_decks: Vec<(i64, DeckInfo)> = file_decks.into_iter().collect();
+    file_decks.sort_by_key(|(id, _)| *id);
+    let mut remap: HashMap<i64, i64> = HashMap::new();
+    for (id, deck) in file_decks {
+        let existing = decks
+            .iter()
+            .find(|(_, known)| known.name == deck.name)
+            .map(|(known_id, _)| *known_id);
+        let merged_id = existing.unwrap_or_else(|| {
+            let mut new_id = id;
+            while decks.contains_key(&new_id) {
+                new_id += 1;
+            }
+            decks.insert(new_id, deck);
+            new_id
+        });
+        remap.insert(id, merged_id);
+    }
+    for card in &mut cards {
+        if let Some(&deck_id) = remap.get(&card.deck_id) {
+            card.deck_id = deck_id;
+        }
+    }
+    cards
+}
+
 fn extract_collection_db(apkg: &Path) -> Result<NamedTempFile> {
     let file = File::open(apkg)
         .with_context(|| format!("failed to open apkg file: {}", apkg.display()))?;
@@ -90,17 +645,37 @@ fn extract_collection_db(apkg: &Path) -> Result<NamedTempFile> {
     Ok(temp)
 }
 
+/// Where a collection keeps its deck and note type definitions.
+#[derive(Debug, PartialEq)]
+enum MetadataSchema {
+    /// Newer Anki: `decks`, `notetypes` and `fields` tables.
+    Tables,
+    /// Older Anki: JSON blobs in the `decks` and `models` columns of `col`.
+    ColJson,
+}
+
+async fn detect_schema(pool: &SqlitePool) -> Result<MetadataSchema> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) AS found FROM sqlite_master WHERE type = 'table' AND name = 'notetypes'",
+    )
+    .fetch_one(pool)
+    .await
+    .context("failed to inspect collection schema")?;
+    let found: i64 = row.try_get("found")?;
+    Ok(if found > 0 {
+        MetadataSchema::Tables
+    } else {
+        MetadataSchema::ColJson
+    })
+}
+
 async fn load_metadata(
     pool: &SqlitePool,
-) -> Result<(HashMap<i64, DeckInfo>, HashMap<i64, ModelKind>)> {
-    let row = sqlx::query("SELECT decks, models FROM col LIMIT 1")
-        .fetch_one(pool)
-        .await
-        .context("failed to read deck metadata")?;
-    let decks_raw: String = row.try_get("decks")?;
-    let models_raw: String = row.try_get("models")?;
-    let decks = parse_decks(&decks_raw)?;
-    let models = parse_models(&models_raw)?;
+) -> Result<(HashMap<i64, DeckInfo>, HashMap<i64, ModelInfo>)> {
+    let (decks, models) = match detect_schema(pool).await? {
+        MetadataSchema::Tables => load_metadata_tables(pool).await?,
+        MetadataSchema::ColJson => load_metadata_col(pool).await?,
+    };
     println!(
         "{} decks and {} models in DB schema",
         Palette::paint(Palette::WARNING, decks.len()),
@@ -109,6 +684,130 @@ async fn load_metadata(
     Ok((decks, models))
 }
 
+/// Reads every `col` row rather than assuming there is exactly one, skipping
+/// the empty blobs newer collections leave behind.
+async fn load_metadata_col(
+    pool: &SqlitePool,
+) -> Result<(HashMap<i64, DeckInfo>, HashMap<i64, ModelInfo>)> {
+    let rows = sqlx::query("SELECT decks, models FROM col")
+        .fetch_all(pool)
+        .await
+        .context("failed to read deck metadata")?;
+    if rows.is_empty() {
+        bail!("collection has no deck metadata (the col table is empty)");
+    }
+    let mut decks = HashMap::new();
+    let mut models = HashMap::new();
+    for row in rows {
+        let decks_raw: String = row.try_get("decks")?;
+        let models_raw: String = row.try_get("models")?;
+        if !decks_raw.trim().is_empty() {
+            decks.extend(parse_decks(&decks_raw)?);
+        }
+        if !models_raw.trim().is_empty() {
+            models.extend(parse_models(&models_raw)?);
+        }
+    }
+    Ok((decks, models))
+}
+
+async fn load_metadata_tables(
+    pool: &SqlitePool,
+) -> Result<(HashMap<i64, DeckInfo>, HashMap<i64, ModelInfo>)> {
+    let mut decks = HashMap::new();
+    let deck_rows = sqlx::query("SELECT id, name FROM decks")
+        .fetch_all(pool)
+        .await
+        .context("failed to read decks table")?;
+    for row in deck_rows {
+        let id: i64 = row.try_get("id")?;
+        // Nested deck names are separated by \x1f here instead of `::`.
+        let name = row.try_get::<String, _>("name")?.replace('\u{1f}', "::");
+        decks.insert(
+            id,
+            DeckInfo {
+                components: deck_components(&name),
+                name,
+            },
+        );
+    }
+
+    let mut models = HashMap::new();
+    let notetype_rows = sqlx::query("SELECT id, config FROM notetypes")
+        .fetch_all(pool)
+        .await
+        .context("failed to read notetypes table")?;
+    for row in notetype_rows {
+        let id: i64 = row.try_get("id")?;
+        let config: Vec<u8> = row.try_get("config")?;
+        let kind = match notetype_kind(&config) {
+            1 => ModelKind::Cloze,
+            _ => ModelKind::Basic,
+        };
+        models.insert(
+            id,
+            ModelInfo {
+                kind,
+                field_names: Vec::new(),
+            },
+        );
+    }
+
+    let field_rows = sqlx::query("SELECT ntid, name FROM fields ORDER BY ntid, ord")
+        .fetch_all(pool)
+        .await
+        .context("failed to read fields table")?;
+    for row in field_rows {
+        let ntid: i64 = row.try_get("ntid")?;
+        let name: String = row.try_get("name")?;
+        if let Some(model) = models.get_mut(&ntid) {
+            model.field_names.push(name.trim().to_string());
+        }
+    }
+    Ok((decks, models))
+}
+
+/// The `kind` (field 1) of a protobuf-encoded notetype config: 0 for normal
+/// note types, 1 for cloze. Anything unreadable counts as normal.
+fn notetype_kind(config: &[u8]) -> u64 {
+    let mut bytes = config.iter().copied();
+    while let Some(key) = read_varint(&mut bytes) {
+        let (field, wire_type) = (key >> 3, key & 0x7);
+        let skip = match wire_type {
+            0 => {
+                let value = read_varint(&mut bytes);
+                if field == 1 {
+                    return value.unwrap_or(0);
+                }
+                0
+            }
+            1 => 8,
+            2 => match read_varint(&mut bytes) {
+                Some(len) => len as usize,
+                None => return 0,
+            },
+            5 => 4,
+            _ => return 0,
+        };
+        if bytes.by_ref().take(skip).count() < skip {
+            return 0;
+        }
+    }
+    0
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes.next()?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
 fn parse_decks(json: &str) -> Result<HashMap<i64, DeckInfo>> {
     let value: Value = serde_json::from_str(json).context("failed to parse decks json")?;
     let mut decks = HashMap::new();
@@ -130,7 +829,7 @@ fn parse_decks(json: &str) -> Result<HashMap<i64, DeckInfo>> {
     Ok(decks)
 }
 
-fn parse_models(json: &str) -> Result<HashMap<i64, ModelKind>> {
+fn parse_models(json: &str) -> Result<HashMap<i64, ModelInfo>> {
     let value: Value = serde_json::from_str(json).context("failed to parse models json")?;
     let mut models = HashMap::new();
     if let Some(map) = value.as_object() {
@@ -140,7 +839,24 @@ fn parse_models(json: &str) -> Result<HashMap<i64, ModelKind>> {
                     1 => ModelKind::Cloze,
                     _ => ModelKind::Basic,
                 };
-                models.insert(id, kind);
+                let mut fields: Vec<(i64, String)> = model
+                    .get("flds")
+                    .and_then(|v| v.as_array())
+                    .map(|flds| {
+                        flds.iter()
+                            .enumerate()
+                            .map(|(i, fld)| {
+                                let ord =
+                                    fld.get("ord").and_then(|v| v.as_i64()).unwrap_or(i as i64);
+                                let name = fld.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                                (ord, name.trim().to_string())
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                fields.sort_by_key(|(ord, _)| *ord);
+                let field_names = fields.into_iter().map(|(_, name)| name).collect();
+                models.insert(id, ModelInfo { kind, field_names });
             }
         }
     }
@@ -187,56 +903,98 @@ async fn load_cards(pool: &SqlitePool) -> Result<Vec<CardRecord>> {
 
 fn build_exports(
     cards: Vec<CardRecord>,
-    models: &HashMap<i64, ModelKind>,
-) -> HashMap<i64, Vec<String>> {
+    models: &HashMap<i64, ModelInfo>,
+    card_limit: Option<usize>,
+    seen: &mut SeenNotes,
+    progress: &mut Progress,
+) -> (HashMap<i64, Vec<String>>, ImportCounts) {
     let mut per_deck: HashMap<i64, Vec<String>> = HashMap::new();
+    let found = cards.len();
+    let mut unknown_model = 0;
+    let mut num_discovered = 0;
+    let mut num_kept = 0;
     let mut num_duplicates = 0;
-    let mut content_hashes: HashSet<String> = HashSet::new();
 
     let mut unexportable = 0;
-    for card in cards {
+    let mut unknown_templates = 0;
+    progress.report("convert", 0, found);
+    for (idx, mut card) in cards.into_iter().enumerate() {
+        if idx > 0 && idx % PROGRESS_EVERY == 0 {
+            progress.report("convert", idx, found);
+        }
+        for field in &mut card.fields {
+            let (stripped, unknown) = strip_template_tokens(field);
+            *field = stripped;
+            unknown_templates += unknown;
+        }
         let Some(model) = models.get(&card.model_id) else {
             println!(
                 "Card with an unknown model id found: {}",
                 Palette::paint(Palette::DANGER, card.model_id)
             );
+            unknown_model += 1;
             continue;
         };
-        let entry = match model {
-            ModelKind::Basic => basic_entry(&card.fields, card.card_order),
+        let entry = match model.kind {
+            ModelKind::Basic => basic_entry(&card.fields, &model.field_names, card.card_order),
             ModelKind::Cloze => cloze_entry(&card.fields),
         };
 
-        let Some(content) = entry else {
+        let Some(entry) = entry else {
             unexportable += 1;
             continue;
         };
-        let Some(content_hash) = get_hash(&content) else {
+        let Some(content_hash) = entry.content_hash() else {
             unexportable += 1;
             continue;
         };
-        if !content_hashes.insert(content_hash) {
+        if !seen.hashes.insert(content_hash.clone()) {
             num_duplicates += 1;
+            seen.duplicates
+                .entry(card.deck_id)
+                .or_default()
+                .push(content_hash);
             continue;
         }
-        per_deck.entry(card.deck_id).or_default().push(content);
+        num_discovered += 1;
+        if let Some(limit) = card_limit
+            && num_kept >= limit
+        {
+            continue;
+        }
+        num_kept += 1;
+        per_deck
+            .entry(card.deck_id)
+            .or_default()
+            .push(entry.markdown);
     }
-    println!(
-        "Removing {} duplicates",
-        Palette::paint(Palette::WARNING, num_duplicates)
-    );
-    println!(
-        "{} unexportable cards",
-        Palette::paint(Palette::WARNING, unexportable)
-    );
-    per_deck
+    if found > 0 {
+        progress.report("convert", found, found);
+    }
+    if card_limit.is_some() {
+        println!(
+            "Keeping {} of {} cards discovered (limited by --count)",
+            Palette::paint(Palette::WARNING, num_kept),
+            Palette::paint(Palette::WARNING, num_discovered)
+        );
+    }
+    let counts = ImportCounts {
+        found,
+        kept: num_kept,
+        duplicates: num_duplicates,
+        unknown_model,
+        unexportable,
+        unknown_templates,
+    };
+    (per_deck, counts)
 }
 
 fn write_exports(
     export_path: &Path,
     decks: &HashMap<i64, DeckInfo>,
     exports: HashMap<i64, Vec<String>>,
-) -> Result<()> {
+    progress: &mut Progress,
+) -> Result<Vec<WrittenDeck>> {
     for deck_id in decks.keys() {
         let exports_per_deck = exports.get(deck_id).map(|v| v.len()).unwrap_or(0);
         println!(
@@ -256,24 +1014,17 @@ fn write_exports(
     entries.sort_by(|(a, _), (b, _)| {
         let name_a = decks.get(a).map(|d| d.name.as_str()).unwrap_or("");
         let name_b = decks.get(b).map(|d| d.name.as_str()).unwrap_or("");
-        name_a.cmp(name_b)
+        name_a.cmp(name_b).then(a.cmp(b))
     });
+    let mut used_paths: HashSet<String> = HashSet::new();
+    let mut written = Vec::with_capacity(entries.len());
+    let total = entries.len();
+    progress.report("write", 0, total);
     for (deck_id, cards) in entries {
         let deck = decks
             .get(&deck_id)
             .ok_or_else(|| anyhow!("missing deck metadata for id {}", deck_id))?;
-        let mut path = PathBuf::from(export_path);
-        if deck.components.len() > 1 {
-            for component in &deck.components[..deck.components.len() - 1] {
-                path.push(component);
-            }
-        }
-        let file_stem = deck
-            .components
-            .last()
-            .cloned()
-            .unwrap_or_else(|| "Deck".to_string());
-        path.push(format!("{file_stem}.md"));
+        let path = unique_deck_path(export_path, deck, &mut used_paths);
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
@@ -287,8 +1038,51 @@ fn write_exports(
             Palette::paint(Palette::ACCENT, path.display())
         );
         fs::write(&path, content)?;
+        written.push(WrittenDeck {
+            path,
+            deck_id,
+            deck: deck.name.clone(),
+            cards: cards.len(),
+        });
+        progress.report("write", written.len(), total);
     }
-    Ok(())
+    Ok(written)
+}
+
+/// Maps a deck to its markdown file, appending `-2`, `-3`, ... to the stem when
+/// another deck already sanitized to the same path. Paths are compared
+/// case-insensitively so the result is safe on macOS and Windows too.
+fn unique_deck_path(
+    export_path: &Path,
+    deck: &DeckInfo,
+    used_paths: &mut HashSet<String>,
+) -> PathBuf {
+    let mut dir = PathBuf::from(export_path);
+    if deck.components.len() > 1 {
+        for component in &deck.components[..deck.components.len() - 1] {
+            dir.push(component);
+        }
+    }
+    let file_stem = deck
+        .components
+        .last()
+        .cloned()
+        .unwrap_or_else(|| "Deck".to_string());
+
+    let mut path = dir.join(format!("{file_stem}.md"));
+    let mut suffix = 2;
+    while !used_paths.insert(path.to_string_lossy().to_lowercase()) {
+        path = dir.join(format!("{file_stem}-{suffix}.md"));
+        suffix += 1;
+    }
+    if suffix > 2 {
+        println!(
+            "Deck {} collides with another deck's file name, writing it to {}",
+            Palette::paint(Palette::ACCENT, deck.name.as_str()),
+            Palette::paint(Palette::WARNING, path.display())
+        );
+    }
+    path
 }
 
 fn split_fields(raw: &str) -> Vec<String> {
@@ -309,6 +1103,46 @@ fn clean_field(field: &str) -> String {
     decode_html_entities(without_tags.trim()).to_string()
 }
 
+/// Template syntax that can leak into a note's fields, such as `{{type:Back}}`
+/// or `{{#Extra}}`, and is safe to drop.
+fn is_known_template_token(token: &str) -> bool {
+    const PREFIXES: [&str; 9] = [
+        "type:",
+        "hint:",
+        "text:",
+        "tts",
+        "furigana:",
+        "kana:",
+        "kanji:",
+        "cloze:",
+        "cloze-only:",
+    ];
+    token == "FrontSide"
+        || token.starts_with(['#', '/', '^', '!'])
+        || PREFIXES.iter().any(|prefix| token.starts_with(prefix))
+}
+
+/// Removes `{{...}}` template tokens from field text, leaving cloze deletions
+/// (`{{c1::...}}`) alone. Returns the text and how many removed tokens were
+/// not recognised Anki template syntax.
+fn strip_template_tokens(field: &str) -> (String, usize) {
+    if !field.contains("{{") {
+        return (field.to_string(), 0);
+    }
+    let mut unknown = 0;
+    let stripped = TEMPLATE_RE.replace_all(field, |caps: &regex::Captures| {
+        let token = caps[1].trim();
+        if CLOZE_RE.is_match(&caps[0]) {
+            return caps[0].to_string();
+        }
+        if !is_known_template_token(token) {
+            unknown += 1;
+        }
+        String::new()
+    });
+    (stripped.trim().to_string(), unknown)
+}
+
 fn deck_components(name: &str) -> Vec<String> {
     let mut parts: Vec<String> = name
         .split("::")
@@ -337,7 +1171,7 @@ fn sanitize_component(input: &str) -> String {
     out.trim().to_string()
 }
 
-fn basic_entry(fields: &[String], ord: i64) -> Option<String> {
+fn basic_entry(fields: &[String], field_names: &[String], ord: i64) -> Option<Entry> {
     if fields.len() < 2 {
         return None;
     }
@@ -348,16 +1182,54 @@ fn basic_entry(fields: &[String], ord: i64) -> Option<String> {
     };
     let mut entry = format_section("Q", question)?;
     entry.push_str(&format_section("A", answer)?);
+    if let Some(footer) = extra_fields_footer(fields, field_names) {
+        entry.push_str(&footer);
+    }
     entry.push('\n');
-    Some(entry)
+    Some(Entry {
+        markdown: entry,
+        content: vec![question.trim().to_string(), answer.trim().to_string()],
+    })
 }
 
-fn cloze_entry(fields: &[String]) -> Option<String> {
+/// Fields past front/back (e.g. `Ref`, `Credit`) as one compact line under
+/// the answer, such as `— Ref: [url](url) · Credit: Jane`. Empty fields are
+/// left out, and `None` means there is nothing to show.
+fn extra_fields_footer(fields: &[String], field_names: &[String]) -> Option<String> {
+    let parts: Vec<String> = fields
+        .iter()
+        .enumerate()
+        .skip(2)
+        .filter_map(|(idx, value)| {
+            let value = value.trim();
+            if value.is_empty() {
+                return None;
+            }
+            let label = field_names
+                .get(idx)
+                .filter(|name| !name.is_empty())
+                .cloned()
+                .unwrap_or_else(|| format!("Field {}", idx + 1));
+            if value.starts_with("http://") || value.starts_with("https://") {
+                Some(format!("{label}: [{value}]({value})"))
+            } else {
+                Some(format!("{label}: {value}"))
+            }
+        })
+        .collect();
+    (!parts.is_empty()).then(|| format!("— {}\n", parts.join(" · ")))
+}
+
+fn cloze_entry(fields: &[String]) -> Option<Entry> {
     let text = fields.first()?;
     let converted = convert_cloze(text);
-    let mut entry = format_section("C", converted.trim())?;
+    let converted = converted.trim();
+    let mut entry = format_section("C", converted)?;
     entry.push('\n');
-    Some(entry)
+    Some(Entry {
+        markdown: entry,
+        content: vec![converted.to_string()],
+    })
 }
 
 fn format_section(label: &str, value: &str) -> Option<String> {
@@ -392,18 +1264,133 @@ mod tests {
         assert_eq!(clean_field(input), "Hello & world");
     }
 
+    #[test]
+    fn template_tokens_are_stripped_from_fields() {
+        assert_eq!(
+            strip_template_tokens("Answer: {{type:Back}}"),
+            ("Answer:".to_string(), 0)
+        );
+        assert_eq!(
+            strip_template_tokens("{{#Extra}}{{hint:Extra}}{{/Extra}} see {{FrontSide}}"),
+            ("see".to_string(), 0)
+        );
+        // Clozes survive; anything unrecognised is dropped and counted.
+        assert_eq!(
+            strip_template_tokens("{{c1::Lima}} is the capital {{Mystery}} {{other}}"),
+            ("{{c1::Lima}} is the capital".to_string(), 2)
+        );
+        assert_eq!(
+            strip_template_tokens("no tokens"),
+            ("no tokens".to_string(), 0)
+        );
+
+        let models = parse_models(r#"{"1": {"id": 1, "type": 0, "flds": []}}"#).unwrap();
+        let cards = vec![CardRecord {
+            deck_id: 1,
+            model_id: 1,
+            card_order: 0,
+            fields: split_fields("Capital of Peru?\u{1f}Lima {{type:Back}}{{Leftover}}"),
+        }];
+        let (exports, counts) = build_exports(
+            cards,
+            &models,
+            None,
+            &mut SeenNotes::default(),
+            &mut Progress::default(),
+        );
+        assert_eq!(
+            exports[&1],
+            vec!["Q: Capital of Peru?\nA: Lima\n\n".to_string()]
+        );
+        assert_eq!(counts.unknown_templates, 1);
+    }
+
     #[test]
     fn basic_entry_swaps_fields_on_reverse_cards() {
         let fields = vec!["Front".into(), "Back".into()];
-        let forward = basic_entry(&fields, 0).unwrap();
+        let forward = basic_entry(&fields, &[], 0).unwrap().markdown;
         assert!(forward.contains("Q: Front"));
         assert!(forward.contains("A: Back"));
 
-        let reverse = basic_entry(&fields, 1).unwrap();
+        let reverse = basic_entry(&fields, &[], 1).unwrap().markdown;
         assert!(reverse.contains("Q: Back"));
         assert!(reverse.contains("A: Front"));
 
-        assert!(basic_entry(&["Only".into()], 0).is_none());
+        assert!(basic_entry(&["Only".into()], &[], 0).is_none());
+    }
+
+    #[test]
+    fn basic_entry_keeps_extra_fields_in_an_answer_footer() {
+        let models = parse_models(
+            r#"{"7": {"id": 7, "type": 0, "flds": [
+                {"name": "Front", "ord": 0}, {"name": "Back", "ord": 1},
+                {"name": "Ref", "ord": 2}, {"name": "Credit", "ord": 3}
+            ]}}"#,
+        )
+        .unwrap();
+        let names = &models[&7].field_names;
+        assert_eq!(names, &["Front", "Back", "Ref", "Credit"]);
+
+        let fields: Vec<String> = vec![
+            "Feature selection methods?".into(),
+            "Decision trees".into(),
+            "https://example.com/post".into(),
+            "".into(),
+        ];
+        let entry = basic_entry(&fields, names, 0).unwrap().markdown;
+        assert!(entry.contains(
+            "A: Decision trees\n— Ref: [https://example.com/post](https://example.com/post)\n"
+        ));
+        assert!(!entry.contains("Credit"));
+
+        let card =
+            crate::parser::content_to_card(std::path::Path::new("deck.md"), &entry, 0, 3).unwrap();
+        let crate::card::CardContent::Basic { answer, .. } = card.content else {
+            panic!("expected a basic card");
+        };
+        assert!(answer.contains("Ref: "));
+
+        let credited: Vec<String> = vec![
+            "Q".into(),
+            "A".into(),
+            "https://example.com".into(),
+            "Jane Doe".into(),
+        ];
+        assert_eq!(
+            extra_fields_footer(&credited, names).as_deref(),
+            Some("— Ref: [https://example.com](https://example.com) · Credit: Jane Doe\n")
+        );
+    }
+
+    #[test]
+    fn empty_ref_and_credit_add_no_footer() {
+        let names: Vec<String> = ["Front", "Back", "Ref", "Credit"]
+            .map(String::from)
+            .to_vec();
+        let fields: Vec<String> = vec!["Question?".into(), "Answer".into(), " ".into(), "".into()];
+        assert_eq!(extra_fields_footer(&fields, &names), None);
+        assert_eq!(
+            basic_entry(&fields, &names, 0).unwrap().markdown,
+            "Q: Question?\nA: Answer\n\n"
+        );
+    }
+
+    #[test]
+    fn content_hash_ignores_markdown_layout() {
+        let fields: Vec<String> = vec!["  Front ".into(), "Back".into(), "Extra".into()];
+        let entry = basic_entry(&fields, &[], 0).unwrap();
+        let relabeled = Entry {
+            markdown: entry.markdown.replace("Q: ", "**Question:** "),
+            content: entry.content.clone(),
+        };
+        assert_eq!(entry.content_hash(), relabeled.content_hash());
+        assert_ne!(entry.content_hash(), get_hash(&entry.markdown));
+
+        let reverse = basic_entry(&fields, &[], 1).unwrap();
+        assert_ne!(entry.content_hash(), reverse.content_hash());
+
+        let cloze = cloze_entry(&["Capital {{c1::Tokyo}}".into()]).unwrap();
+        assert_eq!(cloze.content, vec!["Capital [Tokyo]".to_string()]);
     }
 
     #[test]
@@ -421,6 +1408,23 @@ mod tests {
         assert_eq!(deck_components(""), vec!["Deck".to_string()]);
     }
 
+    #[test]
+    fn write_exports_keeps_decks_that_sanitize_to_the_same_file() {
+        let export_dir = tempfile::tempdir().unwrap();
+        let decks = HashMap::from([(1, deck("ETL?")), (2, deck("ETL:"))]);
+        let exports = HashMap::from([
+            (1, vec!["Q: first\nA: one\n\n".to_string()]),
+            (2, vec!["Q: second\nA: two\n\n".to_string()]),
+        ]);
+
+        write_exports(export_dir.path(), &decks, exports, &mut Progress::default()).unwrap();
+
+        let first = fs::read_to_string(export_dir.path().join("ETL-.md")).unwrap();
+        let second = fs::read_to_string(export_dir.path().join("ETL--2.md")).unwrap();
+        assert!(second.contains("Q: first"));
+        assert!(first.contains("Q: second"));
+    }
+
     #[tokio::test]
     async fn test_with_apkg() {
         let test_file =
@@ -440,8 +1444,476 @@ mod tests {
         assert_eq!(models.len(), 2);
         let cards = load_cards(&export_db).await.unwrap();
         assert_eq!(cards.len(), 545);
-        let exports = build_exports(cards, &models);
+        let (exports, _) = build_exports(
+            cards,
+            &models,
+            None,
+            &mut SeenNotes::default(),
+            &mut Progress::default(),
+        );
         let len = exports.values().next().map(|v: &Vec<String>| v.len());
         assert_eq!(len, Some(320));
     }
+
+    async fn collection_pool(statements: &[&str]) -> SqlitePool {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for statement in statements {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn metadata_is_read_from_notetype_tables_when_present() {
+        let pool = collection_pool(&[
+            "CREATE TABLE col (decks TEXT, models TEXT)",
+            "INSERT INTO col VALUES ('', '')",
+            "CREATE TABLE decks (id INTEGER PRIMARY KEY, name TEXT)",
+            "INSERT INTO decks VALUES (1, 'Science' || char(31) || 'Physics')",
+            "CREATE TABLE notetypes (id INTEGER PRIMARY KEY, name TEXT, config BLOB)",
+            "INSERT INTO notetypes VALUES (10, 'Basic', x'')",
+            "INSERT INTO notetypes VALUES (20, 'Cloze', x'0801')",
+            "CREATE TABLE fields (ntid INTEGER, ord INTEGER, name TEXT)",
+            "INSERT INTO fields VALUES (10, 1, 'Back'), (10, 0, 'Front'), (20, 0, 'Text')",
+        ])
+        .await;
+
+        assert_eq!(detect_schema(&pool).await.unwrap(), MetadataSchema::Tables);
+        let (decks, models) = load_metadata(&pool).await.unwrap();
+        assert_eq!(decks[&1].name, "Science::Physics");
+        assert_eq!(decks[&1].components, vec!["Science", "Physics"]);
+        assert!(matches!(models[&10].kind, ModelKind::Basic));
+        assert_eq!(models[&10].field_names, vec!["Front", "Back"]);
+        assert!(matches!(models[&20].kind, ModelKind::Cloze));
+        assert_eq!(models[&20].field_names, vec!["Text"]);
+    }
+
+    #[tokio::test]
+    async fn metadata_falls_back_to_col_json_across_rows() {
+        let pool = collection_pool(&[
+            "CREATE TABLE col (decks TEXT, models TEXT)",
+            r#"INSERT INTO col VALUES ('{"1": {"id": 1, "name": "Geo"}}', '')"#,
+            r#"INSERT INTO col VALUES ('', '{"5": {"id": 5, "type": 1, "flds": [{"name": "Text", "ord": 0}]}}')"#,
+        ])
+        .await;
+
+        assert_eq!(detect_schema(&pool).await.unwrap(), MetadataSchema::ColJson);
+        let (decks, models) = load_metadata(&pool).await.unwrap();
+        assert_eq!(decks[&1].name, "Geo");
+        assert!(matches!(models[&5].kind, ModelKind::Cloze));
+    }
+
+    #[test]
+    fn notetype_kind_skips_other_fields() {
+        assert_eq!(notetype_kind(&[]), 0);
+        assert_eq!(notetype_kind(&[0x08, 0x01]), 1);
+        // A length-delimited field 3 ("css") before the kind.
+        assert_eq!(notetype_kind(&[0x1a, 0x02, b'a', b'b', 0x08, 0x01]), 1);
+        assert_eq!(notetype_kind(&[0x1a, 0x09, b'a']), 0);
+    }
+
+    #[test]
+    fn notes_info_maps_to_card_records_and_models() {
+        let notes: Value = serde_json::from_str(
+            r#"[
+                {
+                    "noteId": 1502298033753,
+                    "modelName": "Basic (and reversed card)",
+                    "tags": ["geo"],
+                    "fields": {
+                        "Back": {"value": "Lima", "order": 1},
+                        "Front": {"value": "Capital of <b>Peru</b>?", "order": 0}
+                    },
+                    "cards": [1502298033754, 1502298033755]
+                },
+                {
+                    "noteId": 1502298033760,
+                    "modelName": "Cloze",
+                    "tags": [],
+                    "fields": {
+                        "Text": {"value": "{{c1::Lima}} is in Peru", "order": 0},
+                        "Back Extra": {"value": "", "order": 1}
+                    },
+                    "cards": [1502298033761]
+                },
+                {
+                    "noteId": 1502298033770,
+                    "modelName": "Basic (and reversed card)",
+                    "fields": {
+                        "Front": {"value": "Capital of Chile?", "order": 0},
+                        "Back": {"value": "Santiago", "order": 1}
+                    },
+                    "cards": [1502298033771]
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        let (models, cards) = records_from_notes_info(&notes, 7).unwrap();
+        assert_eq!(models.len(), 2);
+        assert!(matches!(models[&1].kind, ModelKind::Basic));
+        assert_eq!(models[&1].field_names, vec!["Front", "Back"]);
+        assert!(matches!(models[&2].kind, ModelKind::Cloze));
+        assert_eq!(models[&2].field_names, vec!["Text", "Back Extra"]);
+
+        let summary: Vec<(i64, i64, i64, &str)> = cards
+            .iter()
+            .map(|card| {
+                (
+                    card.deck_id,
+                    card.model_id,
+                    card.card_order,
+                    card.fields[0].as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (7, 1, 0, "Capital of <b>Peru</b>?"),
+                (7, 1, 1, "Capital of <b>Peru</b>?"),
+                (7, 2, 0, "{{c1::Lima}} is in Peru"),
+                (7, 1, 0, "Capital of Chile?"),
+            ]
+        );
+        assert_eq!(cards[0].fields[1], "Lima");
+
+        let (exports, counts) = build_exports(
+            cards,
+            &models,
+            None,
+            &mut SeenNotes::default(),
+            &mut Progress::default(),
+        );
+        assert_eq!(counts.kept, 4);
+        assert!(exports[&7].iter().any(|entry| entry.contains("Q: Lima")));
+        assert!(
+            exports[&7]
+                .iter()
+                .any(|entry| entry.starts_with("C: [Lima]"))
+        );
+    }
+
+    /// Model 1 as a plain Basic note type.
+    fn basic_models() -> HashMap<i64, ModelInfo> {
+        HashMap::from([(
+            1,
+            ModelInfo {
+                kind: ModelKind::Basic,
+                field_names: Vec::new(),
+            },
+        )])
+    }
+
+    /// A card of model 1 in `deck_id` with the given front and a `Back` field.
+    fn record(deck_id: i64, front: &str) -> CardRecord {
+        CardRecord {
+            deck_id,
+            model_id: 1,
+            card_order: 0,
+            fields: vec![front.to_string(), "Back".to_string()],
+        }
+    }
+
+    fn deck(name: &str) -> DeckInfo {
+        DeckInfo {
+            name: name.into(),
+            components: deck_components(name),
+        }
+    }
+
+    #[test]
+    fn report_counts_cards_duplicates_and_written_files() {
+        let models = basic_models();
+        let decks = HashMap::from([(1, deck("Geo"))]);
+        let cards = vec![
+            record(1, "Paris"),
+            record(1, "Rome"),
+            record(1, "Paris"),
+            CardRecord {
+                model_id: 9,
+                ..record(1, "Berlin")
+            },
+        ];
+        let (exports, counts) = build_exports(
+            cards,
+            &models,
+            None,
+            &mut SeenNotes::default(),
+            &mut Progress::default(),
+        );
+        let export_dir = tempfile::tempdir().unwrap();
+        let written =
+            write_exports(export_dir.path(), &decks, exports, &mut Progress::default()).unwrap();
+        let summaries = [FileSummary {
+            path: PathBuf::from("geo.apkg"),
+            decks: 1,
+            models: 1,
+            counts,
+        }];
+
+        let report: Value =
+            serde_json::from_str(&format_report(&summaries, 1, &written).unwrap()).unwrap();
+        let file = &report["files"][0];
+        assert_eq!(file["path"], "geo.apkg");
+        assert_eq!(file["cards_found"], 4);
+        assert_eq!(file["cards_kept"], 2);
+        assert_eq!(file["duplicates"], 1);
+        assert_eq!(file["unknown_model"], 1);
+        assert_eq!(report["totals"]["cards_kept"], 2);
+        assert_eq!(report["totals"]["files_written"], 1);
+        assert_eq!(report["written"][0]["deck"], "Geo");
+        assert_eq!(report["written"][0]["cards"], 2);
+    }
+
+    #[test]
+    fn json_progress_reports_increasing_counts_per_phase() {
+        let models = basic_models();
+        let decks = HashMap::from([(10, deck("Geo")), (20, deck("Lang"))]);
+        let cards = (0..2 * PROGRESS_EVERY + 10)
+            .map(|idx| record(if idx % 2 == 0 { 10 } else { 20 }, &format!("Front {idx}")))
+            .collect();
+        let events_file = tempfile::NamedTempFile::new().unwrap();
+        let mut progress = Progress::json(Box::new(events_file.reopen().unwrap()));
+        let (exports, _) = build_exports(
+            cards,
+            &models,
+            None,
+            &mut SeenNotes::default(),
+            &mut progress,
+        );
+        let export_dir = tempfile::tempdir().unwrap();
+        write_exports(export_dir.path(), &decks, exports, &mut progress).unwrap();
+        drop(progress);
+
+        let events: Vec<Value> = fs::read_to_string(events_file.path())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        for phase in ["convert", "write"] {
+            let counts: Vec<(u64, u64)> = events
+                .iter()
+                .filter(|event| event["phase"] == phase)
+                .map(|event| {
+                    (
+                        event["processed"].as_u64().unwrap(),
+                        event["total"].as_u64().unwrap(),
+                    )
+                })
+                .collect();
+            assert!(counts.len() >= 3, "{phase}: {counts:?}");
+            assert!(
+                counts.windows(2).all(|pair| pair[0].0 < pair[1].0),
+                "{phase}: {counts:?}"
+            );
+            let (last, total) = *counts.last().unwrap();
+            assert_eq!(last, total);
+            assert!(counts.iter().all(|(_, t)| *t == total));
+        }
+    }
+
+    #[test]
+    fn manifest_maps_each_written_file_to_its_deck() {
+        let models = basic_models();
+        let decks = HashMap::from([(10, deck("Geo")), (20, deck("Lang::French"))]);
+        let cards = vec![
+            record(10, "Paris"),
+            record(10, "Rome"),
+            record(20, "Bonjour"),
+            record(20, "Paris"),
+            record(20, "Merci"),
+            record(20, "Merci"),
+        ];
+        let mut seen = SeenNotes::default();
+        let (exports, _) = build_exports(cards, &models, None, &mut seen, &mut Progress::default());
+        let export_dir = tempfile::tempdir().unwrap();
+        let written =
+            write_exports(export_dir.path(), &decks, exports, &mut Progress::default()).unwrap();
+
+        let manifest: Value = serde_json::from_str(
+            &format_manifest(export_dir.path(), &written, &decks, &seen.duplicates).unwrap(),
+        )
+        .unwrap();
+        let files = manifest["files"].as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0]["path"], "Geo.md");
+        assert_eq!(files[0]["deck"], "Geo");
+        assert_eq!(files[0]["deck_id"], 10);
+        assert_eq!(files[0]["cards"], 2);
+        assert_eq!(files[0]["skipped_duplicates"].as_array().unwrap().len(), 0);
+        assert_eq!(
+            files[1]["path"],
+            Path::new("Lang").join("French.md").display().to_string()
+        );
+        assert_eq!(files[1]["deck"], "Lang::French");
+        assert_eq!(files[1]["cards"], 2);
+        let skipped = files[1]["skipped_duplicates"].as_array().unwrap();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped.len(), seen.duplicates[&20].len());
+    }
+
+    #[test]
+    fn build_exports_caps_total_cards_across_decks() {
+        let models = basic_models();
+        let cards: Vec<CardRecord> = (0..6)
+            .map(|i| record(i % 2, &format!("Front {i}")))
+            .collect();
+
+        let (capped, _) = build_exports(
+            cards.clone(),
+            &models,
+            Some(4),
+            &mut SeenNotes::default(),
+            &mut Progress::default(),
+        );
+        assert_eq!(capped.values().map(Vec::len).sum::<usize>(), 4);
+
+        let (uncapped, _) = build_exports(
+            cards,
+            &models,
+            None,
+            &mut SeenNotes::default(),
+            &mut Progress::default(),
+        );
+        assert_eq!(uncapped.values().map(Vec::len).sum::<usize>(), 6);
+    }
+
+    /// The Markdown files under `dir`, leaving out the manifest.
+    fn read_tree(dir: &Path) -> Vec<(PathBuf, String)> {
+        let mut files: Vec<(PathBuf, String)> = ignore::Walk::new(dir)
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_file() && entry.file_name() != MANIFEST_FILE_NAME)
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(dir).unwrap().to_path_buf();
+                (relative, fs::read_to_string(entry.path()).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn importing_several_apkgs_dedupes_across_files() {
+        let fixture =
+            PathBuf::from("test_data/United_Kingdom_UK_Geography_Regions_Counties_and_Cities.apkg");
+        let apkg_dir = tempfile::tempdir().unwrap();
+        let first = apkg_dir.path().join("a.apkg");
+        let second = apkg_dir.path().join("b.apkg");
+        fs::copy(&fixture, &first).unwrap();
+        fs::copy(&fixture, &second).unwrap();
+        let db = DB::new_in_memory().await.unwrap();
+
+        let single_dir = tempfile::tempdir().unwrap();
+        run(
+            &db,
+            &ImportSource::Apkgs(vec![first.clone()]),
+            single_dir.path(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let both_dir = tempfile::tempdir().unwrap();
+        run(
+            &db,
+            &ImportSource::Apkgs(vec![first, second]),
+            both_dir.path(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let from_dir = tempfile::tempdir().unwrap();
+        run(
+            &db,
+            &ImportSource::Apkgs(vec![apkg_dir.path().to_path_buf()]),
+            from_dir.path(),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Every note in the second file duplicates one in the first, so the
+        // merged tree is exactly what importing one copy produces.
+        let single = read_tree(single_dir.path());
+        assert!(!single.is_empty());
+        assert_eq!(read_tree(both_dir.path()), single);
+        assert_eq!(read_tree(from_dir.path()), single);
+
+        let manifest = |dir: &Path| -> Value {
+            serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE_NAME)).unwrap())
+                .unwrap()
+        };
+        let skipped = |manifest: &Value| -> usize {
+            manifest["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|file| file["skipped_duplicates"].as_array().unwrap().len())
+                .sum()
+        };
+        let kept: u64 = manifest(single_dir.path())["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["cards"].as_u64().unwrap())
+            .sum();
+        // Every note of the second file, kept or skipped in the first, is
+        // recorded as a skipped duplicate.
+        let single_skipped = skipped(&manifest(single_dir.path()));
+        assert_eq!(
+            skipped(&manifest(both_dir.path())),
+            2 * single_skipped + kept as usize
+        );
+    }
+
+    #[test]
+    fn shared_hashes_dedupe_cards_seen_in_an_earlier_file() {
+        let models = basic_models();
+        let mut seen = SeenNotes::default();
+        let (_, first) = build_exports(
+            vec![record(1, "Shared"), record(1, "Only first")],
+            &models,
+            None,
+            &mut seen,
+            &mut Progress::default(),
+        );
+        let (exports, second) = build_exports(
+            vec![record(1, "Shared"), record(1, "Only second")],
+            &models,
+            None,
+            &mut seen,
+            &mut Progress::default(),
+        );
+        assert_eq!(first.kept, 2);
+        assert_eq!(second.kept, 1);
+        assert_eq!(second.duplicates, 1);
+        assert!(exports[&1][0].contains("Only second"));
+    }
+
+    #[test]
+    fn merge_decks_reuses_ids_for_decks_with_the_same_name() {
+        let mut decks = HashMap::new();
+        merge_decks(&mut decks, HashMap::from([(1, deck("Geo"))]), Vec::new());
+
+        let cards = merge_decks(
+            &mut decks,
+            HashMap::from([(1, deck("History")), (7, deck("Geo"))]),
+            vec![record(1, "Paris"), record(7, "Rome")],
+        );
+        assert_eq!(decks.len(), 2);
+        assert_eq!(decks[&1].name, "Geo");
+        assert_eq!(decks[&2].name, "History");
+        assert_eq!(cards[0].deck_id, 2);
+        assert_eq!(cards[1].deck_id, 1);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod bundle;
 pub mod card;
 pub mod check_version;
 pub mod cloze_utils;
@@ -8,6 +9,8 @@ pub mod import;
 pub mod llm;
 pub mod palette;
 pub mod parser;
+pub mod scheduler;
+pub mod shutdown;
 pub mod stats;
 pub mod tui;
 pub mod utils;
@@ -1,20 +1,54 @@
-use crate::llm::secrets::API_KEY_ENV;
+use std::io::{self, IsTerminal};
+
+use crate::llm::secrets::{API_KEY_ENV, OPENAI_API_KEY_ENV};
 use crate::utils::ask_yn;
 use anyhow::{Context, Result, anyhow, bail};
 
 use async_openai::{Client, config::OpenAIConfig};
 
 use super::secrets::{
-    ApiKeySource, get_api_key_from_sources, prompt_for_api_key, store_api_key,
+    ApiKeyLookup, ApiKeySource, get_api_key_from_sources, prompt_for_api_key, store_api_key,
     store_api_key_with_entry,
 };
 
 pub fn ensure_client(user_prompt: &str) -> Result<Client<OpenAIConfig>> {
     let lookup = get_api_key_from_sources()?;
+    ensure_client_with(
+        user_prompt,
+        lookup,
+        io::stdin().is_terminal(),
+        prompt_for_api_key,
+        ask_yn,
+    )
+}
+
+/// Resolves the API key and builds a client. A key from the environment is
+/// treated as consent and never prompts; without a TTY we refuse to block on
+/// stdin and return an error instead.
+fn ensure_client_with<P, C>(
+    user_prompt: &str,
+    lookup: ApiKeyLookup,
+    interactive: bool,
+    prompt_key: P,
+    confirm: C,
+) -> Result<Client<OpenAIConfig>>
+where
+    P: FnOnce(&str) -> Result<String>,
+    C: FnOnce(String) -> bool,
+{
+    let from_env = lookup.source == Some(ApiKeySource::Environment);
+    if !interactive && !from_env {
+        bail!(
+            "No interactive terminal to confirm LLM usage. Set {} or {} to run non-interactively.",
+            API_KEY_ENV,
+            OPENAI_API_KEY_ENV
+        );
+    }
+
     let (key, prompted_for_key) = if let Some(api_key) = lookup.api_key {
         (api_key, false)
     } else {
-        let api_key = prompt_for_api_key(user_prompt)?;
+        let api_key = prompt_key(user_prompt)?;
         if api_key.is_empty() {
             bail!(
                 "No API key provided. Set {} or run `repeater llm key --set <KEY>`.",
@@ -31,9 +65,9 @@ pub fn ensure_client(user_prompt: &str) -> Result<Client<OpenAIConfig>> {
         (api_key, true)
     };
 
-    // If we didn't prompt for the API key (it already existed), confirm with the user
-    if !prompted_for_key {
-        let ok = ask_yn(user_prompt.to_string());
+    // If we didn't prompt for the API key (it came from the keyring), confirm with the user
+    if !prompted_for_key && !from_env {
+        let ok = confirm(user_prompt.to_string());
         if !ok {
             bail!("LLM client not initialized.");
         }
@@ -77,3 +111,41 @@ async fn healthcheck_client(client: &Client<OpenAIConfig>) -> Result<()> {
         .context("Failed to validate API key with OpenAI")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(source: Option<ApiKeySource>, api_key: Option<&str>) -> ApiKeyLookup {
+        ApiKeyLookup {
+            api_key: api_key.map(str::to_string),
+            source,
+            keyring_entry: None,
+        }
+    }
+
+    #[test]
+    fn env_key_builds_client_without_prompting() {
+        let client = ensure_client_with(
+            "prompt",
+            lookup(Some(ApiKeySource::Environment), Some("sk-test")),
+            false,
+            |_| panic!("should not prompt for a key"),
+            |_| panic!("should not ask for confirmation"),
+        );
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn missing_key_without_tty_errors_instead_of_blocking() {
+        let err = ensure_client_with(
+            "prompt",
+            lookup(None, None),
+            false,
+            |_| panic!("should not prompt for a key"),
+            |_| panic!("should not ask for confirmation"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains(OPENAI_API_KEY_ENV));
+    }
+}
@@ -8,10 +8,18 @@ use anyhow::{Result, bail};
 use keyring::{Entry, Error as KeyringError};
 
 pub const API_KEY_ENV: &str = "REPEATER_OPENAI_API_KEY";
+/// Conventional OpenAI variable, honored after [`API_KEY_ENV`] so CI and
+/// scripts can reuse an existing key without touching the keyring.
+pub const OPENAI_API_KEY_ENV: &str = "OPENAI_API_KEY";
 
 const SERVICE: &str = "com.repeater";
 const USERNAME: &str = "openai:default";
 
+/// Where `llm --set` stores the key, as `service/user`.
+pub fn keyring_entry() -> String {
+    format!("{SERVICE}/{USERNAME}")
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ApiKeySource {
     Environment,
@@ -78,16 +86,25 @@ pub fn store_api_key_with_entry(entry: Entry, api_key: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn get_api_key_from_sources() -> Result<ApiKeyLookup> {
-    // 1. Environment variable
-    if let Ok(value) = env::var(API_KEY_ENV)
-        && !value.trim().is_empty()
-    {
-        return Ok(ApiKeyLookup {
+fn api_key_from_env<F>(get_var: F) -> Option<ApiKeyLookup>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    [API_KEY_ENV, OPENAI_API_KEY_ENV]
+        .into_iter()
+        .filter_map(get_var)
+        .find(|value| !value.trim().is_empty())
+        .map(|value| ApiKeyLookup {
             api_key: Some(value),
             source: Some(ApiKeySource::Environment),
             keyring_entry: None,
-        });
+        })
+}
+
+pub fn get_api_key_from_sources() -> Result<ApiKeyLookup> {
+    // 1. Environment variables
+    if let Some(lookup) = api_key_from_env(|name| env::var(name).ok()) {
+        return Ok(lookup);
     }
 
     // 2. Keyring
@@ -106,3 +123,33 @@ pub fn get_api_key_from_sources() -> Result<ApiKeyLookup> {
         Err(err) => bail!(err),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_lookup_prefers_repeater_variable() {
+        let lookup = api_key_from_env(|name| match name {
+            API_KEY_ENV => Some("repeater-key".into()),
+            OPENAI_API_KEY_ENV => Some("openai-key".into()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(lookup.api_key.as_deref(), Some("repeater-key"));
+        assert_eq!(lookup.source, Some(ApiKeySource::Environment));
+    }
+
+    #[test]
+    fn env_lookup_falls_back_to_openai_variable() {
+        let lookup = api_key_from_env(|name| match name {
+            API_KEY_ENV => Some("   ".into()),
+            OPENAI_API_KEY_ENV => Some("openai-key".into()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(lookup.api_key.as_deref(), Some("openai-key"));
+
+        assert!(api_key_from_env(|_| None).is_none());
+    }
+}
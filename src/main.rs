@@ -3,9 +3,17 @@ use std::path::PathBuf;
 use anyhow::{Context, Result, bail};
 use clap::{Parser, Subcommand, ValueHint};
 
-use repeater::commands::{check, create, drill};
+use repeater::bundle::PackOnly;
+use repeater::commands::check::{CheckFormat, CheckOptions};
+use repeater::commands::drill::{DrillOptions, DrillOrder, DueMode, LimitCounts};
+use repeater::commands::{
+    check, create, doctor, drill, find, fix, grade, hash, locate, merge, prune, reset, tags, undo,
+    validate,
+};
 use repeater::crud::DB;
-use repeater::{import, llm};
+use repeater::fsrs::ReviewStatus;
+use repeater::import::{ImportSource, ProgressFormat};
+use repeater::{bundle, import, llm, shutdown};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -38,12 +46,72 @@ enum Command {
         /// Maximum number of cards to drill in a session. By default, all cards due today are drilled.
         #[arg(long, value_name = "COUNT")]
         card_limit: Option<usize>,
-        /// Maximum number of new cards to drill in a session.
+        /// Whether `--card-limit` counts distinct cards, or every card shown including failed ones shown again.
+        #[arg(long, value_enum, default_value_t = LimitCounts::Cards, requires = "card_limit")]
+        limit_counts: LimitCounts,
+        /// Maximum number of new cards to introduce per day, counting ones already seen today.
         #[arg(long, value_name = "COUNT")]
         new_card_limit: Option<usize>,
         /// Rephrase  card questions via the LLM helper before the session starts.
         #[arg(long = "rephrase", default_value_t = false)]
         rephrase_questions: bool,
+        /// Let the 1 (fail) and 2 (pass) keys grade a card before its answer is shown.
+        #[arg(long, default_value_t = false)]
+        grade_before_reveal: bool,
+        /// Drill each card once: failed cards wait for their next due date instead of coming back this session.
+        #[arg(long, default_value_t = false)]
+        no_redo: bool,
+        /// Drill in plain text, one card at a time on stdout, answering by typing a line; works with screen readers.
+        #[arg(long, default_value_t = false, conflicts_with = "images")]
+        no_tui: bool,
+        /// Fail on unreadable files instead of skipping them with a warning.
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// Read deck files that aren't valid UTF-8, replacing the bad bytes and warning.
+        #[arg(long, default_value_t = false)]
+        lossy: bool,
+        /// Order in which due cards are drilled.
+        #[arg(long, value_enum, default_value_t = DrillOrder::Due)]
+        order: DrillOrder,
+        /// With `--order weakest` or `overdue`, show new cards before reviewed ones instead of after.
+        #[arg(long, default_value_t = false)]
+        new_first: bool,
+        /// Take due cards from each deck directory in turn, so a capped session covers them all.
+        #[arg(long, default_value_t = false)]
+        round_robin: bool,
+        /// Draw inline images with the kitty, iTerm2 or sixel graphics protocol when available.
+        #[arg(long, default_value_t = false)]
+        images: bool,
+        /// Whether a reviewed card is due by its date or by its predicted recall.
+        #[arg(long, value_enum, default_value_t = DueMode::Date)]
+        due_mode: DueMode,
+        /// Also drill cards due within this long, e.g. `2d` or `12h`, to get ahead.
+        #[arg(long, value_name = "DURATION", value_parser = parse_ahead)]
+        ahead: Option<chrono::Duration>,
+        /// With `--due-mode retrievability`, drill cards whose recall is below this (default 0.9).
+        #[arg(long, value_name = "RECALL", value_parser = parse_retention)]
+        retention: Option<f64>,
+        /// Show each cloze's hidden term and recall the surrounding text instead.
+        #[arg(long, default_value_t = false)]
+        reverse_clozes: bool,
+        /// Notice cards edited in place since the last `--track-edits` run and offer to keep their history.
+        #[arg(long, default_value_t = false)]
+        track_edits: bool,
+        /// Print the total, average, and slowest deck file parse times.
+        #[arg(long, default_value_t = false)]
+        verbose: bool,
+        /// Keep the card in a centered column at most this many characters wide.
+        #[arg(long, value_name = "COLUMNS", value_parser = clap::value_parser!(u16).range(1..))]
+        max_width: Option<u16>,
+        /// Start the session at the due card with this hash, or the first one containing this text.
+        #[arg(long, value_name = "HASH_OR_QUERY")]
+        start_at: Option<String>,
+        /// Show each card's full file path in the header instead of its path under the drilled folder.
+        #[arg(long, default_value_t = false)]
+        full_path: bool,
+        /// Keep drilling due cards until this much time has passed, e.g. `15m` or `1h`.
+        #[arg(long = "time", value_name = "DURATION", value_parser = parse_time_budget)]
+        time_budget: Option<std::time::Duration>,
     },
     /// Re-index decks and show collection stats
     Check {
@@ -54,21 +122,264 @@ enum Command {
             value_hint = ValueHint::AnyPath
         )]
         paths: Vec<PathBuf>,
+        /// Fail on unreadable files instead of skipping them with a warning.
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// Read deck files that aren't valid UTF-8, replacing the bad bytes and warning.
+        #[arg(long, default_value_t = false)]
+        lossy: bool,
+        /// Print the stats as plain text, a table, or JSON instead of opening the dashboard.
+        #[arg(long, value_enum)]
+        format: Option<CheckFormat>,
+        /// List cards in the DB that no longer match any card file, with their last review.
+        #[arg(long, default_value_t = false)]
+        list_ghosts: bool,
+        /// Print the cards `drill` would show, in order, instead of the stats.
+        #[arg(long, default_value_t = false, conflicts_with = "format")]
+        plan: bool,
+        /// Print a heatmap of reviews per day over the last 12 weeks instead of the stats.
+        #[arg(long, default_value_t = false, conflicts_with_all = ["format", "plan"])]
+        heatmap: bool,
+        /// Print the stored schedule of the cards matching this hash or text, and why they are due.
+        #[arg(long, value_name = "HASH_OR_QUERY", conflicts_with_all = ["format", "plan", "heatmap"])]
+        explain: Option<String>,
+        /// With `--plan`: same as `drill --card-limit`.
+        #[arg(long, value_name = "COUNT", requires = "plan")]
+        card_limit: Option<usize>,
+        /// With `--plan`: same as `drill --new-card-limit`.
+        #[arg(long, value_name = "COUNT", requires = "plan")]
+        new_card_limit: Option<usize>,
+        /// With `--plan`: same as `drill --order`.
+        #[arg(long, value_enum, default_value_t = DrillOrder::Due, requires = "plan")]
+        order: DrillOrder,
+        /// With `--plan`: same as `drill --new-first`.
+        #[arg(long, default_value_t = false, requires = "plan")]
+        new_first: bool,
+        /// With `--plan`: same as `drill --round-robin`.
+        #[arg(long, default_value_t = false, requires = "plan")]
+        round_robin: bool,
+        /// Notice cards edited in place since the last `--track-edits` run and offer to keep their history.
+        #[arg(long, default_value_t = false)]
+        track_edits: bool,
+        /// Print the total, average, and slowest deck file parse times.
+        #[arg(long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// Grade a single card without opening the drill UI
+    Grade {
+        /// `file.md:LINE`, a file with one card, a card hash, or text found in exactly one card
+        #[arg(value_name = "TARGET")]
+        target: String,
+        /// `fail`, `hard`, `pass`, or `easy`
+        #[arg(value_name = "GRADE")]
+        review_status: ReviewStatus,
+        /// Where to search when TARGET is text or a hash
+        #[arg(
+            long = "in",
+            value_name = "PATHS",
+            num_args = 1..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Delete DB rows for cards that no longer exist in any card file
+    Prune {
+        /// Your whole collection: cards outside these paths count as deleted
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// List the cards that would be deleted without deleting them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Go ahead without asking; required when not running in a terminal.
+        #[arg(short, long, default_value_t = false, conflicts_with = "dry_run")]
+        yes: bool,
+    },
+    /// Pick cloze cards missing a [deletion] and let the LLM helper add one in place
+    Fix {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Send every malformed cloze without the checklist (also set by REPEATER_AUTO_CLOZE=1).
+        #[arg(long, default_value_t = false)]
+        auto: bool,
+        /// Print the rewrites the LLM suggests without changing any file.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Delete extra copies of cards that appear more than once in card files
+    Merge {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// List the copies that would be deleted without changing any file.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Go ahead without asking; required when not running in a terminal.
+        #[arg(short, long, default_value_t = false, conflicts_with = "dry_run")]
+        yes: bool,
+    },
+    /// Forget the review progress of cards so they are new again
+    Reset {
+        /// Cards or directories whose progress to reset
+        #[arg(value_name = "PATHS", num_args = 1.., required = true, value_hint = ValueHint::AnyPath)]
+        paths: Vec<PathBuf>,
+        /// List the cards that would be reset without resetting them.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Go ahead without asking; required when not running in a terminal.
+        #[arg(short, long, default_value_t = false, conflicts_with = "dry_run")]
+        yes: bool,
+    },
+    /// Take back the last reviews, restoring each card's previous schedule
+    UndoLast {
+        /// How many of the most recent reviews to undo.
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        count: u16,
     },
     /// Create or append to a card
     Create {
         /// Card path
         #[arg(value_name = "PATH", value_hint = ValueHint::FilePath)]
         path: PathBuf,
+        /// Start with a basic card whose question is this text.
+        #[arg(long, value_name = "TEXT", conflicts_with = "cloze")]
+        front: Option<String>,
+        /// Start with a basic card whose answer is this text.
+        #[arg(long, value_name = "TEXT", conflicts_with = "cloze")]
+        back: Option<String>,
+        /// Start with a cloze card holding this text, e.g. "Paris is in [France]".
+        #[arg(long, value_name = "TEXT")]
+        cloze: Option<String>,
+        /// Keep the editor in a centered column at most this many characters wide.
+        #[arg(long, value_name = "COLUMNS", value_parser = clap::value_parser!(u16).range(1..))]
+        max_width: Option<u16>,
+        /// Save the unsaved card next to the deck every this many seconds, and on exit, to restore next time.
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+        autosave: Option<u64>,
     },
     /// Import from Anki
     Import {
-        /// Anki export path. Must be an apkg file
-        #[arg(value_name = "PATH", value_hint = ValueHint::FilePath)]
-        anki_path: PathBuf,
-        /// Directory to export to
-        #[arg(value_name = "PATH", value_hint = ValueHint::AnyPath)]
-        export_path: PathBuf,
+        /// Anki exports (apkg files, or directories containing them), then the
+        /// directory to export to. With `--ankiconnect`, only the directory.
+        #[arg(
+            value_name = "PATHS",
+            num_args = 1..,
+            required = true,
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Stop after writing this many cards in total, useful for sampling a large deck.
+        #[arg(long, value_name = "COUNT")]
+        count: Option<usize>,
+        /// Also write a JSON summary of what was found and written to this file.
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        report: Option<PathBuf>,
+        /// Print progress events to stderr in this format, for scripts that wrap the import.
+        #[arg(long, value_name = "FORMAT")]
+        progress: Option<ProgressFormat>,
+        /// Read notes from a running Anki through the AnkiConnect add-on instead of apkg files.
+        #[arg(long, default_value_t = false, requires = "deck")]
+        ankiconnect: bool,
+        /// With `--ankiconnect`: the Anki deck to import, including its subdecks.
+        #[arg(long, value_name = "NAME", requires = "ankiconnect")]
+        deck: Option<String>,
+    },
+    /// Bundle the markdown files and media under a directory into a zip
+    Pack {
+        /// Directory containing the deck
+        #[arg(value_name = "DIR", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// Zip file to write
+        #[arg(value_name = "OUT", value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Keep only due or only new cards, by their review history in the database.
+        #[arg(long, value_enum)]
+        only: Option<PackOnly>,
+    },
+    /// Restore a bundle created by `pack`
+    Unpack {
+        /// Zip file created by `pack`
+        #[arg(value_name = "ZIP", value_hint = ValueHint::FilePath)]
+        archive: PathBuf,
+        /// Directory to restore into
+        #[arg(value_name = "DIR", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+    /// Search card text for a fuzzy match and print where each card lives
+    Find {
+        /// Text to look for; its characters may be spread out in the card
+        query: String,
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Most matches to print.
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// List every card tag with how many cards use it
+    Tags {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Check that every card parses, without opening the database; exits nonzero on errors
+    Validate {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Read deck files that aren't valid UTF-8, replacing the bad bytes and warning.
+        #[arg(long, default_value_t = false)]
+        lossy: bool,
+    },
+    /// Print the hash a card with this text would get, to debug duplicate detection
+    Hash {
+        /// Text to hash; read from stdin when omitted
+        #[arg(value_name = "TEXT")]
+        text: Option<String>,
+        /// Also print the normalized words that feed the hash
+        #[arg(long, default_value_t = false)]
+        tokens: bool,
+    },
+    /// Print where the database, config, and API key are stored
+    Where,
+    /// Open the data directory in the file manager
+    Open,
+    /// Diagnose common setup problems without changing anything
+    Doctor {
+        /// Paths that should contain cards
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
     },
     /// Manage LLM helper settings
     Llm {
@@ -94,36 +405,254 @@ async fn main() {
 
 async fn run_cli() -> Result<()> {
     let cli = Cli::parse();
+    // Opening the database creates and migrates it, so these have to run first.
+    match cli.command {
+        Command::Doctor { paths } => return doctor::run(paths).await,
+        Command::Hash { text, tokens } => return hash::run(text, tokens),
+        Command::Where => return locate::print_where(),
+        Command::Open => return locate::open_data_dir(),
+        Command::Tags { paths } => return tags::run(paths),
+        Command::Find {
+            query,
+            paths,
+            limit,
+        } => return find::run(&query, paths, limit),
+        Command::Validate { paths, lossy } => return validate::run(paths, lossy),
+        Command::Merge {
+            paths,
+            dry_run,
+            yes,
+        } => return merge::run(paths, dry_run, yes),
+        Command::Fix {
+            paths,
+            auto,
+            dry_run,
+        } => return fix::run(paths, auto, dry_run).await,
+        _ => {}
+    }
     let db = DB::new().await?;
+    shutdown::spawn_signal_handler(db.clone());
 
     match cli.command {
         Command::Drill {
             paths,
             card_limit,
+            limit_counts,
             new_card_limit,
             rephrase_questions,
+            grade_before_reveal,
+            no_redo,
+            no_tui,
+            strict,
+            lossy,
+            order,
+            new_first,
+            round_robin,
+            images,
+            due_mode,
+            ahead,
+            retention,
+            reverse_clozes,
+            track_edits,
+            verbose,
+            max_width,
+            start_at,
+            full_path,
+            time_budget,
         } => {
-            drill::run(&db, paths, card_limit, new_card_limit, rephrase_questions).await?;
+            if ahead.is_some() && due_mode == DueMode::Retrievability {
+                bail!("--ahead only works with --due-mode date");
+            }
+            let options = DrillOptions {
+                card_limit,
+                limit_counts,
+                new_card_limit,
+                rephrase_questions,
+                grade_before_reveal,
+                no_redo,
+                no_tui,
+                strict,
+                lossy,
+                order,
+                new_first,
+                round_robin,
+                images,
+                due_mode,
+                ahead,
+                retention,
+                reverse_clozes,
+                track_edits,
+                verbose,
+                max_width,
+                start_at,
+                full_path,
+                time_budget,
+            };
+            drill::run(&db, paths, options).await?;
         }
-        Command::Check { paths } => {
-            let _ = check::run(&db, paths).await?;
+        Command::Check {
+            paths,
+            strict,
+            lossy,
+            format,
+            list_ghosts,
+            plan,
+            heatmap,
+            explain,
+            card_limit,
+            new_card_limit,
+            order,
+            new_first,
+            round_robin,
+            track_edits,
+            verbose,
+        } => {
+            let plan = plan.then(|| DrillOptions {
+                card_limit,
+                new_card_limit,
+                strict,
+                order,
+                new_first,
+                round_robin,
+                ..Default::default()
+            });
+            let options = CheckOptions {
+                strict,
+                lossy,
+                format,
+                list_ghosts,
+                plan,
+                heatmap,
+                explain,
+                verbose,
+                track_edits,
+            };
+            let _ = check::run(&db, paths, options).await?;
+        }
+        Command::Grade {
+            target,
+            review_status,
+            paths,
+        } => {
+            grade::run(&db, &target, review_status, paths).await?;
         }
-        Command::Create { path } => {
-            create::run(&db, path).await?;
+        Command::Prune {
+            paths,
+            dry_run,
+            yes,
+        } => prune::run(&db, paths, dry_run, yes).await?,
+        Command::Reset {
+            paths,
+            dry_run,
+            yes,
+        } => reset::run(&db, paths, dry_run, yes).await?,
+        Command::UndoLast { count } => undo::run(&db, usize::from(count)).await?,
+        Command::Create {
+            path,
+            front,
+            back,
+            cloze,
+            max_width,
+            autosave,
+        } => {
+            let prefill = create::Prefill { front, back, cloze };
+            let autosave = autosave.map(std::time::Duration::from_secs);
+            create::run(&db, path, max_width, prefill, autosave).await?;
         }
         Command::Import {
-            anki_path,
-            export_path,
+            mut paths,
+            count,
+            report,
+            progress,
+            ankiconnect: _,
+            deck,
         } => {
-            import::run(&db, &anki_path, &export_path)
+            let export_path = paths.pop().expect("clap requires at least one path");
+            let source = match deck {
+                Some(_) if !paths.is_empty() => {
+                    bail!("--ankiconnect reads from Anki; pass only the directory to export to")
+                }
+                Some(deck) => ImportSource::AnkiConnect { deck },
+                None if paths.is_empty() => {
+                    bail!("Pass one or more apkg files before the directory to export to")
+                }
+                None => ImportSource::Apkgs(paths),
+            };
+            import::run(&db, &source, &export_path, count, report.as_deref(), progress)
                 .await.with_context(|| "Importing from Anki is a work in progress, please report issues on https://github.com/shaankhosla/repeater")?
+        }
+        Command::Pack { dir, out, only } => match only {
+            Some(only) => bundle::pack_only(&db, &dir, &out, only).await?,
+            None => bundle::pack(&dir, &out)?,
         },
+        Command::Unpack { archive, dir } => bundle::unpack(&archive, &dir)?,
+        Command::Doctor { .. }
+        | Command::Tags { .. }
+        | Command::Find { .. }
+        | Command::Validate { .. }
+        | Command::Merge { .. }
+        | Command::Fix { .. }
+        | Command::Hash { .. }
+        | Command::Where
+        | Command::Open => {
+            unreachable!("handled before the database is opened")
+        }
         Command::Llm { set, clear, test } => handle_llm_command(set, clear, test).await?,
     }
 
+    db.close().await;
     Ok(())
 }
 
+fn parse_retention(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(value) if value > 0.0 && value < 1.0 => Ok(value),
+        _ => Err(format!(
+            "expected a recall between 0 and 1, e.g. 0.85, got `{raw}`"
+        )),
+    }
+}
+
+/// How far `--ahead` may reach: a year already takes in nearly every card.
+const MAX_AHEAD_DAYS: i32 = 365;
+
+fn parse_ahead(raw: &str) -> Result<chrono::Duration, String> {
+    let error = || {
+        format!(
+            "expected a number of days or hours up to {MAX_AHEAD_DAYS}d, e.g. 2d or 12h, got `{raw}`"
+        )
+    };
+    let raw_trimmed = raw.trim();
+    let (amount, unit, max) = match raw_trimmed.strip_suffix('h') {
+        Some(hours) => (hours, chrono::Duration::hours(1), MAX_AHEAD_DAYS * 24),
+        None => (
+            raw_trimmed.strip_suffix('d').unwrap_or(raw_trimmed),
+            chrono::Duration::days(1),
+            MAX_AHEAD_DAYS,
+        ),
+    };
+    match amount.parse::<i32>() {
+        Ok(amount) if (1..=max).contains(&amount) => Ok(unit * amount),
+        _ => Err(error()),
+    }
+}
+
+fn parse_time_budget(raw: &str) -> Result<std::time::Duration, String> {
+    let error = || format!("expected a number of minutes or hours, e.g. 15m or 1h, got `{raw}`");
+    let raw_trimmed = raw.trim();
+    let (amount, unit_secs) = match raw_trimmed.strip_suffix('h') {
+        Some(hours) => (hours, 60 * 60),
+        None => (raw_trimmed.strip_suffix('m').unwrap_or(raw_trimmed), 60),
+    };
+    match amount.parse::<u64>() {
+        Ok(amount) if amount > 0 => amount
+            .checked_mul(unit_secs)
+            .map(std::time::Duration::from_secs)
+            .ok_or_else(error),
+        _ => Err(error()),
+    }
+}
+
 async fn handle_llm_command(set: Option<String>, clear: bool, test: bool) -> Result<()> {
     let mut action_taken = false;
 
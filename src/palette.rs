@@ -2,7 +2,7 @@ use std::fmt;
 
 use ratatui::style::Color;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct PaletteColor {
     tui: Color,
     ansi: &'static str,
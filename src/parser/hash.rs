@@ -14,7 +14,9 @@ use blake3::Hasher;
 // Symbols
 // Anything semantic
 
-pub fn get_hash(s: &str) -> Option<String> {
+/// The text `get_hash` actually hashes: lowercased, with every run of
+/// whitespace collapsed to one space and the ends trimmed.
+pub fn normalize_for_hash(s: &str) -> String {
     let lower = s.to_lowercase();
 
     let mut collapsed = String::with_capacity(lower.len());
@@ -32,14 +34,18 @@ pub fn get_hash(s: &str) -> Option<String> {
         }
     }
 
-    let trimmed = collapsed.trim();
+    collapsed.trim().to_string()
+}
+
+pub fn get_hash(s: &str) -> Option<String> {
+    let normalized = normalize_for_hash(s);
 
-    if trimmed.is_empty() {
+    if normalized.is_empty() {
         return None;
     }
 
     let mut hasher = Hasher::new();
-    hasher.update(trimmed.as_bytes());
+    hasher.update(normalized.as_bytes());
 
     Some(hasher.finalize().to_string())
 }
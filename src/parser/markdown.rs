@@ -10,6 +10,7 @@ pub fn render_markdown(md: &str) -> Text<'static> {
     let mut list_stack: Vec<ListKind> = Vec::new();
     let mut pending_prefix: Option<String> = None;
     let mut in_code_block = false;
+    let mut image_dest: Option<String> = None;
 
     for event in parser {
         match event {
@@ -28,6 +29,11 @@ pub fn render_markdown(md: &str) -> Text<'static> {
                 Tag::Link { .. } => push_style(&mut styles, |style| {
                     style.add_modifier(Modifier::UNDERLINED)
                 }),
+                // The alt text follows as regular text events; the path is added on close.
+                Tag::Image { dest_url, .. } => {
+                    image_dest = Some(dest_url.to_string());
+                    push_style(&mut styles, |style| style.add_modifier(Modifier::ITALIC));
+                }
                 Tag::CodeBlock(_) => {
                     flush_line(&mut lines, &mut current_line);
                     in_code_block = true;
@@ -63,6 +69,16 @@ pub fn render_markdown(md: &str) -> Text<'static> {
                 TagEnd::Strong | TagEnd::Emphasis | TagEnd::BlockQuote(_) | TagEnd::Link => {
                     pop_style(&mut styles);
                 }
+                TagEnd::Image => {
+                    pop_style(&mut styles);
+                    if let Some(dest) = image_dest.take() {
+                        maybe_apply_prefix(&mut current_line, &mut pending_prefix);
+                        current_line.push(Span::styled(
+                            format!(" ({dest})"),
+                            Style::default().add_modifier(Modifier::DIM),
+                        ));
+                    }
+                }
                 TagEnd::List(_) => {
                     flush_line(&mut lines, &mut current_line);
                     list_stack.pop();
@@ -701,6 +717,17 @@ mod tests {
         assert!(text.lines[3].spans.is_empty());
     }
 
+    #[test]
+    fn images_fall_back_to_alt_text_and_path() {
+        let text = render_markdown("![a dog](media/dog.png)");
+        let line: String = text.lines[0]
+            .spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(line, "a dog (media/dog.png)");
+    }
+
     #[test]
     fn converts_latex_math_to_unicode() {
         let rendered = latex_to_unicode_math(r"\int_0^\infty e^{-x^2} dx");
@@ -19,6 +19,14 @@ pub struct Media {
 }
 
 impl Media {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &MediaKind {
+        &self.kind
+    }
+
     pub fn play(&self) -> Result<()> {
         if !self.path.is_file() || !self.path.exists() {
             bail!("File does not exist: {}", self.path.display());
@@ -90,10 +98,8 @@ pub fn extract_media(markdown: &str, base_dir: Option<&Path>) -> Vec<Media> {
                 }
             }
 
-            Event::Text(text) => {
-                if current_path.is_some() {
-                    current_label.push_str(&text);
-                }
+            Event::Text(text) if current_path.is_some() => {
+                current_label.push_str(&text);
             }
 
             Event::End(TagEnd::Link) => {
@@ -3,7 +3,11 @@ pub mod markdown;
 pub mod media;
 pub mod parse_from_file;
 
-pub use hash::get_hash;
+pub use hash::{get_hash, normalize_for_hash};
 pub use markdown::render_markdown;
 pub use media::{Media, MediaKind, extract_media};
-pub use parse_from_file::{FileSearchStats, cards_from_md, content_to_card, register_all_cards};
+pub use parse_from_file::{
+    CollectOptions, FileSearchStats, ParseTiming, STDIN_LABEL, ValidationReport, card_hash_source,
+    cards_from_md, collect_cards, content_to_card, find_card_errors, register_all_cards,
+    scan_cards,
+};
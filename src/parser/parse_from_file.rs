@@ -1,36 +1,198 @@
 use crate::cloze_utils::find_cloze_ranges;
 use ignore::WalkBuilder;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::path::{Path, PathBuf};
 
-use crate::card::{Card, CardContent, ClozeRange};
+use crate::card::{Card, CardContent, CardDirectives, ClozeRange};
 use crate::parser::get_hash;
-use crate::utils::{is_markdown, trim_line};
+use crate::utils::{has_extension, markdown_extensions, pluralize, trim_line};
 use ignore::WalkState;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
 use crate::crud::DB;
+use crate::crud::positions::EditedCard;
 
-use anyhow::{Result, anyhow, bail};
+use anyhow::{Context, Result, anyhow, bail};
+use once_cell::sync::Lazy;
 
 #[derive(Default, Clone, Debug)]
 pub struct FileSearchStats {
     pub files_searched: usize,
     pub markdown_files: usize,
+    pub skipped_files: usize,
+    /// Deck files with text but no `Q:`/`A:`/`C:` cards, sorted by path.
+    pub files_without_cards: Vec<PathBuf>,
+    /// How long each deck file took to parse; only filled with `CollectOptions::verbose`.
+    pub parse_timings: Vec<ParseTiming>,
+    /// Cards that look like in-place edits of a reviewed card; only filled with
+    /// `CollectOptions::track_edits`. The caller decides whether each keeps the
+    /// old history.
+    pub edited_cards: Vec<EditedCard>,
+    /// One warning per card repeated within its own file, sorted by path.
+    pub duplicate_cards: Vec<String>,
 }
 
-fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<String>) {
-    #[derive(Copy, Clone)]
-    enum Section {
-        Question,
-        Answer,
-        Cloze,
-        None,
+/// Time spent parsing one deck file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseTiming {
+    pub path: PathBuf,
+    pub duration: Duration,
+    pub cards: usize,
+}
+
+/// Slowest files listed by `--verbose`.
+const SLOWEST_FILES_SHOWN: usize = 10;
+
+/// Knobs for how `register_all_cards` walks and reads deck files.
+#[derive(Default, Clone, Debug)]
+pub struct CollectOptions {
+    /// Abort on the first unreadable file instead of warning and skipping it.
+    pub strict: bool,
+    /// Deck file extensions; `None` uses `REPEATER_EXTENSIONS` or the defaults.
+    pub extensions: Option<Vec<String>>,
+    /// Threads used to walk and parse files; `None` lets the walker decide and
+    /// `Some(1)` parses sequentially. Results are ordered the same either way.
+    pub threads: Option<usize>,
+    /// Time every file and print the slowest ones to stderr.
+    pub verbose: bool,
+    /// Recognise cards edited in place since the last tracked run and offer
+    /// to carry their review history over to the new text.
+    pub track_edits: bool,
+    /// Replace invalid UTF-8 with U+FFFD and warn instead of failing the file.
+    pub lossy: bool,
+}
+
+#[derive(Copy, Clone)]
+enum Section {
+    Question,
+    Answer,
+    Cloze,
+    None,
+}
+
+/// Names to accept for the question, answer and cloze sections alongside
+/// `Q:`, `A:` and `C:`, e.g. `Front`, `Back`, `Text`. Each is stored with its
+/// trailing colon.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionLabels {
+    question: String,
+    answer: String,
+    cloze: String,
+}
+
+impl SectionLabels {
+    /// Parses `Front,Back,Text`: three distinct, non-empty names.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let names: Vec<&str> = raw.split(',').map(str::trim).collect();
+        let [question, answer, cloze] = names.as_slice() else {
+            return None;
+        };
+        let valid = |name: &str| !name.is_empty() && !name.contains(char::is_whitespace);
+        if !valid(question) || !valid(answer) || !valid(cloze) {
+            return None;
+        }
+        if question == answer || question == cloze || answer == cloze {
+            return None;
+        }
+        let label = |name: &str| format!("{}:", name.trim_end_matches(':'));
+        Some(Self {
+            question: label(question),
+            answer: label(answer),
+            cloze: label(cloze),
+        })
+    }
+}
+
+/// Custom section names, e.g. `Front,Back,Text`. `Q:`/`A:`/`C:` keep working.
+pub const SECTION_LABELS_ENV: &str = "REPEATER_SECTION_LABELS";
+
+static SECTION_LABELS: Lazy<Option<SectionLabels>> = Lazy::new(|| {
+    let raw = std::env::var(SECTION_LABELS_ENV).ok()?;
+    if raw.trim().is_empty() {
+        return None;
+    }
+    let labels = SectionLabels::parse(&raw);
+    if labels.is_none() {
+        eprintln!(
+            "warning: {SECTION_LABELS_ENV}: expected three different names such as `Front,Back,Text`, got `{raw}`; using Q/A/C only"
+        );
+    }
+    labels
+});
+
+/// The section a line opens and the text after its label, if it starts with
+/// `Q:`, `A:`, `C:` or one of the custom `labels`.
+fn section_start<'a>(line: &'a str, labels: Option<&SectionLabels>) -> Option<(Section, &'a str)> {
+    let mut prefixes = vec![
+        ("Q:", Section::Question),
+        ("A:", Section::Answer),
+        ("C:", Section::Cloze),
+    ];
+    if let Some(labels) = labels {
+        prefixes.push((&labels.question, Section::Question));
+        prefixes.push((&labels.answer, Section::Answer));
+        prefixes.push((&labels.cloze, Section::Cloze));
     }
+    prefixes
+        .into_iter()
+        .find_map(|(prefix, section)| Some((section, line.strip_prefix(prefix)?)))
+}
+
+/// Splits a one-line card, `Q: question | A: answer`, at the `|` that is
+/// followed by an answer label. Returns the question line and the answer line.
+fn split_one_line_card<'a>(
+    line: &'a str,
+    labels: Option<&SectionLabels>,
+) -> Option<(&'a str, &'a str)> {
+    let Some((Section::Question, _)) = section_start(line, labels) else {
+        return None;
+    };
+    line.match_indices('|').find_map(|(idx, _)| {
+        let after = line[idx + 1..].trim_start();
+        match section_start(after, labels)? {
+            (Section::Answer, _) => Some((line[..idx].trim_end(), after)),
+            _ => None,
+        }
+    })
+}
+
+/// Puts the answer of each one-line card on its own line, so the card parses
+/// and hashes exactly like the multi-line form.
+fn expand_one_line_cards(contents: String, labels: Option<&SectionLabels>) -> String {
+    if !contents.contains('|') {
+        return contents;
+    }
+    let mut text = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        match split_one_line_card(line.trim_start(), labels) {
+            Some((question, answer)) => {
+                text.push_str(question);
+                text.push('\n');
+                text.push_str(answer);
+            }
+            None => text.push_str(line),
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Whether `line` starts a new card, i.e. opens a question or cloze section.
+fn opens_card(line: &str, labels: Option<&SectionLabels>) -> bool {
+    matches!(
+        section_start(line, labels),
+        Some((Section::Question | Section::Cloze, _))
+    )
+}
 
+fn parse_card_lines(
+    contents: &str,
+    labels: Option<&SectionLabels>,
+) -> (Option<String>, Option<String>, Option<String>) {
     let mut question_lines: Vec<&str> = Vec::new();
     let mut answer_lines: Vec<&str> = Vec::new();
     let mut cloze_lines: Vec<&str> = Vec::new();
@@ -59,29 +221,17 @@ fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<S
             );
         }
 
-        if let Some(rest) = line.strip_prefix("Q:") {
-            section = Section::Question;
-            question_lines.clear();
-            if let Some(v) = trim_line(rest) {
-                question_lines.push(v);
-            }
-            continue;
-        }
-
-        if let Some(rest) = line.strip_prefix("A:") {
-            section = Section::Answer;
-            answer_lines.clear();
+        if let Some((started, rest)) = section_start(line, labels) {
+            let lines = match started {
+                Section::Question => &mut question_lines,
+                Section::Answer => &mut answer_lines,
+                Section::Cloze => &mut cloze_lines,
+                Section::None => continue,
+            };
+            section = started;
+            lines.clear();
             if let Some(v) = trim_line(rest) {
-                answer_lines.push(v);
-            }
-            continue;
-        }
-
-        if let Some(rest) = line.strip_prefix("C:") {
-            section = Section::Cloze;
-            cloze_lines.clear();
-            if let Some(v) = trim_line(rest) {
-                cloze_lines.push(v);
+                lines.push(v);
             }
             continue;
         }
@@ -125,53 +275,283 @@ fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<S
         join_nonempty(cloze_lines),
     )
 }
+
+fn card_location(card_path: &Path, file_start_idx: usize, file_end_idx: usize) -> String {
+    format!(
+        "{} (lines {}-{})",
+        card_path.display(),
+        file_start_idx + 1,
+        file_end_idx.max(file_start_idx + 1)
+    )
+}
+
+/// Whether `contents` opens a `C:` section, even an empty one.
+fn has_cloze_section(contents: &str, labels: Option<&SectionLabels>) -> bool {
+    contents
+        .lines()
+        .any(|line| matches!(section_start(line, labels), Some((Section::Cloze, _))))
+}
+
+/// Removes `@directive` lines from a card's text and records them.
+fn split_directives(contents: &str) -> (String, CardDirectives) {
+    let mut directives = CardDirectives::default();
+    let mut text = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        if !directives.apply(line) {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    (text, directives)
+}
+
 pub fn content_to_card(
     card_path: &Path,
     contents: &str,
     file_start_idx: usize,
     file_end_idx: usize,
 ) -> Result<Card> {
-    let (question, answer, cloze) = parse_card_lines(contents);
+    labeled_content_to_card(
+        card_path,
+        contents,
+        (file_start_idx, file_end_idx),
+        SECTION_LABELS.as_ref(),
+    )
+}
+
+/// The text a card's hash is computed from, as `content_to_card` sees it:
+/// directive lines removed and one-line `Q: ... | A: ...` cards expanded.
+pub fn card_hash_source(contents: &str) -> String {
+    card_text(contents, SECTION_LABELS.as_ref()).0
+}
+
+fn card_text(contents: &str, labels: Option<&SectionLabels>) -> (String, CardDirectives) {
+    let (contents, directives) = split_directives(contents);
+    (expand_one_line_cards(contents, labels), directives)
+}
 
-    let card_hash = get_hash(contents).ok_or_else(|| anyhow!("Unable to hash contents"))?;
-    if let (Some(q), Some(a)) = (question, answer) {
-        let content = CardContent::Basic {
+fn labeled_content_to_card(
+    card_path: &Path,
+    contents: &str,
+    (file_start_idx, file_end_idx): (usize, usize),
+    labels: Option<&SectionLabels>,
+) -> Result<Card> {
+    let (contents, directives) = card_text(contents, labels);
+    let (question, answer, cloze) = parse_card_lines(&contents, labels);
+    let location = || card_location(card_path, file_start_idx, file_end_idx);
+
+    let card_hash = get_hash(&contents).ok_or_else(|| anyhow!("{}: card is empty", location()))?;
+    let content = match (question, answer, cloze) {
+        (Some(q), Some(a), _) => CardContent::Basic {
             question: q,
             answer: a,
-        };
+        },
+        (_, _, Some(c)) => {
+            let cloze_idxs = find_cloze_ranges(&c);
+            let cloze_range: Option<ClozeRange> = cloze_idxs
+                .first()
+                .map(|(start, end)| ClozeRange::new(*start, *end))
+                .transpose()
+                .map_err(|_| {
+                    anyhow!(
+                        "{}: found a cloze with empty brackets; put the hidden text inside `[...]`",
+                        location()
+                    )
+                })?;
+
+            CardContent::Cloze {
+                text: c,
+                cloze_range,
+            }
+        }
+        (Some(q), None, None) if !find_cloze_ranges(&q).is_empty() => bail!(
+            "{}: found a question with no answer; it has `[...]` brackets, so if it is meant as a cloze, start it with `C:` instead of `Q:`",
+            location()
+        ),
+        (Some(_), None, None) => bail!(
+            "{}: found a question with no answer; add an `A:` section",
+            location()
+        ),
+        (None, Some(_), None) => bail!(
+            "{}: found an answer with no question; add a `Q:` section",
+            location()
+        ),
+        (None, None, None) if has_cloze_section(&contents, labels) => bail!(
+            "{}: found a cloze section but no bracketed text; put the hidden text inside `[...]`",
+            location()
+        ),
+        (None, None, None) => bail!(
+            "{}: no card content found; expected a `Q:`/`A:` pair or a `C:` section",
+            location()
+        ),
+    };
+    let mut card = Card::new(
+        card_path.to_path_buf(),
+        (file_start_idx, file_end_idx),
+        content,
+        card_hash,
+    );
+    card.directives = directives;
+    Ok(card)
+}
 
-        Ok(Card::new(
-            card_path.to_path_buf(),
-            (file_start_idx, file_end_idx),
-            content,
-            card_hash,
-        ))
-    } else if let Some(c) = cloze {
-        let cloze_idxs = find_cloze_ranges(&c);
-        let cloze_range: Option<ClozeRange> = cloze_idxs
-            .first()
-            .map(|(start, end)| ClozeRange::new(*start, *end))
-            .transpose()?;
-
-        let content = CardContent::Cloze {
-            text: c,
-            cloze_range,
-        };
-        Ok(Card::new(
-            card_path.to_path_buf(),
-            (file_start_idx, file_end_idx),
-            content,
-            card_hash,
-        ))
-    } else {
-        bail!("Unable to parse anything from card contents:\n{}", contents);
+/// A file whose frontmatter `retention:` applies to every deck in its
+/// directory and the directories below it.
+pub const DIRECTORY_SETTINGS_FILE: &str = "_index.md";
+
+/// Path argument that means "read markdown from stdin".
+pub const STDIN_ARG: &str = "-";
+/// `file_path` given to cards read from stdin.
+pub const STDIN_LABEL: &str = "<stdin>";
+
+/// Separator for one-line `term :: definition` cards, e.g. `::`. Unset or
+/// empty disables the inline format.
+pub const INLINE_SEPARATOR_ENV: &str = "REPEATER_INLINE_SEPARATOR";
+
+static INLINE_SEPARATOR: Lazy<Option<String>> = Lazy::new(|| {
+    std::env::var(INLINE_SEPARATOR_ENV)
+        .ok()
+        .map(|raw| raw.trim().to_string())
+        .filter(|separator| !separator.is_empty())
+});
+
+/// Splits `apple :: manzana` into question and answer. The separator must
+/// have whitespace on both sides, so `std::vec` in prose is left alone.
+fn split_inline_card<'a>(line: &'a str, separator: &str) -> Option<(&'a str, &'a str)> {
+    let (question, answer) = line.split_once(separator)?;
+    if !question.ends_with(char::is_whitespace) || !answer.starts_with(char::is_whitespace) {
+        return None;
     }
+    Some((trim_line(question)?, trim_line(answer)?))
 }
 
 pub fn cards_from_md(path: &Path) -> Result<Vec<Card>> {
+    cards_from_file(path, false).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn cards_from_file(path: &Path, lossy: bool) -> Result<Vec<Card>> {
     let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
+    let mut cards = parse_cards(
+        BufReader::new(file),
+        path,
+        INLINE_SEPARATOR.as_deref(),
+        SECTION_LABELS.as_ref(),
+        lossy,
+    )?;
+    // A deck's own frontmatter wins over the directory's.
+    if cards.iter().any(|card| card.retention.is_none())
+        && let Some(retention) = directory_retention(path)
+    {
+        for card in cards.iter_mut().filter(|card| card.retention.is_none()) {
+            card.retention = Some(retention);
+        }
+    }
+    Ok(cards)
+}
+
+/// The `retention:` of the nearest directory above `path` whose
+/// `DIRECTORY_SETTINGS_FILE` sets one.
+fn directory_retention(path: &Path) -> Option<f64> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    path.ancestors().skip(1).find_map(|dir| {
+        let settings = dir.join(DIRECTORY_SETTINGS_FILE);
+        let file = File::open(&settings).ok()?;
+        frontmatter_retention(BufReader::new(file), &settings)
+    })
+}
+
+/// The `retention:` key of the frontmatter block opening `reader`, if any.
+fn frontmatter_retention<R: BufRead>(reader: R, path: &Path) -> Option<f64> {
+    let mut lines = reader.lines().map_while(Result::ok);
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+    let mut retention = None;
+    for line in lines {
+        if line.trim_end() == "---" {
+            return retention;
+        }
+        if opens_card(&line, SECTION_LABELS.as_ref()) {
+            // Only a separator, as in `parse_cards`.
+            return None;
+        }
+        if let Some(raw) = line.strip_prefix("retention:") {
+            retention = parse_frontmatter_retention(path, raw.trim());
+        }
+    }
+    None
+}
+
+/// Parses cards from any markdown stream; `path` is recorded as each card's `file_path`.
+pub fn cards_from_reader<R: BufRead>(reader: R, path: &Path) -> Result<Vec<Card>> {
+    parse_cards(
+        reader,
+        path,
+        INLINE_SEPARATOR.as_deref(),
+        SECTION_LABELS.as_ref(),
+        false,
+    )
+}
+
+/// Reads one line into `line` like `BufRead::read_line`. Invalid UTF-8 is an
+/// `InvalidData` error giving the line and byte offset it starts at, or with
+/// `lossy` is replaced with U+FFFD after a warning naming the file.
+fn read_utf8_line<R: BufRead>(
+    reader: &mut R,
+    line: &mut String,
+    path: &Path,
+    position: (usize, usize),
+    lossy: bool,
+) -> io::Result<usize> {
+    let (line_idx, offset) = position;
+    let mut bytes = Vec::new();
+    let bytes_read = reader.read_until(b'\n', &mut bytes)?;
+    match String::from_utf8(bytes) {
+        Ok(text) => line.push_str(&text),
+        Err(err) => {
+            let bad_offset = offset + err.utf8_error().valid_up_to();
+            let location = format!(
+                "invalid UTF-8 on line {} at byte {bad_offset}",
+                line_idx + 1
+            );
+            if !lossy {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{location}; save the file as UTF-8 or pass --lossy"),
+                ));
+            }
+            eprintln!(
+                "warning: {}: {location}; replaced with U+FFFD",
+                path.display()
+            );
+            line.push_str(&String::from_utf8_lossy(err.as_bytes()));
+        }
+    }
+    Ok(bytes_read)
+}
+
+/// Outside a `Q:`/`C:` block, lines split by `inline_separator` become one-line
+/// basic cards. Blocks run until `---`, so inline lines after one are part of it.
+fn parse_cards<R: BufRead>(
+    reader: R,
+    path: &Path,
+    inline_separator: Option<&str>,
+    labels: Option<&SectionLabels>,
+    lossy: bool,
+) -> Result<Vec<Card>> {
+    parse_cards_reporting(reader, path, inline_separator, labels, lossy, None)
+}
 
+/// `parse_cards`, but with `errors` a malformed card is recorded there and
+/// parsing carries on, so every bad card in the file is found in one pass.
+fn parse_cards_reporting<R: BufRead>(
+    mut reader: R,
+    path: &Path,
+    inline_separator: Option<&str>,
+    labels: Option<&SectionLabels>,
+    lossy: bool,
+    mut errors: Option<&mut Vec<anyhow::Error>>,
+) -> Result<Vec<Card>> {
     let mut cards = Vec::new();
     let mut track_buffer = false;
     let mut buffer = String::new();
@@ -179,24 +559,71 @@ pub fn cards_from_md(path: &Path) -> Result<Vec<Card>> {
     let mut start_idx = 0;
     let mut last_idx = 0;
     let mut line_idx = 0;
+    let mut offset = 0;
+    // A `---` on the first line opens a frontmatter block, closed by the next `---`.
+    let mut in_frontmatter = false;
+    let mut retention = None;
 
     loop {
         line.clear();
-        let bytes_read = reader.read_line(&mut line)?;
+        let bytes_read = read_utf8_line(&mut reader, &mut line, path, (line_idx, offset), lossy)?;
         if bytes_read == 0 {
             break;
         }
+        offset += bytes_read;
+
+        if line_idx == 0 && line.trim_end() == "---" {
+            in_frontmatter = true;
+        } else if in_frontmatter {
+            if opens_card(&line, labels) {
+                // Only a separator after all; the card is parsed as usual.
+                in_frontmatter = false;
+                retention = None;
+            } else {
+                if line.trim_end() == "---" {
+                    in_frontmatter = false;
+                } else if let Some(raw) = line.strip_prefix("retention:") {
+                    retention = parse_frontmatter_retention(path, raw.trim());
+                }
+                last_idx = line_idx;
+                line_idx += 1;
+                continue;
+            }
+        }
 
-        if line.starts_with("Q:") || line.starts_with("C:") {
+        if !track_buffer
+            && let Some(separator) = inline_separator
+            && let Some((question, answer)) = split_inline_card(&line, separator)
+        {
+            let contents = format!("Q: {question}\nA: {answer}\n");
+            keep_card(
+                &mut cards,
+                errors.as_deref_mut(),
+                labeled_content_to_card(path, &contents, (line_idx, line_idx + 1), labels),
+            )?;
+            last_idx = line_idx;
+            line_idx += 1;
+            continue;
+        }
+
+        if opens_card(&line, labels) {
             track_buffer = true;
             if trim_line(&buffer).is_some() {
-                cards.push(content_to_card(path, &buffer, start_idx, line_idx)?);
+                keep_card(
+                    &mut cards,
+                    errors.as_deref_mut(),
+                    labeled_content_to_card(path, &buffer, (start_idx, line_idx), labels),
+                )?;
                 buffer.clear();
             }
             start_idx = line_idx;
         }
         if line.starts_with("---") && trim_line(&buffer).is_some() {
-            cards.push(content_to_card(path, &buffer, start_idx, line_idx)?);
+            keep_card(
+                &mut cards,
+                errors.as_deref_mut(),
+                labeled_content_to_card(path, &buffer, (start_idx, line_idx), labels),
+            )?;
             buffer.clear();
             track_buffer = false;
         }
@@ -207,13 +634,99 @@ pub fn cards_from_md(path: &Path) -> Result<Vec<Card>> {
         line_idx += 1;
     }
     if !buffer.is_empty() {
-        cards.push(content_to_card(path, &buffer, start_idx, last_idx + 1)?);
+        keep_card(
+            &mut cards,
+            errors,
+            labeled_content_to_card(path, &buffer, (start_idx, last_idx + 1), labels),
+        )?;
+    }
+    if !in_frontmatter && let Some(retention) = retention {
+        for card in &mut cards {
+            card.retention = Some(retention);
+        }
     }
-
     Ok(cards)
 }
 
-fn markdown_walk_builder(paths: &[PathBuf]) -> Result<Option<WalkBuilder>> {
+/// Adds a parsed card to `cards`. A parse error is pushed onto `errors` when
+/// it is given, and returned otherwise.
+fn keep_card(
+    cards: &mut Vec<Card>,
+    errors: Option<&mut Vec<anyhow::Error>>,
+    parsed: Result<Card>,
+) -> Result<()> {
+    match (parsed, errors) {
+        (Ok(card), _) => cards.push(card),
+        (Err(err), Some(errors)) => errors.push(err),
+        (Err(err), None) => return Err(err),
+    }
+    Ok(())
+}
+
+/// A frontmatter `retention:` value, which must lie strictly between 0 and 1.
+fn parse_frontmatter_retention(path: &Path, raw: &str) -> Option<f64> {
+    match raw.parse::<f64>() {
+        Ok(value) if value > 0.0 && value < 1.0 => Some(value),
+        _ => {
+            eprintln!(
+                "warning: ignoring retention: {raw} in {}; expected a number between 0 and 1",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Describes cards that hash the same as an earlier card in the same file.
+/// They share a database row, so the copy would otherwise vanish silently.
+fn duplicate_warnings(cards: &[Card]) -> Vec<String> {
+    let mut first_seen: HashMap<&str, &Card> = HashMap::new();
+    let mut warnings = Vec::new();
+    for card in cards {
+        match first_seen.get(card.card_hash.as_str()) {
+            Some(first) => {
+                let (path, first_start, first_end) = first.locator();
+                let (_, start, end) = card.locator();
+                warnings.push(format!(
+                    "{} and lines {}-{} contain the same card; only one will be scheduled",
+                    card_location(path, first_start, first_end),
+                    start + 1,
+                    end.max(start + 1)
+                ));
+            }
+            None => {
+                first_seen.insert(&card.card_hash, card);
+            }
+        }
+    }
+    warnings
+}
+
+/// Expands arguments such as `decks/**/*.md` into the paths they match, in
+/// sorted order. Paths that exist are kept as-is even if they contain glob
+/// characters; a pattern matching nothing is dropped with a warning.
+fn expand_globs(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::with_capacity(paths.len());
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        let is_pattern = pattern.contains(['*', '?', '[']);
+        if !is_pattern || path.exists() {
+            expanded.push(path);
+            continue;
+        }
+        let matches: Vec<PathBuf> = glob::glob(&pattern)
+            .map_err(|err| anyhow!("Invalid glob pattern {pattern:?}: {err}"))?
+            .filter_map(Result::ok)
+            .collect();
+        if matches.is_empty() {
+            eprintln!("warning: no files match {pattern}");
+        }
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+fn markdown_walk_builder(paths: &[PathBuf], threads: Option<usize>) -> Result<Option<WalkBuilder>> {
     let mut iter = paths.iter();
     let Some(first) = iter.next() else {
         return Ok(None);
@@ -223,24 +736,68 @@ fn markdown_walk_builder(paths: &[PathBuf]) -> Result<Option<WalkBuilder>> {
         builder.add(path);
     }
     builder.hidden(false).git_ignore(true).git_exclude(true);
+    if let Some(threads) = threads {
+        builder.threads(threads);
+    }
     Ok(Some(builder))
 }
 
+fn is_io_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<std::io::Error>())
+}
+
+fn warn_skipped(stats: &Mutex<FileSearchStats>, err: &dyn std::fmt::Display) {
+    eprintln!("warning: skipping unreadable path: {err}");
+    stats.lock().unwrap().skipped_files += 1;
+}
+
 fn run_card_walker(
     paths: Vec<PathBuf>,
+    options: CollectOptions,
     sender: mpsc::UnboundedSender<Vec<Card>>,
 ) -> Result<FileSearchStats> {
-    let Some(builder) = markdown_walk_builder(&paths)? else {
-        return Ok(FileSearchStats::default());
+    let (stdin_args, paths): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .into_iter()
+        .partition(|path| path.as_os_str() == STDIN_ARG);
+    let paths = expand_globs(paths)?;
+    let mut stdin_stats = FileSearchStats::default();
+    if !stdin_args.is_empty() {
+        let stdin_path = Path::new(STDIN_LABEL);
+        let reader = std::io::stdin().lock();
+        let cards = parse_cards(
+            reader,
+            stdin_path,
+            INLINE_SEPARATOR.as_deref(),
+            SECTION_LABELS.as_ref(),
+            options.lossy,
+        )
+        .map_err(|err| err.context(format!("Failed to parse {STDIN_LABEL}")))?;
+        stdin_stats.files_searched += 1;
+        stdin_stats.markdown_files += 1;
+        stdin_stats.duplicate_cards = duplicate_warnings(&cards);
+        if !cards.is_empty() && sender.send(cards).is_err() {
+            return Ok(stdin_stats);
+        }
+    }
+
+    let Some(builder) = markdown_walk_builder(&paths, options.threads)? else {
+        return Ok(stdin_stats);
     };
 
     let error_slot = Arc::new(Mutex::new(None));
-    let stats = Arc::new(Mutex::new(FileSearchStats::default()));
+    let stats = Arc::new(Mutex::new(stdin_stats));
 
     builder.build_parallel().run(|| {
         let sender = sender.clone();
         let error_slot = Arc::clone(&error_slot);
         let stats = Arc::clone(&stats);
+        let strict = options.strict;
+        let verbose = options.verbose;
+        let lossy = options.lossy;
+        let extensions = options
+            .extensions
+            .clone()
+            .unwrap_or_else(|| markdown_extensions().to_vec());
         Box::new(move |entry| match entry {
             Ok(entry) => {
                 if !entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
@@ -250,7 +807,7 @@ fn run_card_walker(
                 let mut stats_guard = stats.lock().unwrap();
                 stats_guard.files_searched += 1;
                 let path = entry.path();
-                let is_markdown = is_markdown(path);
+                let is_markdown = has_extension(path, &extensions);
                 if is_markdown {
                     stats_guard.markdown_files += 1;
                 }
@@ -260,15 +817,34 @@ fn run_card_walker(
                     return WalkState::Continue;
                 }
                 let path = entry.path().to_path_buf();
-                match cards_from_md(&path) {
+                let started = Instant::now();
+                let parsed = cards_from_file(&path, lossy);
+                if verbose {
+                    stats.lock().unwrap().parse_timings.push(ParseTiming {
+                        path: path.clone(),
+                        duration: started.elapsed(),
+                        cards: parsed.as_ref().map(Vec::len).unwrap_or(0),
+                    });
+                }
+                match parsed {
                     Ok(cards) => {
                         if cards.is_empty() {
+                            if has_text(&path) {
+                                stats.lock().unwrap().files_without_cards.push(path);
+                            }
                             return WalkState::Continue;
                         }
+                        let duplicates = duplicate_warnings(&cards);
+                        if !duplicates.is_empty() {
+                            stats.lock().unwrap().duplicate_cards.extend(duplicates);
+                        }
                         if sender.send(cards).is_err() {
                             return WalkState::Quit;
                         }
                     }
+                    Err(err) if !strict && is_io_error(&err) => {
+                        warn_skipped(&stats, &format!("{}: {err}", path.display()));
+                    }
                     Err(err) => {
                         *error_slot.lock().unwrap() =
                             Some(err.context(format!("Failed to parse {}", path.display())));
@@ -277,6 +853,10 @@ fn run_card_walker(
                 }
                 WalkState::Continue
             }
+            Err(err) if !strict => {
+                warn_skipped(&stats, &err);
+                WalkState::Continue
+            }
             Err(err) => {
                 *error_slot.lock().unwrap() = Some(anyhow!(err));
                 WalkState::Quit
@@ -289,51 +869,283 @@ fn run_card_walker(
     if let Some(err) = error_slot.lock().unwrap().take() {
         return Err(err);
     }
-    let stats = match Arc::try_unwrap(stats) {
+    let mut stats = match Arc::try_unwrap(stats) {
         Ok(mutex) => mutex.into_inner().unwrap(),
         Err(arc) => arc.lock().unwrap().clone(),
     };
+    stats.files_without_cards.sort();
+    stats.duplicate_cards.sort();
     Ok(stats)
 }
 
+/// The `limit` slowest timings, slowest first; ties keep path order so the
+/// report is stable across runs.
+fn slowest_files(timings: &[ParseTiming], limit: usize) -> Vec<&ParseTiming> {
+    let mut sorted: Vec<&ParseTiming> = timings.iter().collect();
+    sorted.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    sorted.truncate(limit);
+    sorted
+}
+
+/// Total and average parse time followed by the slowest files.
+fn format_parse_timings(timings: &[ParseTiming], limit: usize) -> String {
+    if timings.is_empty() {
+        return "No deck files parsed.\n".to_string();
+    }
+    let total: Duration = timings.iter().map(|timing| timing.duration).sum();
+    let average = total / timings.len() as u32;
+    let mut report = format!(
+        "Parsed {} in {:.1} ms (average {:.2} ms). Slowest:\n",
+        pluralize("file", timings.len()),
+        total.as_secs_f64() * 1000.0,
+        average.as_secs_f64() * 1000.0
+    );
+    for timing in slowest_files(timings, limit) {
+        report.push_str(&format!(
+            "  {:>9.2} ms  {:>5} cards  {}\n",
+            timing.duration.as_secs_f64() * 1000.0,
+            timing.cards,
+            timing.path.display()
+        ));
+    }
+    report
+}
+
+/// Whether a file parsed to zero cards has anything besides whitespace, which
+/// usually means the card markers are missing or indented.
+fn has_text(path: &Path) -> bool {
+    std::fs::read(path)
+        .map(|bytes| bytes.iter().any(|byte| !byte.is_ascii_whitespace()))
+        .unwrap_or(false)
+}
+
+/// Files are parsed on several threads and arrive in any order; sorting the
+/// per-file batches by path makes everything downstream deterministic.
+fn sort_batches(batches: &mut [Vec<Card>]) {
+    batches.sort_by(|a, b| {
+        let path = |batch: &[Card]| batch.first().map(|card| card.file_path.clone());
+        path(a).cmp(&path(b))
+    });
+}
+
+/// Parses every card under `paths` without touching the database, ordered by
+/// file path and then by position in the file.
+pub fn collect_cards(
+    paths: Vec<PathBuf>,
+    options: CollectOptions,
+) -> Result<(Vec<Card>, FileSearchStats)> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<Card>>();
+    let stats = run_card_walker(paths, options, tx)?;
+    let mut batches = Vec::new();
+    while let Ok(batch) = rx.try_recv() {
+        batches.push(batch);
+    }
+    sort_batches(&mut batches);
+    Ok((batches.into_iter().flatten().collect(), stats))
+}
+
+/// Parses every card under `paths` without touching the database and returns
+/// how many were found.
+pub fn scan_cards(
+    paths: Vec<PathBuf>,
+    options: CollectOptions,
+) -> Result<(usize, FileSearchStats)> {
+    let (cards, stats) = collect_cards(paths, options)?;
+    Ok((cards.len(), stats))
+}
+
+/// What `find_card_errors` saw: how many files and cards were read, and every
+/// problem found, each prefixed with its file and lines.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub files: usize,
+    pub cards: usize,
+    pub errors: Vec<String>,
+    /// Cards repeated within one file: not errors, but only one is scheduled.
+    pub warnings: Vec<String>,
+}
+
+/// Parses every Markdown file under `paths` without touching the database,
+/// carrying on past malformed cards and unreadable files so that all of them
+/// are reported. Files are visited in path order.
+pub fn find_card_errors(paths: Vec<PathBuf>, options: &CollectOptions) -> Result<ValidationReport> {
+    let paths = expand_globs(paths)?;
+    let mut report = ValidationReport::default();
+    let Some(builder) = markdown_walk_builder(&paths, None)? else {
+        return Ok(report);
+    };
+    let extensions = options
+        .extensions
+        .clone()
+        .unwrap_or_else(|| markdown_extensions().to_vec());
+    let mut files = Vec::new();
+    for entry in builder.build() {
+        match entry {
+            Ok(entry) if entry.file_type().is_some_and(|ft| ft.is_file()) => {
+                if has_extension(entry.path(), &extensions) {
+                    files.push(entry.into_path());
+                }
+            }
+            Ok(_) => {}
+            Err(err) => report.errors.push(err.to_string()),
+        }
+    }
+    files.sort();
+
+    for path in files {
+        report.files += 1;
+        let mut errors = Vec::new();
+        let parsed = File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                parse_cards_reporting(
+                    BufReader::new(file),
+                    &path,
+                    INLINE_SEPARATOR.as_deref(),
+                    SECTION_LABELS.as_ref(),
+                    options.lossy,
+                    Some(&mut errors),
+                )
+            });
+        match parsed {
+            Ok(cards) => {
+                report.cards += cards.len();
+                report.warnings.extend(duplicate_warnings(&cards));
+            }
+            Err(err) => errors.push(err.context(format!("Failed to read {}", path.display()))),
+        }
+        report
+            .errors
+            .extend(errors.iter().map(|err| format!("{err:#}")));
+    }
+    Ok(report)
+}
+
 pub async fn register_all_cards(
     db: &DB,
     paths: Vec<PathBuf>,
+    options: CollectOptions,
 ) -> Result<(HashMap<String, Card>, FileSearchStats)> {
     let (tx, mut rx) = mpsc::unbounded_channel::<Vec<Card>>();
-    let walker_handle = tokio::task::spawn_blocking(move || run_card_walker(paths, tx));
+    let options_verbose = options.verbose;
+    let track_edits = options.track_edits;
+    let walker_handle = tokio::task::spawn_blocking(move || run_card_walker(paths, options, tx));
 
-    let mut hash_cards = HashMap::new();
+    // Batches are written to the database as they are parsed; the map is built
+    // once every file is in so duplicate cards resolve the same way every run.
+    let mut batches = Vec::new();
     while let Some(batch) = rx.recv().await {
         if batch.is_empty() {
             continue;
         }
         db.add_cards_batch(&batch).await?;
-        for card in batch {
-            hash_cards.insert(card.card_hash.clone(), card);
-        }
+        batches.push(batch);
     }
 
-    let stats = walker_handle.await??;
+    let mut stats = walker_handle.await??;
+    if options_verbose {
+        eprint!(
+            "{}",
+            format_parse_timings(&stats.parse_timings, SLOWEST_FILES_SHOWN)
+        );
+    }
+
+    sort_batches(&mut batches);
+    let mut hash_cards = HashMap::new();
+    for card in batches.into_iter().flatten() {
+        hash_cards.insert(card.card_hash.clone(), card);
+    }
+    if track_edits {
+        stats.edited_cards = db.edited_cards(&hash_cards).await?;
+    }
 
     Ok((hash_cards, stats))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{cards_from_md, content_to_card, parse_card_lines, register_all_cards};
-    use crate::card::CardContent;
+    use super::{
+        CollectOptions, DIRECTORY_SETTINGS_FILE, ParseTiming, STDIN_LABEL, SectionLabels,
+        cards_from_file, cards_from_md, cards_from_reader, collect_cards, content_to_card,
+        duplicate_warnings, expand_globs, format_parse_timings, parse_card_lines, parse_cards,
+        register_all_cards, slowest_files,
+    };
+    use crate::card::{CardContent, CardDirectives, ClozeMode};
     use crate::crud::DB;
     use std::path::PathBuf;
+    use std::time::Duration;
 
     #[test]
     fn test_card_parsing() {
         let contents = "C:\nRegion: [`us-east-2`]\n\nLocation: [Ohio]\n\n---\n\n";
-        let (question, _, cloze) = parse_card_lines(contents);
+        let (question, _, cloze) = parse_card_lines(contents, None);
         assert!(question.is_none());
         assert_eq!("Region: [`us-east-2`]\n\nLocation: [Ohio]", cloze.unwrap());
     }
 
+    #[test]
+    fn globs_expand_to_matching_files_and_empty_matches_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in ["a.md", "sub/b.md", "sub/deeper/c.md", "sub/notes.txt"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "Q: q\nA: a\n").unwrap();
+        }
+        let literal = root.join("lit[1].markdown");
+        std::fs::write(&literal, "Q: q\nA: a\n").unwrap();
+
+        let expanded = expand_globs(vec![
+            root.join("**/*.md"),
+            root.join("nothing/*.md"),
+            literal.clone(),
+            root.join("sub"),
+        ])
+        .unwrap();
+        let expected = vec![
+            root.join("a.md"),
+            root.join("sub/b.md"),
+            root.join("sub/deeper/c.md"),
+            literal.clone(),
+            root.join("sub"),
+        ];
+        assert_eq!(expanded, expected);
+    }
+
+    #[test]
+    fn parse_timings_are_sorted_slowest_first_and_averaged() {
+        let timing = |path: &str, millis, cards| ParseTiming {
+            path: PathBuf::from(path),
+            duration: Duration::from_millis(millis),
+            cards,
+        };
+        let timings = vec![
+            timing("b.md", 10, 1),
+            timing("slow.md", 50, 40),
+            timing("a.md", 10, 2),
+            timing("fast.md", 2, 1),
+        ];
+        let slowest: Vec<&str> = slowest_files(&timings, 3)
+            .iter()
+            .map(|timing| timing.path.to_str().unwrap())
+            .collect();
+        assert_eq!(slowest, ["slow.md", "a.md", "b.md"]);
+
+        let report = format_parse_timings(&timings, 2);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Parsed 4 files in 72.0 ms (average 18.00 ms). Slowest:"
+        );
+        assert_eq!(lines[1], "      50.00 ms     40 cards  slow.md");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(format_parse_timings(&[], 2), "No deck files parsed.\n");
+    }
+
     #[test]
     fn basic_qa() {
         let card_path = PathBuf::from("test.md");
@@ -358,6 +1170,87 @@ mod tests {
         assert!(card.is_err());
     }
 
+    #[test]
+    fn one_line_card_matches_the_multi_line_form() {
+        let card_path = PathBuf::from("test.md");
+        let one_line =
+            content_to_card(&card_path, "Q: capital of France | A: Paris\n", 0, 1).unwrap();
+        let multi_line =
+            content_to_card(&card_path, "Q: capital of France\nA: Paris\n", 0, 1).unwrap();
+        let basic = |card: &crate::card::Card| match &card.content {
+            CardContent::Basic { question, answer } => (question.clone(), answer.clone()),
+            CardContent::Cloze { .. } => panic!("Expected CardContent::Basic"),
+        };
+        assert_eq!(basic(&one_line), basic(&multi_line));
+        assert_eq!(one_line.card_hash, multi_line.card_hash);
+
+        // A `|` not followed by `A:` stays in the question.
+        let piped = content_to_card(&card_path, "Q: a | b?\nA: c\n", 0, 2).unwrap();
+        assert!(
+            matches!(&piped.content, CardContent::Basic { question, .. } if question == "a | b?")
+        );
+
+        let stream = "Q: one? | A: 1\nQ: two? | A: 2\n";
+        let cards = parse_cards(stream.as_bytes(), &card_path, None, None, false).unwrap();
+        assert_eq!(cards.len(), 2);
+    }
+
+    #[test]
+    fn bracketed_question_without_answer_suggests_a_cloze() {
+        let card_path = PathBuf::from("test.md");
+        let err = content_to_card(&card_path, "Q: The [capital] of France\n", 0, 1).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("if it is meant as a cloze, start it with `C:` instead of `Q:`"),
+            "{err}"
+        );
+
+        let err = content_to_card(&card_path, "Q: The capital of France\n", 0, 1).unwrap_err();
+        assert!(err.to_string().ends_with("add an `A:` section"), "{err}");
+    }
+
+    #[test]
+    fn directive_lines_are_parsed_and_left_out_of_the_hash() {
+        let card_path = PathBuf::from("test.md");
+        let plain = content_to_card(&card_path, "Q: what?\nA: yes\n", 0, 2).unwrap();
+        let tagged =
+            content_to_card(&card_path, "@important\nQ: what?\nA: yes\n  @hard\n", 0, 4).unwrap();
+
+        assert_eq!(plain.directives, CardDirectives::default());
+        assert!(tagged.directives.important && tagged.directives.hard);
+        assert_eq!(tagged.card_hash, plain.card_hash);
+        let CardContent::Basic { answer, .. } = &tagged.content else {
+            panic!("Expected CardContent::Basic");
+        };
+        assert_eq!(answer, "yes");
+
+        // Only whole-line directives count; unknown ones stay in the text.
+        let inline = content_to_card(&card_path, "Q: ping @hard?\nA: @later\n", 0, 2).unwrap();
+        assert_eq!(inline.directives, CardDirectives::default());
+
+        let labelled = content_to_card(
+            &card_path,
+            "Q: what?\nA: yes\n@tags: #bio, cells\n@tags cells  exam\n",
+            0,
+            4,
+        )
+        .unwrap();
+        assert_eq!(labelled.directives.tags, ["bio", "cells", "exam"]);
+        assert_eq!(labelled.card_hash, plain.card_hash);
+        let not_tags = content_to_card(&card_path, "Q: what?\nA: @tagsoup\n", 0, 2).unwrap();
+        assert!(not_tags.directives.tags.is_empty());
+
+        let recall =
+            content_to_card(&card_path, "C: [Lima]\n@cloze-mode=recall-term\n", 0, 2).unwrap();
+        assert_eq!(recall.directives.cloze_mode, ClozeMode::RecallTerm);
+
+        let urgent = content_to_card(&card_path, "Q: what?\nA: yes\n@priority=3\n", 0, 3).unwrap();
+        assert_eq!(urgent.directives.priority, 3);
+        assert_eq!(urgent.card_hash, plain.card_hash);
+        let typo = content_to_card(&card_path, "Q: what?\nA: yes\n@priority=high\n", 0, 3).unwrap();
+        assert_eq!(typo.directives.priority, 0);
+    }
+
     #[test]
     fn basic_cloze() {
         let card_path = PathBuf::from("test.md");
@@ -388,7 +1281,9 @@ mod tests {
             .await
             .expect("Failed to connect to or initialize database");
         let dir_path = PathBuf::from("test_data");
-        let (cards, stats) = register_all_cards(&db, vec![dir_path]).await.unwrap();
+        let (cards, stats) = register_all_cards(&db, vec![dir_path], CollectOptions::default())
+            .await
+            .unwrap();
         assert_eq!(cards.len(), 11);
         for card in cards.values() {
             assert!(card.file_path.to_string_lossy().contains("test_data"));
@@ -398,11 +1293,284 @@ mod tests {
 
         let dir_path = PathBuf::from("test_data/");
         let file_path = PathBuf::from("test_data/test.md");
-        let (cards, _) = register_all_cards(&db, vec![dir_path, file_path])
+        let (cards, _) =
+            register_all_cards(&db, vec![dir_path, file_path], CollectOptions::default())
+                .await
+                .unwrap();
+
+        assert_eq!(cards.len(), 11);
+    }
+
+    #[tokio::test]
+    async fn repeated_runs_yield_identical_locators_in_document_order() {
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let db = DB::new_in_memory().await.unwrap();
+            let (cards, _) = register_all_cards(
+                &db,
+                vec![PathBuf::from("test_data")],
+                CollectOptions::default(),
+            )
             .await
             .unwrap();
+            let due = db.due_today(&cards, None, None).await.unwrap();
+            let locators: Vec<(PathBuf, usize, usize)> = due
+                .iter()
+                .map(|card| {
+                    let (path, start, end) = card.locator();
+                    (path.to_path_buf(), start, end)
+                })
+                .collect();
+            runs.push(locators);
+        }
 
-        assert_eq!(cards.len(), 11);
+        assert_eq!(runs[0].len(), 11);
+        assert_eq!(runs[0], runs[1]);
+        let mut sorted = runs[0].clone();
+        sorted.sort();
+        assert_eq!(runs[0], sorted);
+    }
+
+    #[test]
+    fn parallel_and_sequential_parsing_agree_on_cards_and_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for idx in 0..40 {
+            let sub = dir.path().join(format!("deck{}", idx % 4));
+            std::fs::create_dir_all(&sub).unwrap();
+            std::fs::write(
+                sub.join(format!("{idx:02}.md")),
+                format!("Q: q{idx}a?\nA: a\n\nC: cloze [{idx}]\n"),
+            )
+            .unwrap();
+        }
+        let paths = vec![dir.path().to_path_buf(), PathBuf::from("test_data")];
+        let summarize = |threads| {
+            let options = CollectOptions {
+                threads: Some(threads),
+                ..Default::default()
+            };
+            let (cards, stats) = collect_cards(paths.clone(), options).unwrap();
+            let cards: Vec<(PathBuf, usize, usize, String)> = cards
+                .iter()
+                .map(|card| {
+                    let (path, start, end) = card.locator();
+                    (path.to_path_buf(), start, end, card.card_hash.clone())
+                })
+                .collect();
+            (cards, stats.markdown_files)
+        };
+
+        let sequential = summarize(1);
+        assert_eq!(sequential.0.len(), 80 + 11);
+        for _ in 0..3 {
+            assert_eq!(summarize(8), sequential);
+        }
+    }
+
+    #[test]
+    fn cards_from_reader_parses_a_concatenated_stream() {
+        let stream = "Q: first?\nA: one\n\nC: the [second] card\n---\nQ: third?\nA: three\n";
+        let cards =
+            cards_from_reader(stream.as_bytes(), std::path::Path::new(STDIN_LABEL)).unwrap();
+
+        assert_eq!(cards.len(), 3);
+        assert!(
+            cards
+                .iter()
+                .all(|card| card.file_path.as_os_str() == STDIN_LABEL)
+        );
+        assert!(matches!(cards[1].content, CardContent::Cloze { .. }));
+    }
+
+    #[test]
+    fn frontmatter_retention_applies_to_every_card_in_the_file() {
+        let path = std::path::Path::new("deck.md");
+        let stream =
+            "---\ntitle: Spanish\nretention: 0.85\n---\nQ: dog?\nA: perro\n---\nC: [gato] is cat\n";
+        let cards = parse_cards(stream.as_bytes(), path, None, None, false).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert!(cards.iter().all(|card| card.retention == Some(0.85)));
+        assert_eq!(cards[0].locator(), (path, 4, 6));
+
+        // A leading separator followed by a card is not frontmatter.
+        let stream = "---\nretention: 0.85\nQ: dog?\nA: perro\n";
+        let cards = parse_cards(stream.as_bytes(), path, None, None, false).unwrap();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].retention, None);
+
+        let stream = "---\nretention: 1.5\n---\nQ: dog?\nA: perro\n";
+        let cards = parse_cards(stream.as_bytes(), path, None, None, false).unwrap();
+        assert_eq!(cards[0].retention, None);
+    }
+
+    #[test]
+    fn directory_retention_applies_to_the_tree_below_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let spanish = dir.path().join("spanish");
+        let verbs = spanish.join("verbs");
+        std::fs::create_dir_all(&verbs).unwrap();
+        std::fs::write(
+            spanish.join(DIRECTORY_SETTINGS_FILE),
+            "---\nretention: 0.8\n---\n",
+        )
+        .unwrap();
+        let deck = "Q: dog?\nA: perro\n";
+        std::fs::write(verbs.join("ser.md"), deck).unwrap();
+        std::fs::write(
+            spanish.join("strict.md"),
+            format!("---\nretention: 0.95\n---\n{deck}"),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("other.md"), deck).unwrap();
+
+        let retention = |path: PathBuf| cards_from_file(&path, false).unwrap()[0].retention;
+        assert_eq!(retention(verbs.join("ser.md")), Some(0.8));
+        // The deck's own frontmatter wins.
+        assert_eq!(retention(spanish.join("strict.md")), Some(0.95));
+        assert_eq!(retention(dir.path().join("other.md")), None);
+    }
+
+    #[test]
+    fn inline_separator_lines_become_basic_cards() {
+        let stream = "# Spanish\n\napple :: manzana\nUse std::vec here.\n\nQ: dog?\nA: perro\n---\nbread :: pan\n";
+        let path = std::path::Path::new("vocab.md");
+        let cards = parse_cards(stream.as_bytes(), path, Some("::"), None, false).unwrap();
+
+        assert_eq!(cards.len(), 3);
+        let CardContent::Basic { question, answer } = &cards[0].content else {
+            panic!("Expected CardContent::Basic");
+        };
+        assert_eq!((question.as_str(), answer.as_str()), ("apple", "manzana"));
+        assert_eq!(cards[0].locator(), (path, 2, 3));
+        assert!(matches!(&cards[2].content, CardContent::Basic { answer, .. } if answer == "pan"));
+
+        // Off by default: the same stream only yields the Q:/A: card.
+        assert_eq!(
+            parse_cards(stream.as_bytes(), path, None, None, false)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn files_with_text_but_no_cards_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("deck.md"), "Q: card?\nA: yes\n").unwrap();
+        std::fs::write(
+            dir.path().join("notes.md"),
+            "# Notes\n\nQ - forgot the colon\n  C: indented\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("blank.md"), "\n  \n").unwrap();
+
+        let (cards, stats) =
+            collect_cards(vec![dir.path().to_path_buf()], CollectOptions::default()).unwrap();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(stats.files_without_cards, vec![dir.path().join("notes.md")]);
+    }
+
+    #[tokio::test]
+    async fn markdown_extension_is_collected_unless_restricted_to_md() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "Q: md?\nA: yes\n").unwrap();
+        std::fs::write(dir.path().join("b.markdown"), "Q: markdown?\nA: yes\n").unwrap();
+        let paths = vec![dir.path().to_path_buf()];
+
+        let md_only = CollectOptions {
+            extensions: Some(vec!["md".into()]),
+            ..Default::default()
+        };
+        let (cards, _) = register_all_cards(&db, paths.clone(), md_only)
+            .await
+            .unwrap();
+        assert_eq!(cards.len(), 1);
+
+        let with_markdown = CollectOptions {
+            extensions: Some(vec!["md".into(), "markdown".into()]),
+            ..Default::default()
+        };
+        let (cards, stats) = register_all_cards(&db, paths, with_markdown).await.unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_eq!(stats.markdown_files, 2);
+    }
+
+    #[test]
+    fn identical_cards_in_one_file_are_reported_with_both_ranges() {
+        let stream = "Q: capital of France?\nA: Paris\n\nQ: other?\nA: yes\n\nQ: capital of France?\nA: Paris\n";
+        let cards = cards_from_reader(stream.as_bytes(), std::path::Path::new("deck.md")).unwrap();
+        assert_eq!(cards.len(), 3);
+
+        assert_eq!(
+            duplicate_warnings(&cards),
+            vec![
+                "deck.md (lines 1-3) and lines 7-8 contain the same card; only one will be scheduled"
+                    .to_string()
+            ]
+        );
+        assert!(duplicate_warnings(&cards[..2]).is_empty());
+
+        // Collecting hands them back rather than printing them.
+        let dir = tempfile::tempdir().unwrap();
+        let deck = dir.path().join("deck.md");
+        std::fs::write(&deck, stream).unwrap();
+        let (_, stats) = collect_cards(vec![deck.clone()], CollectOptions::default()).unwrap();
+        assert_eq!(stats.duplicate_cards.len(), 1);
+        assert!(stats.duplicate_cards[0].starts_with(&format!("{} (lines 1-3)", deck.display())));
+    }
+
+    #[test]
+    fn custom_section_labels_map_to_question_answer_and_cloze() {
+        let labels = SectionLabels::parse("Front, Back, Text").unwrap();
+        let path = std::path::Path::new("deck.md");
+        let stream = "Front: Capital of Peru?\nBack: Lima\n\nText: The capital of Chile is [Santiago].\n\nQ: Still works?\nA: yes\n";
+        let cards = parse_cards(stream.as_bytes(), path, None, Some(&labels), false).unwrap();
+        assert_eq!(cards.len(), 3);
+        let CardContent::Basic { question, answer } = &cards[0].content else {
+            panic!("expected a basic card");
+        };
+        assert_eq!(
+            (question.as_str(), answer.as_str()),
+            ("Capital of Peru?", "Lima")
+        );
+        let CardContent::Cloze { text, cloze_range } = &cards[1].content else {
+            panic!("expected a cloze card");
+        };
+        assert_eq!(text, "The capital of Chile is [Santiago].");
+        assert!(cloze_range.is_some());
+        assert!(matches!(cards[2].content, CardContent::Basic { .. }));
+
+        // Without the labels the same text is not a card.
+        let default_only = parse_cards(stream.as_bytes(), path, None, None, false).unwrap();
+        assert_eq!(default_only.len(), 1);
+
+        assert_eq!(SectionLabels::parse("Front,Back"), None);
+        assert_eq!(SectionLabels::parse("Front,Front,Text"), None);
+        assert_eq!(SectionLabels::parse("Front,,Text"), None);
+        assert_eq!(SectionLabels::parse("Front:,Back:,Text:"), Some(labels));
+    }
+
+    #[test]
+    fn invalid_utf8_is_reported_with_its_file_and_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latin1.md");
+        let mut bytes = b"Q: first?\nA: one\n\nQ: Caf".to_vec();
+        bytes.extend([0xe9, b'?', b'\n']);
+        bytes.extend(b"A: coffee\n");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let err = cards_from_md(&path).unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains(&path.display().to_string()), "{message}");
+        assert!(message.contains("line 4 at byte 24"), "{message}");
+
+        let cards = cards_from_file(&path, true).unwrap();
+        assert_eq!(cards.len(), 2);
+        let CardContent::Basic { question, .. } = &cards[1].content else {
+            panic!("expected a basic card");
+        };
+        assert_eq!(question, "Caf\u{fffd}?");
     }
 
     #[test]
@@ -442,17 +1610,47 @@ mod tests {
         let content = "Q: What is this?\n";
         let result = content_to_card(&card_path, content, 0, 1);
         assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("Unable to parse anything")
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "test.md (lines 1-1): found a question with no answer; add an `A:` section"
         );
 
         // Answer without question
         let content = "A: This is an answer\n";
-        let result = content_to_card(&card_path, content, 0, 1);
-        assert!(result.is_err());
+        let result = content_to_card(&card_path, content, 4, 6);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "test.md (lines 5-6): found an answer with no question; add a `Q:` section"
+        );
+    }
+
+    #[test]
+    fn content_to_card_reports_specific_errors() {
+        let card_path = PathBuf::from("deck/test.md");
+
+        let err = content_to_card(&card_path, "C: this has empty []", 2, 3).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "deck/test.md (lines 3-3): found a cloze with empty brackets; put the hidden text inside `[...]`"
+        );
+
+        let err = content_to_card(&card_path, "C:\n", 4, 5).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "deck/test.md (lines 5-5): found a cloze section but no bracketed text; put the hidden text inside `[...]`"
+        );
+
+        let err = content_to_card(&card_path, "just some prose", 0, 2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "deck/test.md (lines 1-2): no card content found; expected a `Q:`/`A:` pair or a `C:` section"
+        );
+
+        let err = content_to_card(&card_path, "Q:\nA: lonely answer", 0, 2).unwrap_err();
+        assert!(err.to_string().contains("found an answer with no question"));
+
+        let err = content_to_card(&card_path, "   \n", 0, 1).unwrap_err();
+        assert_eq!(err.to_string(), "deck/test.md (lines 1-1): card is empty");
     }
 
     #[test]
@@ -470,6 +1668,27 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn unreadable_paths_are_skipped_unless_strict() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("good.md"), "Q: ok?\nA: yes\n").unwrap();
+        let missing = dir.path().join("missing.md");
+        let paths = vec![dir.path().to_path_buf(), missing];
+
+        let (cards, stats) = register_all_cards(&db, paths.clone(), CollectOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(stats.skipped_files, 1);
+
+        let strict = CollectOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(register_all_cards(&db, paths, strict).await.is_err());
+    }
+
     #[tokio::test]
     async fn register_all_cards_returns_error_for_invalid_card_file() {
         use std::fs;
@@ -489,7 +1708,8 @@ mod tests {
         writeln!(file, "Q: This is a question").unwrap();
         writeln!(file, "C: This is invalid [cloze").unwrap(); // Invalid cloze
 
-        let result = register_all_cards(&db, vec![temp_dir.clone()]).await;
+        let result =
+            register_all_cards(&db, vec![temp_dir.clone()], CollectOptions::default()).await;
 
         // Clean up
         fs::remove_file(&test_file).unwrap();
@@ -0,0 +1,280 @@
+use chrono::Duration;
+use clap::ValueEnum;
+
+use crate::fsrs::{Performance, ReviewStatus, ReviewedPerformance, update_performance};
+
+/// Picks the scheduling algorithm: `fsrs` (default) or `sm2`.
+pub const SCHEDULER_ENV: &str = "REPEATER_SCHEDULER";
+
+/// Turns a review into the card's next schedule.
+pub trait Scheduler {
+    /// `target_recall` is the recall probability intervals aim for; schedulers
+    /// without a recall model may ignore it.
+    fn review(
+        &self,
+        perf: &Performance,
+        status: ReviewStatus,
+        now: chrono::DateTime<chrono::Utc>,
+        target_recall: f64,
+    ) -> ReviewedPerformance;
+
+    /// Adjusts the first review of an `@hard` card, which starts with a
+    /// slightly lower difficulty.
+    fn start_hard(&self, first: ReviewedPerformance) -> ReviewedPerformance {
+        first
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SchedulerKind {
+    #[default]
+    Fsrs,
+    Sm2,
+}
+
+impl SchedulerKind {
+    pub fn from_env() -> Self {
+        let Ok(raw) = std::env::var(SCHEDULER_ENV) else {
+            return Self::default();
+        };
+        Self::from_str(&raw, true).unwrap_or_else(|_| {
+            eprintln!("warning: ignoring {SCHEDULER_ENV}={raw:?}; expected `fsrs` or `sm2`");
+            Self::default()
+        })
+    }
+
+    pub fn scheduler(&self) -> &'static dyn Scheduler {
+        match self {
+            SchedulerKind::Fsrs => &Fsrs,
+            SchedulerKind::Sm2 => &Sm2,
+        }
+    }
+}
+
+/// FSRS difficulty (1-10 scale) taken off `@hard` cards.
+const HARD_DIFFICULTY_DROP: f64 = 1.0;
+const MIN_DIFFICULTY: f64 = 1.0;
+
+/// The default FSRS scheduler with short learning steps for new cards.
+pub struct Fsrs;
+
+impl Scheduler for Fsrs {
+    fn review(
+        &self,
+        perf: &Performance,
+        status: ReviewStatus,
+        now: chrono::DateTime<chrono::Utc>,
+        target_recall: f64,
+    ) -> ReviewedPerformance {
+        update_performance(*perf, status, now, target_recall)
+    }
+
+    fn start_hard(&self, first: ReviewedPerformance) -> ReviewedPerformance {
+        ReviewedPerformance {
+            difficulty: (first.difficulty - HARD_DIFFICULTY_DROP).max(MIN_DIFFICULTY),
+            ..first
+        }
+    }
+}
+
+const SM2_INITIAL_EASE: f64 = 2.5;
+const SM2_MIN_EASE: f64 = 1.3;
+/// SM-2 grades (0-5) recorded for each button.
+const SM2_FAIL_QUALITY: f64 = 1.0;
+const SM2_HARD_QUALITY: f64 = 3.0;
+const SM2_PASS_QUALITY: f64 = 4.0;
+const SM2_EASY_QUALITY: f64 = 5.0;
+/// Interval growth for `Hard`, instead of the ease factor.
+const SM2_HARD_FACTOR: f64 = 1.2;
+/// Extra growth on top of the ease factor for `Easy`.
+const SM2_EASY_BONUS: f64 = 1.3;
+/// Ease added to `@hard` cards at their first review; a higher ease is a
+/// lower difficulty.
+const SM2_HARD_EASE_BONUS: f64 = 0.15;
+/// A failed card is relearned within the session before going back to 1 day.
+const SM2_RELEARN: Duration = Duration::minutes(10);
+/// `Hard` on a card not yet at 1 day, shorter than `Pass`'s first day.
+const SM2_HARD_FIRST: Duration = Duration::hours(12);
+
+/// Classic SuperMemo-2: intervals of 1 day, 6 days, then the previous interval
+/// times the ease factor. The ease factor is stored in the `difficulty` column
+/// and the interval in `stability`, so retrievability estimates still work.
+pub struct Sm2;
+
+impl Scheduler for Sm2 {
+    fn review(
+        &self,
+        perf: &Performance,
+        status: ReviewStatus,
+        now: chrono::DateTime<chrono::Utc>,
+        _target_recall: f64,
+    ) -> ReviewedPerformance {
+        let (ease, previous_interval, elapsed, review_count) = match perf {
+            Performance::New => (SM2_INITIAL_EASE, 0.0, 0.0, 0),
+            Performance::Reviewed(reviewed) => (
+                reviewed.difficulty,
+                reviewed.interval_raw,
+                (now - reviewed.last_reviewed_at).num_seconds().max(0) as f64 / 86_400.0,
+                reviewed.review_count,
+            ),
+        };
+        // A card reviewed ahead of schedule has only held up for the days that
+        // passed, so it grows from those, but never below the interval it had.
+        let grown_from = previous_interval.min(elapsed);
+        let quality = match status {
+            ReviewStatus::Fail => SM2_FAIL_QUALITY,
+            ReviewStatus::Hard => SM2_HARD_QUALITY,
+            ReviewStatus::Pass => SM2_PASS_QUALITY,
+            ReviewStatus::Easy => SM2_EASY_QUALITY,
+        };
+        let miss = 5.0 - quality;
+        let ease = (ease + 0.1 - miss * (0.08 + miss * 0.02)).max(SM2_MIN_EASE);
+
+        let interval = match status {
+            ReviewStatus::Fail => SM2_RELEARN,
+            ReviewStatus::Pass if previous_interval < 1.0 => Duration::days(1),
+            ReviewStatus::Pass if previous_interval < 6.0 => Duration::days(6),
+            ReviewStatus::Pass => {
+                Duration::days((grown_from * ease).max(previous_interval).round() as i64)
+            }
+            ReviewStatus::Hard if previous_interval < 1.0 => SM2_HARD_FIRST,
+            ReviewStatus::Hard => Duration::days(
+                ((grown_from * SM2_HARD_FACTOR)
+                    .max(previous_interval)
+                    .round() as i64)
+                    .max(1),
+            ),
+            ReviewStatus::Easy => Duration::days(
+                (grown_from.max(1.0) * ease * SM2_EASY_BONUS)
+                    .max(previous_interval)
+                    .round() as i64,
+            ),
+        };
+        let interval_raw = interval.num_seconds() as f64 / 86_400.0;
+        ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: interval_raw,
+            difficulty: ease,
+            interval_raw,
+            interval_days: interval.num_days().max(1) as usize,
+            due_date: now + interval,
+            review_count: review_count + 1,
+        }
+    }
+
+    fn start_hard(&self, first: ReviewedPerformance) -> ReviewedPerformance {
+        ReviewedPerformance {
+            difficulty: first.difficulty + SM2_HARD_EASE_BONUS,
+            ..first
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsrs::TARGET_RECALL;
+
+    #[test]
+    fn sm2_intervals_go_one_six_then_scale_by_ease() {
+        let mut now = chrono::Utc::now();
+        let mut perf = Performance::New;
+        let mut intervals = Vec::new();
+        for _ in 0..4 {
+            let reviewed = Sm2.review(&perf, ReviewStatus::Pass, now, TARGET_RECALL);
+            intervals.push(reviewed.interval_days);
+            now = reviewed.due_date;
+            perf = Performance::Reviewed(reviewed);
+        }
+        assert_eq!(intervals, vec![1, 6, 15, 38]);
+
+        let Performance::Reviewed(before_fail) = perf else {
+            unreachable!();
+        };
+        assert_eq!(before_fail.difficulty, SM2_INITIAL_EASE);
+        let failed = Sm2.review(&perf, ReviewStatus::Fail, now, TARGET_RECALL);
+        assert!(failed.interval_raw < 1.0);
+        assert!(failed.difficulty < SM2_INITIAL_EASE);
+
+        // After relearning the card starts over at 1 day with the lower ease.
+        let relearned = Sm2.review(
+            &Performance::Reviewed(failed),
+            ReviewStatus::Pass,
+            now,
+            TARGET_RECALL,
+        );
+        assert_eq!(relearned.interval_days, 1);
+        assert_eq!(relearned.review_count, 6);
+    }
+
+    #[test]
+    fn sm2_early_reviews_grow_from_the_elapsed_days() {
+        let last_reviewed_at = chrono::Utc::now();
+        let perf = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at,
+            stability: 10.0,
+            difficulty: SM2_INITIAL_EASE,
+            interval_raw: 10.0,
+            interval_days: 10,
+            due_date: last_reviewed_at + Duration::days(10),
+            review_count: 3,
+        });
+        let interval_after = |days| {
+            let now = last_reviewed_at + Duration::days(days);
+            Sm2.review(&perf, ReviewStatus::Pass, now, TARGET_RECALL)
+                .interval_days
+        };
+        assert_eq!(interval_after(10), 25);
+        assert_eq!(interval_after(8), 20);
+        // Reviewing right away never shortens the interval the card had.
+        assert_eq!(interval_after(0), 10);
+    }
+
+    #[test]
+    fn sm2_hard_sits_between_fail_and_pass_before_the_first_day() {
+        let now = chrono::Utc::now();
+        let relearning = Performance::Reviewed(Sm2.review(
+            &Performance::New,
+            ReviewStatus::Fail,
+            now,
+            TARGET_RECALL,
+        ));
+        for (label, perf) in [("new", Performance::New), ("relearning", relearning)] {
+            let interval = |status| Sm2.review(&perf, status, now, TARGET_RECALL).interval_raw;
+            let (fail, hard, pass) = (
+                interval(ReviewStatus::Fail),
+                interval(ReviewStatus::Hard),
+                interval(ReviewStatus::Pass),
+            );
+            assert!(fail < hard, "{label}: fail {fail} < hard {hard}");
+            assert!(hard < pass, "{label}: hard {hard} < pass {pass}");
+        }
+    }
+
+    #[test]
+    fn sm2_ease_never_drops_below_the_floor() {
+        let now = chrono::Utc::now();
+        let mut perf = Performance::New;
+        for _ in 0..10 {
+            perf = Performance::Reviewed(Sm2.review(&perf, ReviewStatus::Fail, now, TARGET_RECALL));
+        }
+        let Performance::Reviewed(reviewed) = perf else {
+            unreachable!();
+        };
+        assert_eq!(reviewed.difficulty, SM2_MIN_EASE);
+    }
+
+    #[test]
+    fn fsrs_scheduler_matches_update_performance() {
+        let now = chrono::Utc::now();
+        assert_eq!(
+            SchedulerKind::Fsrs.scheduler().review(
+                &Performance::New,
+                ReviewStatus::Pass,
+                now,
+                TARGET_RECALL
+            ),
+            update_performance(Performance::New, ReviewStatus::Pass, now, TARGET_RECALL)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use crate::crud::DB;
+use crate::tui::terminal::restore_terminal;
+
+/// How long to wait for in-flight queries before exiting anyway.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Listens for SIGINT/SIGTERM in the background; on either, restores the
+/// terminal, closes the database pool, and exits. Inside the TUIs raw mode
+/// turns Ctrl+C into a key press, so this mostly catches `kill` and Ctrl+C
+/// during parsing or imports.
+pub fn spawn_signal_handler(db: DB) {
+    tokio::spawn(async move {
+        let exit_code = wait_for_signal().await;
+        cleanup(&db).await;
+        std::process::exit(exit_code);
+    });
+}
+
+/// Resolves with the conventional exit code (128 + signal number).
+async fn wait_for_signal() -> i32 {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            return tokio::select! {
+                _ = tokio::signal::ctrl_c() => 130,
+                _ = terminate.recv() => 143,
+            };
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+    130
+}
+
+/// Leaves the TUI if one is active and closes the pool so SQLite can
+/// checkpoint its WAL. Safe to call more than once.
+pub async fn cleanup(db: &DB) {
+    if crossterm::terminal::is_raw_mode_enabled().unwrap_or(false) {
+        restore_terminal();
+    }
+    if tokio::time::timeout(CLOSE_TIMEOUT, db.close())
+        .await
+        .is_err()
+    {
+        eprintln!("warning: timed out waiting for the database to close");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cleanup_is_idempotent() {
+        let db = DB::new_in_memory().await.unwrap();
+        cleanup(&db).await;
+        assert!(db.is_closed());
+
+        cleanup(&db).await;
+        assert!(db.is_closed());
+        assert!(db.last_session().await.is_err());
+    }
+}
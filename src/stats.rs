@@ -2,21 +2,38 @@ use std::collections::{BTreeMap, HashMap};
 
 use std::path::PathBuf;
 
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, Utc};
+
 use crate::card::Card;
+use crate::crud::sessions::SessionRecord;
 use crate::crud::stats::CardStatsRow;
 use crate::fsrs::{LEARN_AHEAD_THRESHOLD_MINS, calculate_recall};
 
 #[derive(Debug, Default)]
 pub struct CardStats {
     pub total_cards_in_db: i64,
+    pub added_today: i64,
+    pub reviewed_today: i64,
     pub num_cards: i64,
     pub card_lifecycles: HashMap<CardLifeCycle, i64>,
     pub due_cards: i64,
-    pub upcoming_week: BTreeMap<String, usize>,
+    pub upcoming_week: BTreeMap<NaiveDate, usize>,
     pub upcoming_month: i64,
     pub file_paths: HashMap<PathBuf, usize>,
     pub difficulty_histogram: Histogram<5>,
     pub retrievability_histogram: Histogram<5>,
+    pub day_zone: DayZone,
+    pub last_session: Option<SessionRecord>,
+    /// DB rows whose hash matches none of the scanned cards, sorted by hash.
+    pub ghosts: Vec<GhostCard>,
+}
+
+/// A card still tracked in the DB but no longer present in any scanned file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GhostCard {
+    pub card_hash: String,
+    pub last_reviewed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone)]
@@ -54,13 +71,175 @@ impl<const N: usize> Histogram<N> {
     }
 }
 
+/// UTC bounds `[start, end)` of the local calendar day containing `now`.
+pub fn local_day_bounds<Tz: chrono::TimeZone>(
+    now: chrono::DateTime<Tz>,
+) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
+    let tz = now.timezone();
+    let midnight = |date: chrono::NaiveDate| {
+        let naive = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+        tz.from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.to_utc())
+            .unwrap_or_else(|| naive.and_utc())
+    };
+    let today = now.date_naive();
+    let tomorrow = today.succ_opt().unwrap_or(today);
+    (midnight(today), midnight(tomorrow))
+}
+
+/// Overrides the timezone used to decide which calendar day a timestamp falls on.
+pub const TZ_ENV: &str = "REPEATER_TZ";
+/// strftime pattern for dates shown in `check`.
+pub const DATE_FORMAT_ENV: &str = "REPEATER_DATE_FORMAT";
+const DEFAULT_DATE_FORMAT: &str = "%a %d";
+
+/// The timezone that defines "today" and day buckets: the system's local zone
+/// unless `REPEATER_TZ` sets `UTC` or a fixed offset such as `+05:30`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DayZone {
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl DayZone {
+    pub fn from_env() -> Self {
+        let Ok(raw) = std::env::var(TZ_ENV) else {
+            return DayZone::Local;
+        };
+        Self::parse(&raw).unwrap_or_else(|| {
+            eprintln!("warning: ignoring {TZ_ENV}={raw:?}; expected `local`, `UTC`, or an offset like `+05:30`");
+            DayZone::Local
+        })
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if raw.eq_ignore_ascii_case("local") {
+            return Some(DayZone::Local);
+        }
+        if raw.eq_ignore_ascii_case("utc") || raw == "Z" {
+            return FixedOffset::east_opt(0).map(DayZone::Fixed);
+        }
+        let (sign, rest) = match raw.as_bytes().first()? {
+            b'+' => (1, &raw[1..]),
+            b'-' => (-1, &raw[1..]),
+            _ => return None,
+        };
+        let (hours, minutes) = match rest.split_once(':') {
+            Some((h, m)) => (h, m),
+            None if rest.len() == 4 => rest.split_at(2),
+            None => (rest, "0"),
+        };
+        let hours: i32 = hours.parse().ok()?;
+        let minutes: i32 = minutes.parse().ok()?;
+        if !(0..60).contains(&minutes) {
+            return None;
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(DayZone::Fixed)
+    }
+
+    /// Calendar day `at` falls on in this zone.
+    pub fn date_of(&self, at: DateTime<Utc>) -> NaiveDate {
+        match self {
+            DayZone::Local => at.with_timezone(&Local).date_naive(),
+            DayZone::Fixed(offset) => at.with_timezone(offset).date_naive(),
+        }
+    }
+
+    /// UTC bounds `[start, end)` of the day containing `now` in this zone.
+    pub fn day_bounds(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        match self {
+            DayZone::Local => local_day_bounds(now.with_timezone(&Local)),
+            DayZone::Fixed(offset) => local_day_bounds(now.with_timezone(offset)),
+        }
+    }
+}
+
+/// Date format for display, from `REPEATER_DATE_FORMAT` when it is a valid strftime pattern.
+pub fn display_date_format() -> String {
+    match std::env::var(DATE_FORMAT_ENV) {
+        Ok(format) if is_valid_date_format(&format) => format,
+        Ok(format) => {
+            eprintln!("warning: ignoring invalid {DATE_FORMAT_ENV}={format:?}");
+            DEFAULT_DATE_FORMAT.to_string()
+        }
+        Err(_) => DEFAULT_DATE_FORMAT.to_string(),
+    }
+}
+
+fn is_valid_date_format(format: &str) -> bool {
+    !format.is_empty() && !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+/// Weeks shown by `check --heatmap`, ending with the current one.
+pub const HEATMAP_WEEKS: usize = 12;
+
+/// Reviews per calendar day over whole weeks, Monday first, for a
+/// contribution-style heatmap. Days after `today` are in the grid but empty.
+#[derive(Debug, PartialEq)]
+pub struct ReviewHeatmap {
+    /// The Monday the first column starts on.
+    pub start: NaiveDate,
+    pub today: NaiveDate,
+    /// One column per week, each holding Monday through Sunday.
+    pub weeks: Vec<[usize; 7]>,
+}
+
+impl ReviewHeatmap {
+    /// Buckets `reviewed_at` times into the days of the `weeks` weeks ending
+    /// with the one holding `today`. Times outside the window are ignored.
+    pub fn from_reviews(
+        reviewed_at: &[DateTime<Utc>],
+        today: NaiveDate,
+        weeks: usize,
+        day_zone: DayZone,
+    ) -> Self {
+        let this_monday =
+            today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+        let start = this_monday - chrono::Duration::weeks(weeks.saturating_sub(1) as i64);
+        let mut grid = vec![[0; 7]; weeks];
+        for &at in reviewed_at {
+            let day = day_zone.date_of(at);
+            if day > today {
+                continue;
+            }
+            let Ok(offset) = usize::try_from((day - start).num_days()) else {
+                continue;
+            };
+            if let Some(week) = grid.get_mut(offset / 7) {
+                week[offset % 7] += 1;
+            }
+        }
+        Self {
+            start,
+            today,
+            weeks: grid,
+        }
+    }
+
+    pub fn busiest_day(&self) -> usize {
+        self.weeks.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.weeks.iter().flatten().sum()
+    }
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum CardLifeCycle {
     New,
+    /// Reviewed but not graduated: still in its first short steps, or back in
+    /// them after a lapse.
+    Learning,
     Young,
     Mature,
 }
 const MATURE_INTERVAL: f64 = 21.0;
+/// Cards scheduled no more than this many days out have not graduated yet.
+const LEARNING_INTERVAL: f64 = 1.0;
 
 impl CardStats {
     // row is a Record
@@ -72,13 +251,16 @@ impl CardStats {
         let stability = row.stability.unwrap_or_default();
         let last_reviewed_at = row.last_reviewed_at;
 
-        let now = chrono::Utc::now();
+        let now = Utc::now();
+        let today = self.day_zone.date_of(now);
         let week_horizon = now + chrono::Duration::days(7);
         let month_horizon = now + chrono::Duration::days(30);
         *self.file_paths.entry(card.file_path.clone()).or_insert(0) += 1;
 
         let lifecycle = if review_count == 0 {
             CardLifeCycle::New
+        } else if interval <= LEARNING_INTERVAL {
+            CardLifeCycle::Learning
         } else if interval > MATURE_INTERVAL {
             CardLifeCycle::Mature
         } else {
@@ -90,19 +272,17 @@ impl CardStats {
         match due_date {
             None => {
                 self.due_cards += 1;
-                let day = now.format("%Y-%m-%d").to_string();
-                *self.upcoming_week.entry(day).or_insert(0) += 1;
+                *self.upcoming_week.entry(today).or_insert(0) += 1;
                 self.upcoming_month += 1;
             }
             Some(due_date) => {
                 if due_date <= now + LEARN_AHEAD_THRESHOLD_MINS {
                     self.due_cards += 1;
-                    let day = now.format("%Y-%m-%d").to_string();
-                    *self.upcoming_week.entry(day).or_insert(0) += 1;
+                    *self.upcoming_week.entry(today).or_insert(0) += 1;
                     self.upcoming_month += 1;
                 } else {
                     if due_date <= week_horizon {
-                        let day = due_date.format("%Y-%m-%d").to_string();
+                        let day = self.day_zone.date_of(due_date);
                         *self.upcoming_week.entry(day).or_insert(0) += 1;
                     }
 
@@ -194,6 +374,35 @@ mod tests {
         assert_eq!(stats.upcoming_week.values().sum::<usize>(), 1);
     }
 
+    #[test]
+    fn a_card_failed_on_its_first_review_is_learning() {
+        let mut stats = CardStats::default();
+        let card = sample_card("deck/file.md");
+        let failed = crate::fsrs::update_performance(
+            crate::fsrs::Performance::New,
+            crate::fsrs::ReviewStatus::Fail,
+            Utc::now(),
+            crate::fsrs::TARGET_RECALL,
+        );
+        let mut row = default_row();
+        row.review_count = failed.review_count as i64;
+        row.interval_raw = Some(failed.interval_raw);
+        row.due_date = Some(failed.due_date);
+
+        stats.update(&card, &row);
+
+        assert_eq!(
+            stats.card_lifecycles.get(&CardLifeCycle::Learning),
+            Some(&1)
+        );
+        assert_eq!(stats.card_lifecycles.get(&CardLifeCycle::Young), None);
+        assert_eq!(stats.card_lifecycles.get(&CardLifeCycle::New), None);
+
+        row.interval_raw = Some(4.0);
+        stats.update(&card, &row);
+        assert_eq!(stats.card_lifecycles.get(&CardLifeCycle::Young), Some(&1));
+    }
+
     #[test]
     fn updates_retrievability_histogram_when_reviewed() {
         let mut stats = CardStats::default();
@@ -211,6 +420,106 @@ mod tests {
         assert_eq!(stats.retrievability_histogram.bins[idx], 1);
     }
 
+    #[test]
+    fn local_day_bounds_follow_the_local_calendar_day() {
+        use chrono::{FixedOffset, TimeZone};
+
+        let tz = FixedOffset::west_opt(5 * 3600).unwrap();
+        let now = tz.with_ymd_and_hms(2024, 3, 10, 23, 30, 0).unwrap();
+        let (start, end) = local_day_bounds(now);
+        assert_eq!(start.to_rfc3339(), "2024-03-10T05:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2024-03-11T05:00:00+00:00");
+    }
+
+    #[test]
+    fn due_dates_near_midnight_bucket_into_the_configured_day() {
+        use chrono::TimeZone;
+
+        let tokyo = DayZone::parse("+09:00").unwrap();
+        let new_york = DayZone::parse("-0500").unwrap();
+        let late_evening_utc = Utc.with_ymd_and_hms(2024, 1, 1, 20, 0, 0).unwrap();
+        let early_morning_utc = Utc.with_ymd_and_hms(2024, 1, 2, 3, 0, 0).unwrap();
+
+        assert_eq!(
+            tokyo.date_of(late_evening_utc),
+            NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+        );
+        assert_eq!(
+            new_york.date_of(early_morning_utc),
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+        );
+
+        let mut stats = CardStats {
+            day_zone: tokyo,
+            ..Default::default()
+        };
+        let mut row = default_row();
+        row.review_count = 3;
+        let due = Utc::now() + Duration::days(2);
+        row.due_date = Some(due);
+        stats.update(&sample_card("deck/file.md"), &row);
+        let expected = due.with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap());
+        assert_eq!(stats.upcoming_week.get(&expected.date_naive()), Some(&1));
+    }
+
+    #[test]
+    fn heatmap_buckets_reviews_into_day_cells() {
+        use chrono::TimeZone;
+
+        let utc = DayZone::parse("UTC").unwrap();
+        // A Wednesday.
+        let today = NaiveDate::from_ymd_opt(2024, 3, 13).unwrap();
+        let at = |month, day, hour| Utc.with_ymd_and_hms(2024, month, day, hour, 0, 0).unwrap();
+        let reviews = [
+            at(3, 13, 9),
+            at(3, 13, 23),
+            at(3, 11, 0),
+            // Sunday of the previous week.
+            at(3, 10, 12),
+            // First day of the window and the day before it.
+            at(2, 26, 8),
+            at(2, 25, 8),
+            // Tomorrow.
+            at(3, 14, 1),
+        ];
+        let heatmap = ReviewHeatmap::from_reviews(&reviews, today, 3, utc);
+
+        assert_eq!(heatmap.start, NaiveDate::from_ymd_opt(2024, 2, 26).unwrap());
+        assert_eq!(
+            heatmap.weeks,
+            vec![
+                [1, 0, 0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0, 0, 1],
+                [1, 0, 2, 0, 0, 0, 0],
+            ]
+        );
+        assert_eq!(heatmap.busiest_day(), 2);
+        assert_eq!(heatmap.total(), 5);
+
+        // Days follow the configured zone: 23:00 UTC is tomorrow in Tokyo.
+        let tokyo = DayZone::parse("+09:00").unwrap();
+        let shifted = ReviewHeatmap::from_reviews(&[at(3, 12, 23)], today, 1, tokyo);
+        assert_eq!(shifted.weeks, vec![[0, 0, 1, 0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn parses_timezone_overrides_and_date_formats() {
+        assert_eq!(DayZone::parse("local"), Some(DayZone::Local));
+        assert_eq!(
+            DayZone::parse("UTC"),
+            Some(DayZone::Fixed(FixedOffset::east_opt(0).unwrap()))
+        );
+        assert_eq!(
+            DayZone::parse("+5:30"),
+            Some(DayZone::Fixed(
+                FixedOffset::east_opt(5 * 3600 + 1800).unwrap()
+            ))
+        );
+        assert_eq!(DayZone::parse("Europe/Paris"), None);
+        assert!(is_valid_date_format("%d/%m"));
+        assert!(!is_valid_date_format("%Q"));
+    }
+
     #[test]
     fn histogram_mean_returns_none_when_empty() {
         let histogram: Histogram<5> = Histogram::default();
@@ -0,0 +1,201 @@
+use std::io;
+use std::time::Duration;
+
+use anyhow::Result;
+use crossterm::{
+    event::{self, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Terminal,
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    text::Line,
+    widgets::{List, ListItem, ListState, Paragraph},
+};
+
+use super::{TerminalGuard, Theme, read_key};
+
+/// Which items of a checklist are ticked, and which one the cursor is on.
+/// Every item starts ticked.
+#[derive(Clone, Debug)]
+pub struct Checklist {
+    checked: Vec<bool>,
+    cursor: usize,
+}
+
+impl Checklist {
+    pub fn new(len: usize) -> Self {
+        Self {
+            checked: vec![true; len],
+            cursor: 0,
+        }
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_checked(&self, index: usize) -> bool {
+        self.checked.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn move_up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.cursor + 1 < self.checked.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Ticks or unticks the item under the cursor.
+    pub fn toggle(&mut self) {
+        if let Some(checked) = self.checked.get_mut(self.cursor) {
+            *checked = !*checked;
+        }
+    }
+
+    /// Unticks everything if every item is ticked, otherwise ticks everything.
+    pub fn toggle_all(&mut self) {
+        let tick = !self.checked.iter().all(|checked| *checked);
+        self.checked.fill(tick);
+    }
+
+    /// Indices of the ticked items, in order.
+    pub fn selected(&self) -> Vec<usize> {
+        self.checked
+            .iter()
+            .enumerate()
+            .filter(|(_, checked)| **checked)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Shows `items` as a checklist and returns the indices the user confirmed
+/// with Enter, or `None` if they backed out with Esc.
+pub fn pick(title: &str, items: &[String]) -> Result<Option<Vec<usize>>> {
+    enable_raw_mode()?;
+    let guard = TerminalGuard::for_terminal();
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let mut checklist = Checklist::new(items.len());
+    let mut list_state = ListState::default();
+    let result = loop {
+        list_state.select(Some(checklist.cursor()));
+        terminal.draw(|frame| {
+            let area = frame.area();
+            frame.render_widget(Theme::backdrop(), area);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(area);
+
+            let rows: Vec<ListItem> = items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let mark = if checklist.is_checked(index) {
+                        "[x]"
+                    } else {
+                        "[ ]"
+                    };
+                    ListItem::new(Line::from(vec![
+                        Theme::label_span(mark),
+                        Theme::span(format!(" {item}")),
+                    ]))
+                })
+                .collect();
+            let selected = checklist.selected().len();
+            let list = List::new(rows)
+                .block(Theme::panel(format!(
+                    "{title} ({selected}/{} selected)",
+                    items.len()
+                )))
+                .highlight_style(Theme::emphasis())
+                .highlight_symbol("> ");
+            frame.render_stateful_widget(list, chunks[0], &mut list_state);
+
+            let help = Paragraph::new(Line::from(vec![
+                Theme::key_chip("Space"),
+                Theme::span(" toggle"),
+                Theme::bullet(),
+                Theme::key_chip("A"),
+                Theme::span(" all/none"),
+                Theme::bullet(),
+                Theme::key_chip("Enter"),
+                Theme::span(" confirm"),
+                Theme::bullet(),
+                Theme::key_chip("Esc"),
+                Theme::span(" cancel"),
+            ]))
+            .block(Theme::panel_with_line(Theme::section_header("Controls")));
+            frame.render_widget(help, chunks[1]);
+        })?;
+
+        if event::poll(Duration::from_millis(16))?
+            && let Some(key) = read_key(&mut terminal)?
+        {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => break None,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break None,
+                KeyCode::Enter => break Some(checklist.selected()),
+                KeyCode::Char(' ') => checklist.toggle(),
+                KeyCode::Char('a') | KeyCode::Char('A') => checklist.toggle_all(),
+                KeyCode::Up | KeyCode::Char('k') => checklist.move_up(),
+                KeyCode::Down | KeyCode::Char('j') => checklist.move_down(),
+                _ => {}
+            }
+        }
+    };
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    guard.disarm();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checklist_toggles_items_under_the_cursor() {
+        let mut checklist = Checklist::new(3);
+        assert_eq!(checklist.selected(), vec![0, 1, 2]);
+
+        checklist.toggle();
+        checklist.move_down();
+        checklist.move_down();
+        checklist.move_down();
+        assert_eq!(checklist.cursor(), 2);
+        checklist.toggle();
+        assert_eq!(checklist.selected(), vec![1]);
+
+        checklist.move_up();
+        checklist.move_up();
+        checklist.move_up();
+        assert_eq!(checklist.cursor(), 0);
+        checklist.toggle();
+        assert_eq!(checklist.selected(), vec![0, 1]);
+
+        checklist.toggle_all();
+        assert_eq!(checklist.selected(), vec![0, 1, 2]);
+        checklist.toggle_all();
+        assert!(checklist.selected().is_empty());
+
+        let mut empty = Checklist::new(0);
+        empty.toggle();
+        empty.move_down();
+        assert!(empty.selected().is_empty());
+    }
+}
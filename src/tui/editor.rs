@@ -22,6 +22,19 @@ impl Editor {
             card_type,
         }
     }
+    /// Starts with `content` instead of the empty template, cursor at its end.
+    pub fn with_content(card_type: CardType, content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut editor = Self::new();
+        editor.card_type = card_type;
+        if !lines.is_empty() {
+            editor.lines = lines;
+        }
+        editor.cursor_row = editor.lines.len() - 1;
+        editor.cursor_col = editor.line_len(editor.cursor_row);
+        editor
+    }
+
     fn init_lines(card_type: &CardType) -> Vec<String> {
         match card_type {
             CardType::Basic => vec!["Q: ".to_string(), "A: ".to_string()],
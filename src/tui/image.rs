@@ -0,0 +1,356 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::parser::{Media, MediaKind};
+
+/// Overrides protocol detection: `kitty`, `iterm`, `sixel`, or `none`.
+pub const IMAGE_PROTOCOL_ENV: &str = "REPEATER_IMAGE_PROTOCOL";
+
+/// Used when the terminal does not report its size in pixels.
+const FALLBACK_CELL_PX: (u32, u32) = (10, 20);
+/// Kitty expects base64 payloads split into chunks of at most this many bytes.
+const KITTY_CHUNK: usize = 4096;
+
+/// Terminal graphics protocols that can draw an image inline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    ITerm,
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    /// Guesses the protocol from the environment, honouring `REPEATER_IMAGE_PROTOCOL`.
+    pub fn detect() -> Option<Self> {
+        Self::from_env(|key| std::env::var(key).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if let Some(raw) = var(IMAGE_PROTOCOL_ENV) {
+            return match raw.trim().to_lowercase().as_str() {
+                "kitty" => Some(Self::Kitty),
+                "iterm" | "iterm2" => Some(Self::ITerm),
+                "sixel" => Some(Self::Sixel),
+                _ => None,
+            };
+        }
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if term == "xterm-kitty"
+            || var("KITTY_WINDOW_ID").is_some()
+            || matches!(program.as_str(), "WezTerm" | "ghostty")
+        {
+            return Some(Self::Kitty);
+        }
+        if program == "iTerm.app" || var("LC_TERMINAL").as_deref() == Some("iTerm2") {
+            return Some(Self::ITerm);
+        }
+        if term.contains("sixel") || matches!(term.as_str(), "foot" | "mlterm" | "contour") {
+            return Some(Self::Sixel);
+        }
+        None
+    }
+
+    /// Kitty and sixel are fed PNG data here; iTerm2 decodes other formats itself.
+    fn can_draw(&self, path: &Path) -> bool {
+        match self {
+            Self::ITerm => true,
+            Self::Kitty | Self::Sixel => is_png(path),
+        }
+    }
+}
+
+/// The image to draw for a card: the first image the protocol can display.
+/// `protocol` is `None` when `--images` is off or the terminal has no support.
+pub fn inline_image(medias: &[Media], protocol: Option<GraphicsProtocol>) -> Option<&Media> {
+    let protocol = protocol?;
+    medias
+        .iter()
+        .find(|media| *media.kind() == MediaKind::Image && protocol.can_draw(media.path()))
+}
+
+/// Escape sequence that removes previously drawn images, if the protocol needs one.
+/// For the cell-based protocols a full redraw does the job instead.
+pub fn clear_sequence(protocol: GraphicsProtocol) -> &'static str {
+    match protocol {
+        GraphicsProtocol::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+        GraphicsProtocol::ITerm | GraphicsProtocol::Sixel => "",
+    }
+}
+
+/// Encodes the image at `path` to fit in a `cols` x `rows` cell box.
+pub fn encode_image(
+    protocol: GraphicsProtocol,
+    path: &Path,
+    cols: u16,
+    rows: u16,
+) -> Result<String> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    match protocol {
+        GraphicsProtocol::Kitty => Ok(encode_kitty(&bytes, cols, rows)),
+        GraphicsProtocol::ITerm => Ok(encode_iterm(&bytes, cols, rows)),
+        GraphicsProtocol::Sixel => {
+            let image = decode_png(&bytes)?;
+            let (cell_w, cell_h) = cell_pixels();
+            Ok(encode_sixel(
+                &image,
+                u32::from(cols) * cell_w,
+                u32::from(rows) * cell_h,
+            ))
+        }
+    }
+}
+
+fn encode_kitty(png: &[u8], cols: u16, rows: u16) -> String {
+    let payload = STANDARD.encode(png);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(idx + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).expect("base64 is ascii");
+        if idx == 0 {
+            // `C=1` keeps the cursor still so the TUI layout is not disturbed.
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=100,q=2,C=1,c={cols},r={rows},m={more};{chunk}\x1b\\"
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={more};{chunk}\x1b\\");
+        }
+    }
+    out
+}
+
+fn encode_iterm(bytes: &[u8], cols: u16, rows: u16) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=1:{}\x07",
+        bytes.len(),
+        STANDARD.encode(bytes)
+    )
+}
+
+struct Rgba {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Rgba> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().context("failed to read png header")?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .context("failed to decode png")?;
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => bail!("unexpected indexed png after expansion"),
+    };
+    let mut pixels = Vec::with_capacity((info.width * info.height * 4) as usize);
+    for px in buf[..info.buffer_size()].chunks(channels) {
+        let rgba = match px {
+            [l] => [*l, *l, *l, 255],
+            [l, a] => [*l, *l, *l, *a],
+            [r, g, b] => [*r, *g, *b, 255],
+            [r, g, b, a] => [*r, *g, *b, *a],
+            _ => unreachable!("chunks match the channel count"),
+        };
+        pixels.extend_from_slice(&rgba);
+    }
+    Ok(Rgba {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn cell_pixels() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            u32::from(size.width / size.columns),
+            u32::from(size.height / size.rows),
+        ),
+        _ => FALLBACK_CELL_PX,
+    }
+}
+
+/// Sixel encoding against a fixed 6x6x6 colour cube, scaled down (never up)
+/// with nearest-neighbour sampling to fit `max_w` x `max_h` pixels.
+fn encode_sixel(image: &Rgba, max_w: u32, max_h: u32) -> String {
+    let scale = f64::min(
+        1.0,
+        f64::min(
+            f64::from(max_w) / f64::from(image.width),
+            f64::from(max_h) / f64::from(image.height),
+        ),
+    );
+    let width = ((f64::from(image.width) * scale) as u32).max(1);
+    let height = ((f64::from(image.height) * scale) as u32).max(1);
+    // Palette index per output pixel, `None` where the source is transparent.
+    let indexed: Vec<Option<usize>> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let sx = (u64::from(x) * u64::from(image.width) / u64::from(width)) as usize;
+            let sy = (u64::from(y) * u64::from(image.height) / u64::from(height)) as usize;
+            let offset = (sy * image.width as usize + sx) * 4;
+            let [r, g, b, a] = image.pixels[offset..offset + 4] else {
+                unreachable!("rgba pixels are four bytes");
+            };
+            (a >= 128).then(|| cube_index(r, g, b))
+        })
+        .collect();
+
+    let mut out = String::from("\x1bP0;1;0q");
+    let _ = write!(out, "\"1;1;{width};{height}");
+    for idx in 0..216 {
+        let (r, g, b) = (idx / 36, idx / 6 % 6, idx % 6);
+        let _ = write!(out, "#{idx};2;{};{};{}", r * 20, g * 20, b * 20);
+    }
+    for band in (0..height).step_by(6) {
+        let mut colors: Vec<usize> = (band..(band + 6).min(height))
+            .flat_map(|y| (0..width).map(move |x| (y * width + x) as usize))
+            .filter_map(|i| indexed[i])
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+        for (n, color) in colors.iter().enumerate() {
+            if n > 0 {
+                out.push('$');
+            }
+            let _ = write!(out, "#{color}");
+            let row: Vec<u8> = (0..width)
+                .map(|x| {
+                    (0..6).fold(0u8, |bits, bit| {
+                        let y = band + bit;
+                        let hit = y < height && indexed[(y * width + x) as usize] == Some(*color);
+                        bits | (u8::from(hit) << bit)
+                    })
+                })
+                .collect();
+            push_sixel_runs(&mut out, &row);
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn cube_index(r: u8, g: u8, b: u8) -> usize {
+    let level = |c: u8| (usize::from(c) * 5 + 127) / 255;
+    level(r) * 36 + level(g) * 6 + level(b)
+}
+
+/// Appends sixel characters, collapsing repeats with `!<count>`.
+fn push_sixel_runs(out: &mut String, row: &[u8]) {
+    let mut iter = row.iter().peekable();
+    while let Some(&bits) = iter.next() {
+        let mut count = 1;
+        while iter.peek() == Some(&&bits) {
+            iter.next();
+            count += 1;
+        }
+        let ch = char::from(b'?' + bits);
+        if count > 3 {
+            let _ = write!(out, "!{count}{ch}");
+        } else {
+            out.extend(std::iter::repeat_n(ch, count));
+        }
+    }
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::parser::extract_media;
+
+    fn env(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn detects_protocols_from_the_environment() {
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[("TERM", "xterm-kitty")])),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[("TERM_PROGRAM", "iTerm.app")])),
+            Some(GraphicsProtocol::ITerm)
+        );
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[("TERM", "foot")])),
+            Some(GraphicsProtocol::Sixel)
+        );
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[("TERM", "xterm-256color")])),
+            None
+        );
+        assert_eq!(
+            GraphicsProtocol::from_env(env(&[
+                ("TERM", "xterm-kitty"),
+                (IMAGE_PROTOCOL_ENV, "none")
+            ])),
+            None
+        );
+    }
+
+    #[test]
+    fn inline_image_needs_an_image_and_a_protocol_that_can_draw_it() {
+        let png = extract_media("![chart](media/chart.png) [audio](media/a.mp3)", None);
+        let jpg = extract_media("![photo](media/photo.jpg)", None);
+        let audio_only = extract_media("[audio](media/a.mp3)", None);
+
+        assert!(inline_image(&png, None).is_none());
+        assert_eq!(
+            inline_image(&png, Some(GraphicsProtocol::Kitty)).map(Media::path),
+            Some(Path::new("media/chart.png"))
+        );
+        assert!(inline_image(&jpg, Some(GraphicsProtocol::Sixel)).is_none());
+        assert!(inline_image(&jpg, Some(GraphicsProtocol::ITerm)).is_some());
+        assert!(inline_image(&audio_only, Some(GraphicsProtocol::ITerm)).is_none());
+    }
+
+    #[test]
+    fn sixel_encoding_covers_every_pixel_band() {
+        // 2x7 opaque red image: two sixel bands.
+        let image = Rgba {
+            width: 2,
+            height: 7,
+            pixels: [255, 0, 0, 255].repeat(14),
+        };
+        let sixel = encode_sixel(&image, 100, 100);
+        let red = cube_index(255, 0, 0);
+        assert!(sixel.starts_with("\x1bP"));
+        assert!(sixel.ends_with("\x1b\\"));
+        assert!(sixel.contains(&format!("#{red}~~-#{red}@@-")));
+    }
+
+    #[test]
+    fn kitty_payload_is_chunked() {
+        let encoded = encode_kitty(&vec![0u8; KITTY_CHUNK], 10, 5);
+        assert!(encoded.starts_with("\x1b_Ga=T,f=100"));
+        assert!(encoded.contains(",m=1;"));
+        assert!(encoded.contains("\x1b_Gm=0;"));
+    }
+}
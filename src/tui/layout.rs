@@ -0,0 +1,28 @@
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+
+/// Narrows `area` to at most `max_width` columns, centered horizontally.
+/// `None`, or a limit wider than the terminal, keeps the full width.
+pub fn centered_column(area: Rect, max_width: Option<u16>) -> Rect {
+    let Some(max_width) = max_width else {
+        return area;
+    };
+    Layout::horizontal([Constraint::Max(max_width)])
+        .flex(Flex::Center)
+        .split(area)[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centered_column_bounds_and_centers_the_content() {
+        let area = Rect::new(0, 0, 200, 50);
+        assert_eq!(centered_column(area, None), area);
+        assert_eq!(centered_column(area, Some(80)), Rect::new(60, 0, 80, 50));
+        assert_eq!(centered_column(area, Some(300)), area);
+
+        let offset = Rect::new(10, 2, 101, 20);
+        assert_eq!(centered_column(offset, Some(81)), Rect::new(20, 2, 81, 20));
+    }
+}
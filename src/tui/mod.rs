@@ -1,5 +1,13 @@
+pub mod checklist;
 pub mod editor;
+pub mod image;
+pub mod layout;
+pub mod spinner;
+pub mod terminal;
 pub mod theme;
 
 pub use editor::Editor;
+pub use layout::centered_column;
+pub use spinner::Spinner;
+pub use terminal::{TerminalGuard, read_key};
 pub use theme::Theme;
@@ -0,0 +1,58 @@
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+/// Quick runs finish before this and never see the spinner.
+const SHOW_AFTER: Duration = Duration::from_millis(300);
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Animates `message` on stderr while slow work runs; the line is cleared when
+/// the spinner is dropped. Does nothing when stderr is not a terminal.
+pub struct Spinner {
+    task: Option<JoinHandle<()>>,
+    shown: Arc<AtomicBool>,
+}
+
+impl Spinner {
+    pub fn start(message: impl Into<String>) -> Self {
+        let shown = Arc::new(AtomicBool::new(false));
+        if !std::io::stderr().is_terminal() {
+            return Self { task: None, shown };
+        }
+        let message = message.into();
+        let task = tokio::spawn({
+            let shown = Arc::clone(&shown);
+            async move {
+                tokio::time::sleep(SHOW_AFTER).await;
+                shown.store(true, Ordering::SeqCst);
+                for frame in FRAMES.iter().cycle() {
+                    let mut stderr = std::io::stderr();
+                    let _ = write!(stderr, "\r{frame} {message}");
+                    let _ = stderr.flush();
+                    tokio::time::sleep(FRAME_INTERVAL).await;
+                }
+            }
+        });
+        Self {
+            task: Some(task),
+            shown,
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        if self.shown.load(Ordering::SeqCst) {
+            let mut stderr = std::io::stderr();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+}
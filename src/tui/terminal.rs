@@ -0,0 +1,154 @@
+use std::io;
+
+use crossterm::{
+    cursor::Show,
+    event::{self, Event, KeyEvent, PopKeyboardEnhancementFlags},
+    execute,
+    terminal::{LeaveAlternateScreen, disable_raw_mode},
+};
+use ratatui::{Terminal, backend::Backend};
+
+/// Runs a restore routine when dropped, so a panic inside a TUI loop still
+/// hands the user back a usable terminal. Call `disarm` once the normal
+/// teardown has run.
+pub struct TerminalGuard<F: FnOnce() = fn()> {
+    restore: Option<F>,
+}
+
+impl TerminalGuard {
+    /// Guard that leaves raw mode and the alternate screen on drop.
+    pub fn for_terminal() -> Self {
+        Self::new(restore_terminal)
+    }
+}
+
+impl<F: FnOnce()> TerminalGuard<F> {
+    pub fn new(restore: F) -> Self {
+        Self {
+            restore: Some(restore),
+        }
+    }
+
+    pub fn disarm(mut self) {
+        self.restore = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for TerminalGuard<F> {
+    fn drop(&mut self) {
+        if let Some(restore) = self.restore.take() {
+            restore();
+        }
+    }
+}
+
+/// Best-effort terminal reset; errors are ignored because this runs while unwinding.
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        PopKeyboardEnhancementFlags,
+        LeaveAlternateScreen,
+        Show
+    );
+}
+
+/// A screen that can be repainted from scratch: the real `Terminal`, or a
+/// recorder in tests.
+pub trait Redraw {
+    fn redraw(&mut self) -> io::Result<()>;
+}
+
+impl<B: Backend<Error = io::Error>> Redraw for Terminal<B> {
+    fn redraw(&mut self) -> io::Result<()> {
+        // Clearing also resets ratatui's last frame, so the next `draw` paints
+        // every cell at the new size instead of diffing against stale ones.
+        self.autoresize()?;
+        self.clear()
+    }
+}
+
+/// Waits for the next terminal event. Key presses are returned; a resize
+/// repaints `screen` right away so nothing stays clipped until the next key.
+pub fn read_key(screen: &mut impl Redraw) -> io::Result<Option<KeyEvent>> {
+    key_or_redraw(event::read()?, screen)
+}
+
+fn key_or_redraw(event: Event, screen: &mut impl Redraw) -> io::Result<Option<KeyEvent>> {
+    match event {
+        Event::Key(key) => Ok(Some(key)),
+        Event::Resize(..) => {
+            screen.redraw()?;
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Redraw, TerminalGuard, key_or_redraw};
+
+    #[derive(Default)]
+    struct RecordingScreen {
+        redraws: usize,
+    }
+
+    impl Redraw for RecordingScreen {
+        fn redraw(&mut self) -> std::io::Result<()> {
+            self.redraws += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn resize_events_redraw_and_keys_pass_through() {
+        let mut screen = RecordingScreen::default();
+        let key = KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE);
+
+        assert_eq!(
+            key_or_redraw(Event::Key(key), &mut screen).unwrap(),
+            Some(key)
+        );
+        assert_eq!(screen.redraws, 0);
+
+        assert_eq!(
+            key_or_redraw(Event::Resize(80, 24), &mut screen).unwrap(),
+            None
+        );
+        assert_eq!(screen.redraws, 1);
+
+        assert_eq!(
+            key_or_redraw(Event::FocusGained, &mut screen).unwrap(),
+            None
+        );
+        assert_eq!(screen.redraws, 1);
+    }
+
+    #[test]
+    fn dropping_the_guard_runs_the_restore_routine_once() {
+        let calls = Cell::new(0);
+        {
+            let _guard = TerminalGuard::new(|| calls.set(calls.get() + 1));
+        }
+        assert_eq!(calls.get(), 1);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = TerminalGuard::new(|| calls.set(calls.get() + 1));
+            panic!("drill blew up");
+        }));
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn disarmed_guard_does_not_restore() {
+        let calls = Cell::new(0);
+        TerminalGuard::new(|| calls.set(calls.get() + 1)).disarm();
+        assert_eq!(calls.get(), 0);
+    }
+}
@@ -1,12 +1,52 @@
+use std::io::IsTerminal;
 use std::path::Path;
 
+use anyhow::{Result, bail};
 use dialoguer::Confirm;
 use dialoguer::theme::ColorfulTheme;
+use once_cell::sync::Lazy;
+
+/// Comma-separated list of file extensions treated as decks, e.g. `md,markdown,mdown`.
+pub const MARKDOWN_EXTENSIONS_ENV: &str = "REPEATER_EXTENSIONS";
+pub const DEFAULT_MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+static MARKDOWN_EXTENSIONS: Lazy<Vec<String>> = Lazy::new(|| {
+    std::env::var(MARKDOWN_EXTENSIONS_ENV)
+        .ok()
+        .map(|raw| parse_extensions(&raw))
+        .filter(|extensions| !extensions.is_empty())
+        .unwrap_or_else(|| {
+            DEFAULT_MARKDOWN_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect()
+        })
+});
+
+/// Extensions accepted as decks: `REPEATER_EXTENSIONS` if set, otherwise `md` and `markdown`.
+pub fn markdown_extensions() -> &'static [String] {
+    &MARKDOWN_EXTENSIONS
+}
+
+pub fn parse_extensions(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
 
 pub fn is_markdown(path: &Path) -> bool {
+    has_extension(path, markdown_extensions())
+}
+
+pub fn has_extension(path: &Path, extensions: &[String]) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
-        .map(|ext| ext.eq_ignore_ascii_case("md"))
+        .map(|ext| {
+            extensions
+                .iter()
+                .any(|allowed| ext.eq_ignore_ascii_case(allowed))
+        })
         .unwrap_or(false)
 }
 
@@ -63,6 +103,24 @@ pub fn strip_controls_and_escapes(input: &str) -> String {
     out.trim().to_string()
 }
 
+/// Whether `ask_yn` can be used: it prints the question to stdout and reads
+/// the answer from the terminal, so both have to be one.
+pub fn can_prompt() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Confirms a destructive change: `yes` (from `--yes`) answers up front,
+/// otherwise the user is asked, and without a terminal to ask on this fails.
+pub fn confirm(prompt: String, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !can_prompt() {
+        bail!("Not running in a terminal; pass --yes to go ahead without confirming");
+    }
+    Ok(ask_yn(prompt))
+}
+
 pub fn ask_yn(prompt: String) -> bool {
     println!("{}", prompt);
     Confirm::with_theme(&ColorfulTheme::default())
@@ -89,6 +147,17 @@ mod tests {
         assert!(!is_markdown(Path::new("test.txt")));
     }
 
+    #[test]
+    fn extensions_are_configurable() {
+        let extensions = parse_extensions(" md, .Markdown ,mdown,,");
+        assert_eq!(extensions, ["md", "markdown", "mdown"]);
+        assert!(has_extension(Path::new("deck.MDOWN"), &extensions));
+        assert!(!has_extension(
+            Path::new("deck.markdown"),
+            &parse_extensions("md")
+        ));
+    }
+
     #[test]
     fn test_pluralize_single() {
         assert_eq!(pluralize("card", 1), "1 card");